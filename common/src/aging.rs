@@ -0,0 +1,69 @@
+use rand::Rng;
+
+use crate::enums::Race;
+
+/// Ages, in years, that shape a race's career
+pub struct AgingCurve {
+    /// Youngest and oldest a new player can be
+    pub debut: (i64, i64),
+    /// Players improve until they reach this age
+    pub prime_start: i64,
+    /// Players decline after this age
+    pub prime_end: i64,
+    /// Nobody plays past this age
+    pub retirement: i64,
+}
+
+pub fn aging_curve(race: Race) -> AgingCurve {
+    match race {
+        Race::Dwarf => AgingCurve { debut: (40, 50), prime_start: 55, prime_end: 75, retirement: 95 },
+        Race::Elf => AgingCurve { debut: (100, 120), prime_start: 125, prime_end: 160, retirement: 200 },
+        Race::Goblin => AgingCurve { debut: (10, 12), prime_start: 13, prime_end: 18, retirement: 22 },
+        Race::Halfling => AgingCurve { debut: (22, 28), prime_start: 30, prime_end: 42, retirement: 55 },
+        Race::Human => AgingCurve { debut: (20, 24), prime_start: 26, prime_end: 32, retirement: 40 },
+        Race::Orc => AgingCurve { debut: (14, 16), prime_start: 18, prime_end: 24, retirement: 30 },
+    }
+}
+
+pub fn debut_age<R: Rng + ?Sized>(rng: &mut R, race: Race) -> i64 {
+    let (youngest, oldest) = aging_curve(race).debut;
+
+    rng.gen_range(youngest..=oldest)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CareerStage {
+    Developing,
+    Prime,
+    Declining,
+}
+
+pub fn career_stage(race: Race, age: i64) -> CareerStage {
+    let curve = aging_curve(race);
+
+    if age < curve.prime_start {
+        CareerStage::Developing
+    } else if age <= curve.prime_end {
+        CareerStage::Prime
+    } else {
+        CareerStage::Declining
+    }
+}
+
+/// Percent chance a player retires this offseason. It climbs from nothing at the end of their
+/// prime to certain at the race's retirement age, and players who kept playing hang on longer
+pub fn retirement_chance(race: Race, age: i64, played: bool) -> i64 {
+    let curve = aging_curve(race);
+
+    if age >= curve.retirement {
+        return 100;
+    }
+
+    if age <= curve.prime_end {
+        return 0;
+    }
+
+    let chance = (age - curve.prime_end) * 100 / (curve.retirement - curve.prime_end);
+
+    if played { chance / 2 } else { chance }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::auth::AuthError;
+use crate::enums::{ApiScope, Role};
+use crate::CurrentUser;
+
+/// Every API key starts with this, which is how they're told apart from session tokens
+pub static API_KEY_PREFIX: &str = "fbk_";
+
+/// API keys carry this instead of a session
+pub static API_KEY_SESSION: &str = "api_key";
+
+/// What the services need to check a key. The users service owns integrations and publishes
+/// their keys here, only a hash of the secret is kept
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: String,
+    /// The integration the key belongs to
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub secret_hash: String,
+}
+
+fn api_key_key(id: &str) -> String {
+    format!("apikey:{}", id)
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A new key id and secret, along with the full key handed to the integration. The full key
+/// is never stored
+pub fn generate_api_key() -> (String, String, String) {
+    let id = Uuid::new_v4().to_simple().to_string();
+    let secret = format!("{}{}", Uuid::new_v4().to_simple(), Uuid::new_v4().to_simple());
+    let key = format!("{}{}_{}", API_KEY_PREFIX, id, secret);
+
+    (id, secret, key)
+}
+
+async fn connection(client: &redis::Client) -> Result<redis::aio::Connection, AuthError> {
+    Ok(client.get_async_std_connection().await?)
+}
+
+pub async fn publish_api_key(client: &redis::Client, api_key: &ApiKey) -> Result<(), AuthError> {
+    let mut con = connection(client).await?;
+    let value = serde_json::to_string(api_key).expect("API keys always serialize");
+
+    let _: () = redis::cmd("SET").arg(api_key_key(&api_key.id)).arg(value).query_async(&mut con).await?;
+
+    Ok(())
+}
+
+pub async fn remove_api_key(client: &redis::Client, id: &str) -> Result<(), AuthError> {
+    let mut con = connection(client).await?;
+
+    let _: () = redis::cmd("DEL").arg(api_key_key(id)).query_async(&mut con).await?;
+
+    Ok(())
+}
+
+/// The integration behind a full key, as long as the key hasn't been revoked
+pub async fn authenticate_api_key(client: &redis::Client, key: &str) -> Result<CurrentUser, AuthError> {
+    let (id, secret) = key
+        .strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('_'))
+        .ok_or(AuthError::InvalidToken)?;
+
+    let mut con = connection(client).await?;
+    let value: Option<String> = redis::cmd("GET").arg(api_key_key(id)).query_async(&mut con).await?;
+
+    let api_key: ApiKey = value
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .ok_or(AuthError::InvalidToken)?;
+
+    if api_key.secret_hash != hash_secret(secret) {
+        return Err(AuthError::InvalidToken)
+    }
+
+    Ok(CurrentUser {
+        id: format!("integration:{}", api_key.id),
+        username: api_key.name,
+        role: Role::Integration,
+        session: API_KEY_SESSION.to_string(),
+        scopes: api_key.scopes,
+    })
+}
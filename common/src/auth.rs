@@ -0,0 +1,205 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+use actix_web::dev::Payload;
+use actix_web::{web::Data, FromRequest, HttpRequest};
+use async_graphql::guard::Guard;
+use async_graphql::{Context, Error, ErrorExtensions, Result};
+use futures::future::LocalBoxFuture;
+
+use crate::api_key::{authenticate_api_key, API_KEY_PREFIX};
+use crate::enums::{ApiScope, Role};
+use crate::{decode_token, get_current_user, CurrentUser};
+
+/// Extension code on every error for a caller that isn't logged in
+pub static UNAUTHENTICATED: &str = "UNAUTHENTICATED";
+/// Extension code on every error for a caller that isn't allowed to do something
+pub static FORBIDDEN: &str = "FORBIDDEN";
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// The request came without a token
+    MissingToken,
+    /// The token is malformed, expired or wasn't signed by us
+    InvalidToken,
+    /// The user logged out, or their session was revoked
+    SessionExpired,
+    /// A refresh token came back after it had been swapped for a new one
+    RefreshTokenReused,
+    /// The session store couldn't be reached
+    Redis(redis::RedisError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "You need to be logged in"),
+            AuthError::InvalidToken => write!(f, "Invalid token"),
+            AuthError::SessionExpired => write!(f, "Your session has ended, log in again"),
+            AuthError::RefreshTokenReused => write!(f, "This session was signed out for your safety, log in again"),
+            AuthError::Redis(err) => write!(f, "Can't reach the session store: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<redis::RedisError> for AuthError {
+    fn from(err: redis::RedisError) -> Self {
+        AuthError::Redis(err)
+    }
+}
+
+impl ErrorExtensions for AuthError {
+    fn extend(&self) -> Error {
+        let code = match self {
+            AuthError::Redis(_) => "INTERNAL_SERVER_ERROR",
+            _ => UNAUTHENTICATED,
+        };
+
+        Error::new(self.to_string()).extend_with(|_, e| e.set("code", code))
+    }
+}
+
+pub fn forbidden(message: &str) -> Error {
+    Error::new(message).extend_with(|_, e| e.set("code", FORBIDDEN))
+}
+
+// TODO: auth disabling is needed for tests. try to reimplement when https://github.com/rust-lang/rust/issues/45599 will be resolved (using cfg(test))
+fn auth_disabled() -> bool {
+    match env::var("DISABLE_AUTH") {
+        Ok(boolean) => bool::from_str(boolean.as_str()).expect("Can't parse bool"),
+        Err(_) => false,
+    }
+}
+
+/// The secret tokens are signed with, registered as app data so [`MaybeUser`] can check them
+#[derive(Clone)]
+pub struct SessionSecret(pub String);
+
+/// The user or integration behind the request's bearer token. `None` for anonymous requests
+/// and for tokens that don't check out, resolvers turn that into an `UNAUTHENTICATED` error
+/// when it matters
+pub struct MaybeUser(pub Option<CurrentUser>);
+
+impl MaybeUser {
+    pub fn into_inner(self) -> Option<CurrentUser> {
+        self.0
+    }
+}
+
+impl FromRequest for MaybeUser {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = bearer_token(req);
+        let secret = req.app_data::<Data<SessionSecret>>().cloned();
+        let redis_client = req.app_data::<Data<redis::Client>>().cloned();
+
+        Box::pin(async move {
+            let current_user = match (token, secret, redis_client) {
+                (Some(token), Some(secret), Some(redis_client)) => {
+                    authenticate(&redis_client, &token, &secret.0).await.ok()
+                },
+                _ => None,
+            };
+
+            Ok(MaybeUser(current_user))
+        })
+    }
+}
+
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|header_value| header_value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Checks an API key, or decodes a token and checks its session
+pub async fn authenticate(client: &redis::Client, token: &str, secret: &str) -> Result<CurrentUser, AuthError> {
+    if token.starts_with(API_KEY_PREFIX) {
+        return authenticate_api_key(client, token).await
+    }
+
+    let token_data = decode_token(token, secret).map_err(|_| AuthError::InvalidToken)?;
+
+    get_current_user(client, &token_data).await
+}
+
+/// The user making the request
+pub fn current_user<'a>(ctx: &'a Context<'_>) -> Result<&'a CurrentUser> {
+    ctx.data_opt::<CurrentUser>().ok_or_else(|| AuthError::MissingToken.extend())
+}
+
+pub fn require_role(ctx: &Context<'_>, role: Role) -> Result<()> {
+    if auth_disabled() {
+        return Ok(())
+    }
+
+    if current_user(ctx)?.role == role {
+        Ok(())
+    } else {
+        Err(forbidden("Forbidden"))
+    }
+}
+
+pub fn require_admin(ctx: &Context<'_>) -> Result<()> {
+    require_role(ctx, Role::Admin)
+}
+
+/// Lets integrations through when their key has the scope, along with admins
+pub fn require_scope(ctx: &Context<'_>, scope: ApiScope) -> Result<()> {
+    if auth_disabled() {
+        return Ok(())
+    }
+
+    if current_user(ctx)?.has_scope(scope) {
+        Ok(())
+    } else {
+        Err(forbidden("Forbidden"))
+    }
+}
+
+/// `#[graphql(guard(RoleGuard(role = "Role::Admin")))]`
+pub struct RoleGuard {
+    pub role: Role,
+}
+
+#[async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        require_role(ctx, self.role)
+    }
+}
+
+/// Admins, or integrations whose API key has the scope
+pub struct ScopeGuard {
+    pub scope: ApiScope,
+}
+
+#[async_trait::async_trait]
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        require_scope(ctx, self.scope)
+    }
+}
+
+/// Lets the owner through, whether of a team or a league, along with admins and the services
+pub fn require_owner(ctx: &Context<'_>, owner_id: &str, message: &str) -> Result<()> {
+    if auth_disabled() {
+        return Ok(())
+    }
+
+    let user = current_user(ctx)?;
+
+    if user.is_admin() || user.id == owner_id {
+        Ok(())
+    } else {
+        Err(forbidden(message))
+    }
+}
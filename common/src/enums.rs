@@ -30,6 +30,48 @@ pub enum Position {
     OutfieldReserve,
 }
 
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            Position::Reserve => write!(f, "Reserve"),
+            Position::StartingPitcher => write!(f, "Starting Pitcher"),
+            Position::ReliefPitcher => write!(f, "Relief Pitcher"),
+            Position::Catcher => write!(f, "Catcher"),
+            Position::ReserveCatcher => write!(f, "Reserve Catcher"),
+            Position::FirstBase => write!(f, "First Base"),
+            Position::SecondBase => write!(f, "Second Base"),
+            Position::ThirdBase => write!(f, "Third Base"),
+            Position::Shortstop => write!(f, "Shortstop"),
+            Position::InfieldReserve => write!(f, "Infield Reserve"),
+            Position::LeftField => write!(f, "Left Field"),
+            Position::CenterField => write!(f, "Center Field"),
+            Position::RightField => write!(f, "Right Field"),
+            Position::OutfieldReserve => write!(f, "Outfield Reserve"),
+        }
+    }
+}
+
+impl fmt::Debug for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            Position::Reserve => write!(f, "RESERVE"),
+            Position::StartingPitcher => write!(f, "STARTING_PITCHER"),
+            Position::ReliefPitcher => write!(f, "RELIEF_PITCHER"),
+            Position::Catcher => write!(f, "CATCHER"),
+            Position::ReserveCatcher => write!(f, "RESERVE_CATCHER"),
+            Position::FirstBase => write!(f, "FIRST_BASE"),
+            Position::SecondBase => write!(f, "SECOND_BASE"),
+            Position::ThirdBase => write!(f, "THIRD_BASE"),
+            Position::Shortstop => write!(f, "SHORTSTOP"),
+            Position::InfieldReserve => write!(f, "INFIELD_RESERVE"),
+            Position::LeftField => write!(f, "LEFT_FIELD"),
+            Position::CenterField => write!(f, "CENTER_FIELD"),
+            Position::RightField => write!(f, "RIGHT_FIELD"),
+            Position::OutfieldReserve => write!(f, "OUTFIELD_RESERVE"),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, EnumIter, EnumCountMacro, PartialEq, Enum, EnumString, Serialize, Deserialize)]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -59,7 +101,7 @@ impl fmt::Debug for Gender {
 impl Distribution<Gender> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Gender {
         match rng.gen_range(0..=6) {
-            0 | 1 | 2 | 3 => Gender::Male,
+            0..=3 => Gender::Male,
             _ => Gender::Female,
         }
     }
@@ -204,9 +246,9 @@ impl fmt::Debug for Handedness {
 impl Distribution<Handedness> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Handedness {
         if rng.gen_range(0..100) > 75 {
-            return Handedness::Left;
+            Handedness::Left
         } else {
-            return Handedness::Right;
+            Handedness::Right
         }
     }
 }
@@ -273,3 +315,86 @@ impl Distribution<Trait> for Standard {
         }
     }
 }
+
+/// How a computer controlled team spends its gold
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ComputerStrategy {
+    /// Always goes after the best player it can afford
+    BestAvailable,
+    /// Fills the thinnest part of the roster first
+    Balanced,
+    /// Looks for the most ability per gold spent
+    Bargain,
+}
+
+#[derive(Copy, Clone, Debug, Eq, EnumIter, PartialEq, Enum, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Ability {
+    Strength,
+    Dexterity,
+    Constitution,
+    Intelligence,
+    Wisdom,
+    Charisma,
+}
+
+/// How much of a player's cost is returned to the team when they are released
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Enum, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RefundPolicy {
+    None,
+    #[default]
+    Half,
+    Full,
+}
+
+impl RefundPolicy {
+    pub fn refund(&self, cost: i64) -> i64 {
+        match self {
+            RefundPolicy::None => 0,
+            RefundPolicy::Half => cost / 2,
+            RefundPolicy::Full => cost,
+        }
+    }
+}
+
+/// Who gets to add a year to a contract when it runs out
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ContractOption {
+    /// The team decides whether to add the extra year
+    Team,
+    /// The player decides whether to add the extra year
+    Player,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Enum, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Role {
+    // Roles were stored lowercase before they were typed
+    #[serde(alias = "admin")]
+    Admin,
+    #[serde(alias = "user")]
+    #[default]
+    User,
+    /// Service accounts calling in with an API key, limited to the key's scopes
+    Integration,
+}
+
+/// The mutations an integration's API key can be allowed to call
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiScope {
+    CreatePlayer,
+    RevealTrait,
+    AwardExperience,
+    SetLeagueStatus,
+    RecordGame,
+}
@@ -91,7 +91,7 @@ pub fn format_filter(data: &Map<String, Value>) -> Map<String, Value> {
         }
 
         if value.is_object() {
-            let value = format_filter(&value.as_object().unwrap());
+            let value = format_filter(value.as_object().unwrap());
             new_value = Value::Object(value.clone());
         }
 
@@ -100,7 +100,7 @@ pub fn format_filter(data: &Map<String, Value>) -> Map<String, Value> {
 
             for item in value.as_array().unwrap().iter() {
                 if item.is_object() {
-                    let v = format_filter(&item.as_object().unwrap());
+                    let v = format_filter(item.as_object().unwrap());
                     new_array.push(Value::Object(v.clone()));
                 }
             }
@@ -114,6 +114,23 @@ pub fn format_filter(data: &Map<String, Value>) -> Map<String, Value> {
     map
 }
 
+/// Whether any key in the filter, at any depth, is one of the keys or a dotted path into one
+pub fn uses_keys(filter: &HashMap<String, Value>, keys: &[&str]) -> bool {
+    fn value_uses(value: &Value, keys: &[&str]) -> bool {
+        match value {
+            Value::Object(map) => map.iter().any(|(key, value)| key_matches(key, keys) || value_uses(value, keys)),
+            Value::Array(items) => items.iter().any(|item| value_uses(item, keys)),
+            _ => false,
+        }
+    }
+
+    fn key_matches(key: &str, keys: &[&str]) -> bool {
+        keys.iter().any(|name| key == *name || key.starts_with(&format!("{}.", name)))
+    }
+
+    filter.iter().any(|(key, value)| key_matches(key, keys) || value_uses(value, keys))
+}
+
 pub fn process_filter(filter: HashMap<String, Value>) -> Result<bson::Document> {
     let filter: Map<String, Value> = filter.into_iter().collect();
     let filter = format_filter(&filter);
@@ -122,3 +139,35 @@ pub fn process_filter(filter: HashMap<String, Value>) -> Result<bson::Document>
     Ok(filter)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filter(value: Value) -> HashMap<String, Value> {
+        value.as_object().unwrap().clone().into_iter().collect()
+    }
+
+    #[test]
+    fn top_level_keys_are_found() {
+        assert!(uses_keys(&filter(json!({ "hiddenTraits": "GREEDY" })), &["hiddenTraits"]));
+        assert!(!uses_keys(&filter(json!({ "traits": "GREEDY" })), &["hiddenTraits"]));
+    }
+
+    #[test]
+    fn dotted_paths_into_a_key_are_found() {
+        assert!(uses_keys(&filter(json!({ "scouting.team": "team" })), &["scouting"]));
+        assert!(!uses_keys(&filter(json!({ "scoutingNotes": "x" })), &["scouting"]));
+    }
+
+    #[test]
+    fn keys_nested_in_operators_are_found() {
+        let nested = filter(json!({
+            "_or": [ { "name": "Ada" }, { "_and": [ { "hiddenTraits": { "_in": ["GREEDY"] } } ] } ],
+        }));
+
+        assert!(uses_keys(&nested, &["hiddenTraits"]));
+        assert!(uses_keys(&filter(json!({ "_where": "true" })), &["_where"]));
+    }
+}
@@ -2,16 +2,30 @@ use chrono::Utc;
 use jsonwebtoken::{DecodingKey, TokenData, Validation};
 use jsonwebtoken::{EncodingKey, Header};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
+use crate::auth::AuthError;
+use crate::enums::{ApiScope, Role};
+use crate::session::get_session;
+
+pub mod aging;
+pub mod api_key;
+pub mod auth;
+pub mod client;
 pub mod enums;
 pub mod filter;
 pub mod meta;
+pub mod morale;
+pub mod pricing;
+pub mod progression;
+pub mod session;
 pub mod structs;
 
 // TODO: Proper error handling
 
-static ONE_WEEK: i64 = 60 * 60 * 24 * 7; // in seconds
+static FIVE_MINUTES: i64 = 60 * 5; // in seconds
+
+/// Service tokens carry this instead of a session, they're never stored in redis
+pub static SERVICE_SESSION: &str = "service";
 
 #[derive(Deserialize, Serialize)]
 pub struct Claims {
@@ -22,30 +36,47 @@ pub struct Claims {
     // data:
     pub id: String,
     pub user: String,
-    pub role: String,
+    pub role: Role,
     pub session: String,
 }
 
+#[derive(Clone, Debug)]
 pub struct CurrentUser {
     pub id: String,
     pub username: String,
-    pub role: String,
+    pub role: Role,
+    pub session: String,
+    /// Only integrations have scopes, the mutations their API key may call
+    pub scopes: Vec<ApiScope>,
+}
+
+impl CurrentUser {
+    /// Admins include the services calling each other
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// Admins can do anything an integration can
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.is_admin() || (self.role == Role::Integration && self.scopes.contains(&scope))
+    }
 }
 
 pub fn generate_token(
     id: &str,
     user: &str,
-    role: &str,
+    role: Role,
     session: &str,
+    lifetime: i64, // in seconds
     secret: &str,
 ) -> String {
-    let now = Utc::now().timestamp_nanos() / 1_000_000_000; // nanosecond -> second
+    let now = Utc::now().timestamp();
     let payload = Claims {
         iat: now,
-        exp: now + ONE_WEEK,
+        exp: now + lifetime,
         id: id.to_string(),
         user: user.to_string(),
-        role: role.to_string(),
+        role,
         session: session.to_string(),
     };
 
@@ -57,15 +88,24 @@ pub fn generate_token(
     .unwrap()
 }
 
-// FIXME: This needs to gracefully send back an error if the session doesn't exist
-pub fn generate_session(
-    con: &mut redis::Connection,
-    user_id: &str,
-) -> String {
-    let session = Uuid::new_v4().to_simple().to_string();
-    set_session(con, user_id, &session);
+/// A short lived admin token one service uses to call another directly
+pub fn generate_service_token(service: &str, secret: &str) -> String {
+    let now = Utc::now().timestamp();
+    let payload = Claims {
+        iat: now,
+        exp: now + FIVE_MINUTES,
+        id: format!("service:{}", service),
+        user: service.to_string(),
+        role: Role::Admin,
+        session: SERVICE_SESSION.to_string(),
+    };
 
-    session
+    jsonwebtoken::encode(
+        &Header::default(),
+        &payload,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
 }
 
 pub fn decode_token(
@@ -73,66 +113,39 @@ pub fn decode_token(
     secret: &str,
 ) -> jsonwebtoken::errors::Result<TokenData<Claims>> {
     jsonwebtoken::decode::<Claims>(
-        &token,
+        token,
         &DecodingKey::from_secret(secret.as_bytes()),
         &Validation::default(),
     )
 }
 
-pub fn set_session(
-    con: &mut redis::Connection,
-    user_id: &str,
-    session: &str,
-) {
-    let _: () = redis::cmd("SET")
-        .arg(user_id)
-        .arg(session)
-        .query(con)
-        .expect("Failed to store the user session in redis");
-}
-
-pub fn get_session(
-    con: &mut redis::Connection,
-    user_id: &str
-) -> String {
-    let session: String = redis::cmd("GET").arg(user_id).query(con)
-        .expect("Failed to retrieve the user session from redis");
-
-    session
-}
-
-pub fn remove_session(
-    con: &mut redis::Connection,
-    user_id: &str
-) {
-    let _: () = redis::cmd("DEL")
-        .arg(user_id)
-        .query(con)
-        .expect("Failed to delete user session in redis");
-}
-
-pub fn verify_token(
-    con: &mut redis::Connection,
+pub async fn verify_token(
+    client: &redis::Client,
     token_data: &TokenData<Claims>,
-) -> Result<(), String> {
-    let stored_session = get_session(con, &token_data.claims.id);
+) -> Result<(), AuthError> {
+    // Only the services hold the secret, so a signed service token needs no session
+    if token_data.claims.session == SERVICE_SESSION {
+        return Ok(())
+    }
 
-    if stored_session == token_data.claims.session {
-        Ok(())
-    } else {
-        Err("Invalid token".to_string())
+    match get_session(client, &token_data.claims.session).await? {
+        Some(session) if session.user == token_data.claims.id => Ok(()),
+        Some(_) => Err(AuthError::InvalidToken),
+        None => Err(AuthError::SessionExpired),
     }
 }
 
-pub fn get_current_user(
-    con: &mut redis::Connection,
+pub async fn get_current_user(
+    client: &redis::Client,
     token_data: &TokenData<Claims>,
-) -> Option<CurrentUser> {
-    verify_token(con, &token_data).expect("Can't verify token");
+) -> Result<CurrentUser, AuthError> {
+    verify_token(client, token_data).await?;
 
-    Some(CurrentUser{
+    Ok(CurrentUser {
         id: token_data.claims.id.clone(),
         username: token_data.claims.user.clone(),
-        role: token_data.claims.role.clone(),
+        role: token_data.claims.role,
+        session: token_data.claims.session.clone(),
+        scopes: Vec::new(),
     })
 }
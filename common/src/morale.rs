@@ -0,0 +1,72 @@
+use crate::enums::Trait;
+
+pub const DEFAULT_MORALE: i64 = 50;
+pub const MAX_MORALE: i64 = 100;
+
+pub fn clamp_morale(morale: i64) -> i64 {
+    morale.clamp(0, MAX_MORALE)
+}
+
+/// What the game simulator adds to a player's rolls, -5 when miserable up to +5 when thrilled
+pub fn morale_modifier(morale: i64) -> i64 {
+    (clamp_morale(morale) - DEFAULT_MORALE) / 10
+}
+
+/// How one game moves a player's morale, given their traits and their teammates'
+pub fn game_morale_change(traits: &[Trait], won: bool, played: bool, teammates: &[Vec<Trait>]) -> i64 {
+    let has = |t: Trait| traits.contains(&t);
+    let teammates_with = |t: Trait| teammates.iter().filter(|other| other.contains(&t)).count() as i64;
+
+    let mut change = if won { 2 } else { -2 };
+
+    if !won && has(Trait::HotTemper) {
+        change -= 2;
+    }
+
+    if played {
+        change += 1;
+    } else if !has(Trait::Lazy) {
+        change -= if has(Trait::Greedy) { 2 } else { 1 };
+    }
+
+    // A loudmouth wears everyone down unless there's a goon around to keep the peace
+    if teammates_with(Trait::Goon) == 0 {
+        change -= teammates_with(Trait::Belligerent);
+    }
+
+    if has(Trait::SuckUp) {
+        change += 1;
+    }
+
+    if has(Trait::Boring) {
+        change /= 2;
+    }
+
+    change
+}
+
+/// A team's chemistry from its players' morale and traits, 0 to 100
+pub fn chemistry(players: &[(i64, Vec<Trait>)]) -> i64 {
+    if players.is_empty() {
+        return DEFAULT_MORALE;
+    }
+
+    let count = |t: Trait| players.iter().filter(|(_, traits)| traits.contains(&t)).count() as i64;
+
+    let average = players.iter().map(|(morale, _)| *morale).sum::<i64>() / players.len() as i64;
+
+    let belligerent = count(Trait::Belligerent);
+    let unchecked = (belligerent - count(Trait::Goon)).max(0);
+
+    let score = average
+        - unchecked * 5
+        - count(Trait::Greedy) * 2
+        - count(Trait::HotTemper) * 2
+        + count(Trait::SuckUp);
+
+    clamp_morale(score)
+}
+
+pub fn chemistry_modifier(chemistry: i64) -> i64 {
+    morale_modifier(chemistry)
+}
@@ -0,0 +1,143 @@
+use crate::aging::{aging_curve, career_stage, CareerStage};
+use crate::enums::{Class, Race, Trait};
+use crate::structs::Abilities;
+
+const PREMIUM_MULT: i64 = 500;
+const SECONDARY_MULT: i64 = 200;
+const NORMAL_MULT: i64 = 100;
+const DUMP_MULT: i64 = 25;
+
+/// What the class pays for each ability score
+pub fn ability_cost(class: Class, abilities: &Abilities) -> i64 {
+    let mut cost: i64 = 0;
+    match class {
+        Class::Bard => {
+            cost += abilities.strength * DUMP_MULT;
+            cost += abilities.dexterity * SECONDARY_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * NORMAL_MULT;
+            cost += abilities.wisdom * NORMAL_MULT;
+            cost += abilities.charisma * PREMIUM_MULT;
+        },
+        Class::Cleric => {
+            cost += abilities.strength * NORMAL_MULT;
+            cost += abilities.dexterity * NORMAL_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * DUMP_MULT;
+            cost += abilities.wisdom * PREMIUM_MULT;
+            cost += abilities.charisma * NORMAL_MULT;
+        },
+        Class::Fighter => {
+            cost += abilities.strength * PREMIUM_MULT;
+            cost += abilities.dexterity * DUMP_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * NORMAL_MULT;
+            cost += abilities.wisdom * NORMAL_MULT;
+            cost += abilities.charisma * NORMAL_MULT;
+        },
+        Class::Paladin => {
+            cost += abilities.strength * PREMIUM_MULT;
+            cost += abilities.dexterity * DUMP_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * DUMP_MULT;
+            cost += abilities.wisdom * DUMP_MULT;
+            cost += abilities.charisma * PREMIUM_MULT;
+        },
+        Class::Ranger => {
+            cost += abilities.strength * DUMP_MULT;
+            cost += abilities.dexterity * PREMIUM_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * NORMAL_MULT;
+            cost += abilities.wisdom * SECONDARY_MULT;
+            cost += abilities.charisma * DUMP_MULT;
+        },
+        Class::Rogue => {
+            cost += abilities.strength * DUMP_MULT;
+            cost += abilities.dexterity * PREMIUM_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * SECONDARY_MULT;
+            cost += abilities.wisdom * NORMAL_MULT;
+            cost += abilities.charisma * NORMAL_MULT;
+        },
+        Class::Wizard => {
+            cost += abilities.strength * DUMP_MULT;
+            cost += abilities.dexterity * SECONDARY_MULT;
+            cost += abilities.constitution * SECONDARY_MULT;
+            cost += abilities.intelligence * PREMIUM_MULT;
+            cost += abilities.wisdom * SECONDARY_MULT;
+            cost += abilities.charisma * DUMP_MULT;
+        },
+    }
+
+    cost
+}
+
+pub fn trait_cost(t: Trait) -> i64 {
+    match t {
+        Trait::Belligerent => -2000,
+        Trait::Boring => 0,
+        Trait::Cleptomaniac => 1000,
+        Trait::Clumsy => -2000,
+        Trait::Dirty => 1000,
+        Trait::Fat => -1000,
+        Trait::Goon => 0,
+        Trait::Greedy => -1000,
+        Trait::HotTemper => -1000,
+        Trait::Lazy => -1000,
+        Trait::LightningArm => 5000,
+        Trait::Lucky => 5000,
+        Trait::Quick => 2000,
+        Trait::QuickWitted => 2000,
+        Trait::SuckUp => 1000,
+        Trait::SureShot => 10000,
+        Trait::Timid => -500,
+        Trait::Tough => 1000,
+        Trait::Switch => 4000,
+    }
+}
+
+/// Percent of their price a player is worth at their age. Young players carry a premium for the
+/// improvement still to come, veterans lose value the closer they get to retiring
+pub fn age_factor(race: Race, age: i64) -> i64 {
+    let curve = aging_curve(race);
+
+    match career_stage(race, age) {
+        CareerStage::Developing => 110,
+        CareerStage::Prime => 100,
+        CareerStage::Declining => {
+            let remaining = (curve.retirement - age).max(0);
+            let decline = curve.retirement - curve.prime_end;
+
+            50 + remaining * 50 / decline.max(1)
+        },
+    }
+}
+
+/// Percent of their price a player is worth for what they've shown on the field, 5% a level
+pub fn performance_factor(level: i64) -> i64 {
+    100 + (level - 1).max(0) * 5
+}
+
+/// A player's price. Only pass the traits everyone can see, the price is public and would give
+/// hidden traits away
+pub fn player_cost(
+    class: Class,
+    race: Race,
+    abilities: &Abilities,
+    traits: &[Trait],
+    age: Option<i64>,
+    level: i64,
+) -> i64 {
+    let mut cost = ability_cost(class, abilities);
+
+    // AGENT COST
+    cost += traits.iter().map(|t| trait_cost(*t)).sum::<i64>();
+
+    if let Some(age) = age {
+        cost = cost * age_factor(race, age) / 100;
+    }
+
+    cost = cost * performance_factor(level) / 100;
+
+    cost.max(0)
+}
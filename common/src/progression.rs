@@ -0,0 +1,91 @@
+use rand::Rng;
+
+use crate::enums::{Ability, Class};
+use crate::structs::Abilities;
+
+pub const MAX_LEVEL: i64 = 20;
+pub const MAX_ABILITY_SCORE: i64 = 20;
+
+/// Experience needed to reach each level, starting at level 1
+const EXPERIENCE_TABLE: [i64; MAX_LEVEL as usize] = [
+    0, 300, 900, 2700, 6500, 14000, 23000, 34000, 48000, 64000,
+    85000, 100000, 120000, 140000, 165000, 195000, 225000, 265000, 305000, 355000,
+];
+
+/// Levels that grant an ability score increase instead of a single point
+const ABILITY_SCORE_IMPROVEMENT_LEVELS: [i64; 5] = [4, 8, 12, 16, 19];
+
+pub fn ability_modifier(score: i64) -> i64 {
+    (score - 10) / 2
+}
+
+pub fn hit_die(class: Class) -> i64 {
+    match class {
+        Class::Bard => 8,
+        Class::Cleric => 8,
+        Class::Fighter => 10,
+        Class::Paladin => 10,
+        Class::Ranger => 10,
+        Class::Rogue => 8,
+        Class::Wizard => 6,
+    }
+}
+
+/// A new player's max health, the full hit die plus their constitution modifier
+pub fn starting_max_health(class: Class, constitution: i64) -> i64 {
+    hit_die(class) + ability_modifier(constitution)
+}
+
+/// Max health gained on a level up, a hit die roll plus the constitution modifier but at least 1
+pub fn roll_max_health<R: Rng + ?Sized>(rng: &mut R, class: Class, constitution: i64) -> i64 {
+    (rng.gen_range(1..=hit_die(class)) + ability_modifier(constitution)).max(1)
+}
+
+/// The class's most and second most valued abilities, matching how players are priced
+pub fn key_abilities(class: Class) -> (Ability, Ability) {
+    match class {
+        Class::Bard => (Ability::Charisma, Ability::Dexterity),
+        Class::Cleric => (Ability::Wisdom, Ability::Constitution),
+        Class::Fighter => (Ability::Strength, Ability::Constitution),
+        Class::Paladin => (Ability::Strength, Ability::Charisma),
+        Class::Ranger => (Ability::Dexterity, Ability::Wisdom),
+        Class::Rogue => (Ability::Dexterity, Ability::Intelligence),
+        Class::Wizard => (Ability::Intelligence, Ability::Constitution),
+    }
+}
+
+pub fn level_for_experience(experience: i64) -> i64 {
+    EXPERIENCE_TABLE.iter()
+        .rposition(|needed| experience >= *needed)
+        .map(|index| index as i64 + 1)
+        .unwrap_or(1)
+}
+
+pub fn experience_for_level(level: i64) -> Option<i64> {
+    if !(1..=MAX_LEVEL).contains(&level) {
+        return None;
+    }
+
+    Some(EXPERIENCE_TABLE[level as usize - 1])
+}
+
+/// Points to add to each ability on reaching `level`. Improvement levels give two points to the
+/// class's key ability, other levels give one, spilling into the second ability at the cap
+pub fn ability_increases(class: Class, abilities: &Abilities, level: i64) -> Vec<(Ability, i64)> {
+    let (primary, secondary) = key_abilities(class);
+
+    let mut points = if ABILITY_SCORE_IMPROVEMENT_LEVELS.contains(&level) { 2 } else { 1 };
+    let mut increases = Vec::<(Ability, i64)>::new();
+
+    for ability in [primary, secondary].iter() {
+        let room = (MAX_ABILITY_SCORE - abilities.get(*ability)).max(0);
+        let amount = points.min(room);
+
+        if amount > 0 {
+            increases.push((*ability, amount));
+            points -= amount;
+        }
+    }
+
+    increases
+}
@@ -0,0 +1,218 @@
+use chrono::Utc;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::AuthError;
+
+/// One login on one device. Each has its own refresh token, so a user can stay logged in on
+/// several devices and sign any of them out on its own
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub id: String,
+    pub user: String,
+    /// The label the device gave itself when logging in
+    pub device: String,
+    /// In seconds, like the token claims
+    pub created_at: i64,
+    /// Updated each time the refresh token is used, so within an access token's lifetime
+    pub last_active: i64,
+    /// The secret half of the current refresh token
+    refresh: String,
+}
+
+impl Session {
+    fn refresh_token(&self) -> String {
+        format!("{}.{}", self.id, self.refresh)
+    }
+}
+
+lazy_static! {
+    /// Swaps in the rotated session only if the stored refresh secret is still the one that was
+    /// presented, revoking the session when it isn't. Returns 1 on success, 0 when the session
+    /// is gone and -1 when the token had already been used
+    static ref ROTATE_REFRESH: redis::Script = redis::Script::new(r#"
+        local value = redis.call('GET', KEYS[1])
+        if not value then
+            return 0
+        end
+
+        if cjson.decode(value).refresh ~= ARGV[1] then
+            redis.call('DEL', KEYS[1])
+            redis.call('SREM', KEYS[2], ARGV[2])
+            return -1
+        end
+
+        redis.call('SET', KEYS[1], ARGV[3], 'EX', ARGV[4])
+        redis.call('SADD', KEYS[2], ARGV[2])
+        redis.call('EXPIRE', KEYS[2], ARGV[4])
+        return 1
+    "#);
+}
+
+fn session_key(id: &str) -> String {
+    format!("session:{}", id)
+}
+
+fn user_sessions_key(user_id: &str) -> String {
+    format!("sessions:{}", user_id)
+}
+
+fn new_secret() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
+
+// actix-web 3 runs on tokio 0.2, the async-std connection brings its own reactor and works there
+async fn connection(client: &redis::Client) -> Result<redis::aio::Connection, AuthError> {
+    Ok(client.get_async_std_connection().await?)
+}
+
+/// Stores the session for `ttl` seconds, every save restarts the clock
+async fn save(con: &mut redis::aio::Connection, session: &Session, ttl: u64) -> Result<(), AuthError> {
+    let value = serde_json::to_string(session).expect("Sessions always serialize");
+    let index = user_sessions_key(&session.user);
+
+    let _: () = redis::pipe()
+        .atomic()
+        .cmd("SET").arg(session_key(&session.id)).arg(value).arg("EX").arg(ttl).ignore()
+        .cmd("SADD").arg(&index).arg(&session.id).ignore()
+        .cmd("EXPIRE").arg(&index).arg(ttl).ignore()
+        .query_async(con)
+        .await?;
+
+    Ok(())
+}
+
+async fn load(con: &mut redis::aio::Connection, id: &str) -> Result<Option<Session>, AuthError> {
+    let value: Option<String> = redis::cmd("GET").arg(session_key(id)).query_async(con).await?;
+
+    Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Starts a session, returning it along with its first refresh token
+pub async fn create_session(
+    client: &redis::Client,
+    user_id: &str,
+    device: &str,
+    ttl: u64,
+) -> Result<(Session, String), AuthError> {
+    let now = Utc::now().timestamp();
+    let session = Session {
+        id: new_secret(),
+        user: user_id.to_string(),
+        device: device.to_string(),
+        created_at: now,
+        last_active: now,
+        refresh: new_secret(),
+    };
+
+    let mut con = connection(client).await?;
+    save(&mut con, &session, ttl).await?;
+
+    let refresh_token = session.refresh_token();
+
+    Ok((session, refresh_token))
+}
+
+/// `None` once the session has been revoked or has gone unused past its lifetime
+pub async fn get_session(client: &redis::Client, id: &str) -> Result<Option<Session>, AuthError> {
+    let mut con = connection(client).await?;
+
+    load(&mut con, id).await
+}
+
+/// Swaps a refresh token for a new one. Each token works once, so when an old one comes back
+/// it has leaked and the whole session is revoked
+pub async fn refresh_session(
+    client: &redis::Client,
+    refresh_token: &str,
+    ttl: u64,
+) -> Result<(Session, String), AuthError> {
+    let (id, secret) = refresh_token.split_once('.').ok_or(AuthError::InvalidToken)?;
+
+    let mut con = connection(client).await?;
+    let mut session = load(&mut con, id).await?.ok_or(AuthError::SessionExpired)?;
+
+    session.refresh = new_secret();
+    session.last_active = Utc::now().timestamp();
+
+    let value = serde_json::to_string(&session).expect("Sessions always serialize");
+
+    // The check and the swap happen together in redis, so two requests racing with the same
+    // token can't both get a new one
+    let rotated: i64 = ROTATE_REFRESH
+        .key(session_key(&session.id))
+        .key(user_sessions_key(&session.user))
+        .arg(secret)
+        .arg(&session.id)
+        .arg(value)
+        .arg(ttl)
+        .invoke_async(&mut con)
+        .await?;
+
+    match rotated {
+        1 => {
+            let refresh_token = session.refresh_token();
+
+            Ok((session, refresh_token))
+        },
+        0 => Err(AuthError::SessionExpired),
+        _ => Err(AuthError::RefreshTokenReused),
+    }
+}
+
+/// Every live session the user has, most recently active first
+pub async fn user_sessions(client: &redis::Client, user_id: &str) -> Result<Vec<Session>, AuthError> {
+    let mut con = connection(client).await?;
+    let index = user_sessions_key(user_id);
+
+    let ids: Vec<String> = redis::cmd("SMEMBERS").arg(&index).query_async(&mut con).await?;
+    let mut sessions = Vec::<Session>::new();
+
+    for id in ids.iter() {
+        match load(&mut con, id).await? {
+            Some(session) => sessions.push(session),
+            // Expired on its own, only the index still remembers it
+            None => redis::cmd("SREM").arg(&index).arg(id).query_async(&mut con).await?,
+        }
+    }
+
+    sessions.sort_by_key(|session| -session.last_active);
+
+    Ok(sessions)
+}
+
+pub async fn remove_session(client: &redis::Client, user_id: &str, id: &str) -> Result<(), AuthError> {
+    let mut con = connection(client).await?;
+
+    let _: () = redis::pipe()
+        .atomic()
+        .cmd("DEL").arg(session_key(id)).ignore()
+        .cmd("SREM").arg(user_sessions_key(user_id)).arg(id).ignore()
+        .query_async(&mut con)
+        .await?;
+
+    Ok(())
+}
+
+/// Logs the user out on every device
+pub async fn remove_user_sessions(client: &redis::Client, user_id: &str) -> Result<(), AuthError> {
+    let mut con = connection(client).await?;
+    let index = user_sessions_key(user_id);
+
+    let ids: Vec<String> = redis::cmd("SMEMBERS").arg(&index).query_async(&mut con).await?;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    for id in ids.iter() {
+        pipe.cmd("DEL").arg(session_key(id)).ignore();
+    }
+
+    pipe.cmd("DEL").arg(&index).ignore();
+
+    let _: () = pipe.query_async(&mut con).await?;
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use crate::enums::Ability;
+
 pub struct Abilities {
     pub strength: i64,
     pub dexterity: i64,
@@ -6,3 +8,27 @@ pub struct Abilities {
     pub wisdom: i64,
     pub charisma: i64,
 }
+
+impl Abilities {
+    pub fn get(&self, ability: Ability) -> i64 {
+        match ability {
+            Ability::Strength => self.strength,
+            Ability::Dexterity => self.dexterity,
+            Ability::Constitution => self.constitution,
+            Ability::Intelligence => self.intelligence,
+            Ability::Wisdom => self.wisdom,
+            Ability::Charisma => self.charisma,
+        }
+    }
+
+    pub fn get_mut(&mut self, ability: Ability) -> &mut i64 {
+        match ability {
+            Ability::Strength => &mut self.strength,
+            Ability::Dexterity => &mut self.dexterity,
+            Ability::Constitution => &mut self.constitution,
+            Ability::Intelligence => &mut self.intelligence,
+            Ability::Wisdom => &mut self.wisdom,
+            Ability::Charisma => &mut self.charisma,
+        }
+    }
+}
@@ -5,7 +5,7 @@ import { useRecoilValue, useSetRecoilState } from "recoil";
 
 import {tokenState} from '../components/App';
 import LeagueDropdown from '../components/LeagueDropdown';
-import { AUTH_TOKEN, MY_ID } from "../constant";
+import { AUTH_TOKEN, MY_ID, REFRESH_TOKEN } from "../constant";
 import { ME } from "../graphql/Auth";
 
 type HeaderLinkProps = {
@@ -31,6 +31,7 @@ function HeaderRightAuth() {
 
   const handleLogout = (event: MouseEvent) => {
     localStorage.removeItem(AUTH_TOKEN);
+    localStorage.removeItem(REFRESH_TOKEN);
     setToken(null);
     client.clearStore();
     navigate("/");
@@ -1,3 +1,4 @@
 export const AUTH_TOKEN = 'AUTH_TOKEN'
+export const REFRESH_TOKEN = 'REFRESH_TOKEN'
 export const SELECTED_LEAGUE = "SELECTED_LEAGUE"
 export const MY_ID = "MY_ID"
\ No newline at end of file
@@ -1,16 +1,74 @@
 import React from 'react';
 import ReactDOM from 'react-dom';
-import { ApolloClient, ApolloLink, ApolloProvider, createHttpLink, gql, InMemoryCache } from '@apollo/client';
+import { ApolloClient, ApolloLink, ApolloProvider, createHttpLink, fromPromise, gql, InMemoryCache } from '@apollo/client';
 import { setContext } from '@apollo/client/link/context';
+import { onError } from '@apollo/client/link/error';
 
 
 import './index.css';
 import App from './components/App';
 import reportWebVitals from './reportWebVitals';
-import { AUTH_TOKEN } from './constant';
+import { AUTH_TOKEN, REFRESH_TOKEN } from './constant';
 import { RecoilRoot } from 'recoil';
 
-const httpLink = createHttpLink({ uri: 'http://localhost:4000/' });
+const GATEWAY = 'http://localhost:4000/';
+
+const httpLink = createHttpLink({ uri: GATEWAY });
+
+const REFRESH_SESSION = `
+mutation RefreshSession($refreshToken: String!) {
+  refreshSession(refreshToken: $refreshToken) {
+    token
+    refreshToken
+  }
+}
+`;
+
+// Refresh tokens only work once, so requests failing together share a single refresh
+let refreshing: Promise<string | null> | null = null;
+
+async function refreshSession(): Promise<string | null> {
+  const refreshToken = localStorage.getItem(REFRESH_TOKEN);
+
+  if (!refreshToken) {
+    return null;
+  }
+
+  const response = await fetch(GATEWAY, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ query: REFRESH_SESSION, variables: { refreshToken } }),
+  });
+  const { data } = await response.json();
+
+  if (!data?.refreshSession) {
+    localStorage.removeItem(AUTH_TOKEN);
+    localStorage.removeItem(REFRESH_TOKEN);
+    return null;
+  }
+
+  localStorage.setItem(AUTH_TOKEN, data.refreshSession.token);
+  localStorage.setItem(REFRESH_TOKEN, data.refreshSession.refreshToken);
+
+  return data.refreshSession.token;
+}
+
+// Access tokens are short lived, get a new one and retry once when the old one has expired
+const refreshLink = onError(({ graphQLErrors, operation, forward }) => {
+  const unauthenticated = graphQLErrors?.some((error) => error.extensions?.code === 'UNAUTHENTICATED');
+
+  if (!unauthenticated || operation.getContext().retried || !localStorage.getItem(REFRESH_TOKEN)) {
+    return;
+  }
+
+  if (!refreshing) {
+    refreshing = refreshSession().finally(() => { refreshing = null; });
+  }
+
+  operation.setContext({ retried: true });
+
+  return fromPromise(refreshing).flatMap(() => forward(operation));
+});
 
 const authLink = setContext((_, { headers }) => {
   // get the authentication token from local storage if it exists
@@ -25,7 +83,7 @@ const authLink = setContext((_, { headers }) => {
 });
 
 const client = new ApolloClient({
-  link: authLink.concat(httpLink),
+  link: ApolloLink.from([refreshLink, authLink, httpLink]),
   cache: new InMemoryCache()
 });
 
@@ -5,7 +5,7 @@ import { useSetRecoilState } from "recoil";
 import { tokenState } from "../components/App";
 
 import Form from "../components/Form";
-import { AUTH_TOKEN } from "../constant";
+import { AUTH_TOKEN, REFRESH_TOKEN } from "../constant";
 
 export const LOGIN = gql`
 mutation Login(
@@ -16,11 +16,37 @@ mutation Login(
       usernameOrEmail: $usernameOrEmail
       password: $password
   }) {
-    id
+    __typename
+    ... on LoginResponse {
+      id
       username
       email
       role
       token
+      refreshToken
+    }
+    ... on TwoFactorChallenge {
+      challenge
+    }
+  }
+}
+`;
+
+export const COMPLETE_LOGIN = gql`
+mutation CompleteLogin(
+  $challenge: String!
+  $code: String!
+) {
+  completeLogin(input: {
+      challenge: $challenge
+      code: $code
+  }) {
+    id
+    username
+    email
+    role
+    token
+    refreshToken
   }
 }
 `;
@@ -31,20 +57,47 @@ export default function Login() {
 
   const [username, setUsername] = useState("");
   const [password, setPassword] = useState("");
+  const [challenge, setChallenge] = useState("");
+  const [code, setCode] = useState("");
 
   const [error, setError] = useState("");
 
+  const startSession = (session: { token: string, refreshToken: string }) => {
+    localStorage.setItem(AUTH_TOKEN, session.token);
+    localStorage.setItem(REFRESH_TOKEN, session.refreshToken);
+    setToken(session.token);
+    navigate('/');
+  };
+
   const [login] = useMutation(LOGIN, {
     variables: {
       usernameOrEmail: username,
       password: password,
     },
     onCompleted: ({ login }) => {
-      localStorage.setItem(AUTH_TOKEN, login.token);
-      setToken(login.token);
-      navigate('/');
+      if (login.__typename === "TwoFactorChallenge") {
+        setError("");
+        setChallenge(login.challenge);
+      } else {
+        startSession(login);
+      }
+    },
+    onError: ({message}) => {
+      setError(message);
+    }
+  })
+
+  // A challenge only gets one code, a wrong one starts the login over
+  const [completeLogin] = useMutation(COMPLETE_LOGIN, {
+    variables: {
+      challenge: challenge,
+      code: code,
+    },
+    onCompleted: ({ completeLogin }) => {
+      startSession(completeLogin);
     },
     onError: ({message}) => {
+      setChallenge("");
       setError(message);
     }
   })
@@ -54,10 +107,23 @@ export default function Login() {
     login();
   };
 
+  const handleCode = (event: FormEvent<HTMLFormElement>) => {
+    event.preventDefault();
+    completeLogin();
+  };
+
+  if (challenge) {
+    return (
+      <Form.FullPage title="Two-factor code" submitLabel="Verify" error={error} onSubmit={handleCode}>
+        <Form.Input type="input" label="Code from your authenticator, or a recovery code" state={setCode} />
+      </Form.FullPage>
+    )
+  }
+
   return (
     <Form.FullPage title="Login" submitLabel="Login" error={error} onSubmit={handleSubmit}>
       <Form.Input type="input" label="Username" state={setUsername} />
       <Form.Input type="password" label="Password" state={setPassword} />
     </Form.FullPage>
   )
-}
\ No newline at end of file
+}
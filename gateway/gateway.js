@@ -6,6 +6,10 @@ class AuthenticatedDataSource extends RemoteGraphQLDataSource {
         if (context.authHeaderValue) {
             request.http.headers.set('Authorization', context.authHeaderValue);
         }
+        // The users service limits logins per client IP
+        if (context.clientIp) {
+            request.http.headers.set('X-Forwarded-For', context.clientIp);
+        }
     }
 }
 
@@ -48,7 +52,8 @@ const server = new ApolloServer({
     gateway,
     subscriptions: false,
     context: ({ req }) => ({
-        authHeaderValue: req.headers.authorization
+        authHeaderValue: req.headers.authorization,
+        clientIp: req.ip
     })
 });
 
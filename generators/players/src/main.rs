@@ -4,15 +4,37 @@ use graphql_client::{reqwest::post_graphql_blocking as post_graphql, GraphQLQuer
 use ::reqwest::blocking::Client;
 use anyhow::Error;
 
-use rand;
 use rand::distributions::{Distribution, Uniform};
 
 use common::enums::{Class, Race, Gender, Handedness, Trait};
+use common::aging::debut_age;
+use common::pricing::player_cost;
+use common::progression::starting_max_health;
 use common::structs::{Abilities};
 
 const SERVER: &str = "http://localhost:4000/";
 const PER_PLAYER: i64 = 64;
 
+/// Creating players needs an admin token or an integration API key with the CREATE_PLAYER and
+/// SET_LEAGUE_STATUS scopes, passed in AUTH_TOKEN
+fn default_headers() -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+
+    headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        reqwest::header::HeaderValue::from_str("application/json").unwrap(),
+    );
+
+    if let Ok(token) = std::env::var("AUTH_TOKEN") {
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token)).expect("Invalid AUTH_TOKEN"),
+        );
+    }
+
+    headers
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "../../schema.graphql",
@@ -117,7 +139,7 @@ fn fetch_name(race: &str, gender: &str) -> Result<String, Error> {
     let response = reqwest::blocking::get(url)?
         .json::<HashMap<String, String>>()?;
 
-    return Ok(response["name"].clone())
+    Ok(response["name"].clone())
 }
 
 fn rand_ability_score() -> u16 {
@@ -133,7 +155,7 @@ fn rand_ability_score() -> u16 {
 
     results.sort();
 
-    return results[3] + results[2] + results[1]
+    results[3] + results[2] + results[1]
 }
 
 fn set_abilities(class: Class, ability_scores: Vec<u16>) -> Abilities {
@@ -240,121 +262,6 @@ fn add_racial_abilities(race: Race, abilities: &mut Abilities) {
     }
 }
 
-fn calc_max_health(class: Class, constitution: i64) -> i64 {
-    let modifier = (constitution - 10) / 2;
-    match class {
-        Class::Bard => 8 + modifier,
-        Class::Cleric => 8 + modifier,
-        Class::Fighter => 10 + modifier,
-        Class::Paladin => 10 + modifier,
-        Class::Ranger => 10 + modifier,
-        Class::Rogue => 8 + modifier,
-        Class::Wizard => 6 + modifier,
-    }
-}
-
-const PREMIUM_MULT: i64 = 500;
-const SECONDARY_MULT: i64 = 200;
-const NORMAL_MULT: i64 = 100;
-const DUMP_MULT: i64 = 25;
-
-fn calc_cost(class: Class, abilities: &Abilities, trait_one: Trait, trait_two: Trait) -> i64 {
-    let mut cost: i64 = 0;
-    match class {
-        Class::Bard => {
-            cost += abilities.strength * DUMP_MULT;
-            cost += abilities.dexterity * SECONDARY_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * NORMAL_MULT;
-            cost += abilities.wisdom * NORMAL_MULT;
-            cost += abilities.charisma * PREMIUM_MULT;
-        },
-        Class::Cleric => {
-            cost += abilities.strength * NORMAL_MULT;
-            cost += abilities.dexterity * NORMAL_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * DUMP_MULT;
-            cost += abilities.wisdom * PREMIUM_MULT;
-            cost += abilities.charisma * NORMAL_MULT;
-        },
-        Class::Fighter => {
-            cost += abilities.strength * PREMIUM_MULT;
-            cost += abilities.dexterity * DUMP_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * NORMAL_MULT;
-            cost += abilities.wisdom * NORMAL_MULT;
-            cost += abilities.charisma * NORMAL_MULT;
-        },
-        Class::Paladin => {
-            cost += abilities.strength * PREMIUM_MULT;
-            cost += abilities.dexterity * DUMP_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * DUMP_MULT;
-            cost += abilities.wisdom * DUMP_MULT;
-            cost += abilities.charisma * PREMIUM_MULT;
-        },
-        Class::Ranger => {
-            cost += abilities.strength * DUMP_MULT;
-            cost += abilities.dexterity * PREMIUM_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * NORMAL_MULT;
-            cost += abilities.wisdom * SECONDARY_MULT;
-            cost += abilities.charisma * DUMP_MULT;
-        },
-        Class::Rogue => {
-            cost += abilities.strength * DUMP_MULT;
-            cost += abilities.dexterity * PREMIUM_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * SECONDARY_MULT;
-            cost += abilities.wisdom * NORMAL_MULT;
-            cost += abilities.charisma * NORMAL_MULT;
-        },
-        Class::Wizard => {
-            cost += abilities.strength * DUMP_MULT;
-            cost += abilities.dexterity * SECONDARY_MULT;
-            cost += abilities.constitution * SECONDARY_MULT;
-            cost += abilities.intelligence * PREMIUM_MULT;
-            cost += abilities.wisdom * SECONDARY_MULT;
-            cost += abilities.charisma * DUMP_MULT;
-        },
-    }
-
-
-    // AGENT COST
-    cost += calc_trait_cost(trait_one);
-    cost += calc_trait_cost(trait_two);
-
-    let mut rng = rand::thread_rng();
-    cost += Uniform::from(0..=2000).sample(&mut rng);
-
-    cost
-}
-
-fn calc_trait_cost(t: Trait) -> i64{
-    match t {
-        Trait::Belligerent => -2000,
-        Trait::Boring => 0,
-        Trait::Cleptomaniac => 1000,
-        Trait::Clumsy => -2000,
-        Trait::Dirty => 1000,
-        Trait::Fat => -1000,
-        Trait::Goon => 0,
-        Trait::Greedy => -1000,
-        Trait::HotTemper => -1000,
-        Trait::Lazy => -1000,
-        Trait::LightningArm => 5000,
-        Trait::Lucky => 5000,
-        Trait::Quick => 2000,
-        Trait::QuickWitted => 2000,
-        Trait::SuckUp => 1000,
-        Trait::SureShot => 10000,
-        Trait::Timid => -500,
-        Trait::Tough => 1000,
-        Trait::Switch => 4000,
-    }
-}
-
-
 fn gen_players_for_league(league_id: &str, count: i64) -> Result<(), Error> {
     for _ in 0..count {
         let race: Race = rand::random();
@@ -385,19 +292,22 @@ fn gen_players_for_league(league_id: &str, count: i64) -> Result<(), Error> {
             let trait_one: Trait = rand::random();
             let trait_two: Trait = rand::random();
 
-            let max_health = calc_max_health(class, abilities.constitution);
+            let max_health = starting_max_health(class, abilities.constitution);
+
+            let age = debut_age(&mut rand::thread_rng(), race);
 
-            let cost = calc_cost(class, &abilities, trait_one, trait_two);
+            let cost = player_cost(class, race, &abilities, &[trait_one, trait_two], Some(age), 1);
 
             let variables = create_player::Variables{
-                name: name,
+                name,
                 league: league_id.to_string(),
                 race: race.into(),
                 class: class.into(),
                 gender: gender.into(),
                 handedness: handedness.into(),
-                max_health: max_health,
-                cost: cost,
+                max_health,
+                cost,
+                age: Some(age),
                 strength: abilities.strength,
                 dexterity: abilities.dexterity,
                 constitution: abilities.constitution,
@@ -426,13 +336,7 @@ fn gen_players_for_league(league_id: &str, count: i64) -> Result<(), Error> {
 
             let client = Client::builder()
                 .user_agent("afb/0.1.0")
-                .default_headers(
-                    std::iter::once((
-                        reqwest::header::CONTENT_TYPE,
-                        reqwest::header::HeaderValue::from_str("application/json").unwrap(),
-                    ))
-                    .collect()
-                )
+                .default_headers(default_headers())
                 .build()?;
 
             //dbg!("{:?}", response);
@@ -442,19 +346,19 @@ fn gen_players_for_league(league_id: &str, count: i64) -> Result<(), Error> {
 
             let response = post_graphql::<CreatePlayer, _>(&client, SERVER, variables)?;
 
-            if !response.errors.is_none() {
+            if response.errors.is_some() {
                 dbg!{"{:?}", response.errors};
             }
 
 
-            let data: create_player::ResponseData = response.data.unwrap();
+            let _data: create_player::ResponseData = response.data.unwrap();
 
             //println!("{:?}", data.create_player.id.to_string());
 
 
 
         } else {
-            dbg!("{:?}", name);
+            eprintln!("Couldn't create {:?}", name);
         }
     }
 
@@ -468,13 +372,7 @@ fn query_leagues() -> Result<leagues::ResponseData, Error> {
 
     let client = Client::builder()
         .user_agent("afb/0.1.0")
-        .default_headers(
-            std::iter::once((
-                reqwest::header::CONTENT_TYPE,
-                reqwest::header::HeaderValue::from_str("application/json").unwrap(),
-            ))
-            .collect()
-        )
+        .default_headers(default_headers())
         .build()?;
 
     let response = post_graphql::<Leagues, _>(&client, SERVER, variables)?;
@@ -484,6 +382,7 @@ fn query_leagues() -> Result<leagues::ResponseData, Error> {
     Ok(data)
 }
 
+#[allow(dead_code)]
 fn set_league_status(id: &str, status: set_league_status::LeagueStatus) -> Result<(), Error> {
     let variables = set_league_status::Variables{
         id: id.to_string(),
@@ -492,18 +391,12 @@ fn set_league_status(id: &str, status: set_league_status::LeagueStatus) -> Resul
 
     let client = Client::builder()
         .user_agent("afb/0.1.0")
-        .default_headers(
-            std::iter::once((
-                reqwest::header::CONTENT_TYPE,
-                reqwest::header::HeaderValue::from_str("application/json").unwrap(),
-            ))
-            .collect()
-        )
+        .default_headers(default_headers())
         .build()?;
 
     let response = post_graphql::<SetLeagueStatus, _>(&client, SERVER, variables)?;
 
-    let data: set_league_status::ResponseData = response.data.expect("missing response data");
+    let _data: set_league_status::ResponseData = response.data.expect("missing response data");
 
     Ok(())
 }
@@ -516,13 +409,15 @@ fn main() {
         Ok(data) => {
             for league in data.leagues.iter() {
                 if league.status == leagues::LeagueStatus::PLAYER_GENERATION {
-                    gen_players_for_league(&league.id, league.max_players * PER_PLAYER);
+                    if let Err(err) = gen_players_for_league(&league.id, league.max_players * PER_PLAYER) {
+                        eprintln!("Couldn't generate players for {}: {:?}", league.id, err);
+                    }
                     //set_league_status(&league.id, set_league_status::LeagueStatus::PLAYERS_COMPLETED);
                 }
             }
         },
         Err(err) => {
-            dbg!{err};
+            eprintln!("Couldn't fetch leagues: {:?}", err);
         }
     }
 }
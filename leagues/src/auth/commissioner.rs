@@ -0,0 +1,15 @@
+use async_graphql::{Context, Result, ID};
+use wither::mongodb::Database;
+
+use common::auth::require_owner;
+
+use crate::models::League;
+
+/// Loads a league the current user runs, as its commissioner or an admin
+pub async fn find_commissioned_league(ctx: &Context<'_>, db: &Database, id: &ID, message: &str) -> Result<League> {
+    let league = League::find_by_id(db, id).await.ok_or("League doesn't exist!")?;
+
+    require_owner(ctx, &league.owner, message)?;
+
+    Ok(league)
+}
@@ -0,0 +1,3 @@
+pub mod commissioner;
+
+pub use commissioner::*;
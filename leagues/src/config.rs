@@ -32,6 +32,24 @@ pub struct ServerConfig {
     pub port: u16,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServicesConfig {
+    pub users: String,
+    pub teams: String,
+    pub players: String,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        ServicesConfig {
+            users: String::from("http://localhost:8000"),
+            teams: String::from("http://localhost:8002"),
+            players: String::from("http://localhost:8003"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
@@ -40,6 +58,8 @@ pub struct Settings {
     pub server: ServerConfig,
     pub redis: RedisConfig,
     pub session: SessionConfig,
+    #[serde(default)]
+    pub services: ServicesConfig,
 }
 
 impl Settings {
@@ -1,13 +1,22 @@
+use crate::auth::find_commissioned_league;
+use async_graphql::guard::Guard;
 use crate::models::*;
-use common::*;
+use crate::services::{
+    age_players, check_rosters, create_computer_manager, create_computer_team,
+    expire_contracts, find_successor, league_deleted, manager_left, prepare_teams, reprice_players,
+    rollover_teams, run_team_payroll, settle_game, sign_free_agents,
+};
+use common::auth::{current_user, RoleGuard, ScopeGuard};
+use common::enums::{ApiScope, ComputerStrategy, RefundPolicy, Role};
 use async_graphql::*;
-use jsonwebtoken::TokenData;
 use strum::IntoEnumIterator;
 use wither::prelude::*;
 use wither::mongodb::Database;
 
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
+
 use common::filter::process_filter;
 use common::meta::MetaSelect;
 
@@ -43,6 +52,42 @@ impl League {
         self.max_players
     }
 
+    async fn designated_hitter(&self) -> bool {
+        self.designated_hitter
+    }
+
+    async fn season(&self) -> i64 {
+        self.season
+    }
+
+    async fn keeper_rules(&self) -> &KeeperRules {
+        &self.keeper_rules
+    }
+
+    async fn keeper_deadline(&self) -> Option<DateTime<Utc>> {
+        self.keeper_rules.deadline.map(|deadline| deadline.0)
+    }
+
+    async fn keepers_open(&self) -> bool {
+        self.keeper_rules.is_open()
+    }
+
+    async fn payroll_rules(&self) -> &PayrollRules {
+        &self.payroll_rules
+    }
+
+    async fn last_payroll(&self) -> Option<DateTime<Utc>> {
+        self.payroll_rules.last_payroll.map(|last| last.0)
+    }
+
+    async fn economy_rules(&self) -> &EconomyRules {
+        &self.economy_rules
+    }
+
+    async fn release_refund(&self) -> RefundPolicy {
+        self.release_refund
+    }
+
     async fn state(&self) -> LeagueState {
         self.state
     }
@@ -71,6 +116,7 @@ pub struct User {
     pub id: ID,
 }
 
+#[allow(dead_code)]
 pub struct Team {
     pub id: ID,
 }
@@ -185,107 +231,210 @@ pub struct Mutation;
 impl Mutation {
     async fn create_league(&self, ctx: &Context<'_>, input: CreateLeagueInput) -> Result<League, Error> {
         let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-        let redis_client: &redis::Client = ctx.data()?;
-
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
-
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        let mut new_league = League::new_league(
+            &input.name,
+            &input.description,
+            input.public,
+            input.password,
+            input.max_players,
+            input.designated_hitter.unwrap_or(false),
+            input.manual_state,
+            &current_user.id,
+        );
 
-        if let Some(current_user) = maybe_current_user {
-            let mut new_league = League::new_league(
-                &input.name,
-                &input.description,
-                input.public,
-                input.password,
-                input.max_players,
-                input.manual_state,
-                &current_user.id,
-            );
+        new_league.save(db, None).await?;
 
-            new_league.save(&db, None).await?;
+        Ok(new_league)
 
+        /*if let Ok(_) = new_league.save(&db, None).await {
             Ok(new_league)
-
-            /*if let Ok(_) = new_league.save(&db, None).await {
-                Ok(new_league)
-            } else {
-                Err(Error::new("Can't create league user"))
-            }*/
         } else {
-            Err("Can't create league".into())
-        }
+            Err(Error::new("Can't create league user"))
+        }*/
     }
 
     async fn update_league(&self, ctx: &Context<'_>, input: UpdateLeagueInput) -> Result<League, Error> {
         let db: &Database = ctx.data()?;
 
-        let redis_client: &redis::Client = ctx.data()?;
+        let league = find_commissioned_league(ctx, db, &input.id, "Can't update a league you don't own").await?;
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        if input.state == Some(LeagueState::SeasonStart) {
+            check_rosters(&league).await?;
+        }
 
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        League::update(db, &input.id.clone(), input).await?;
 
-        if let Some(current_user) = maybe_current_user {
-            let maybe_league = League::find_by_id(&db, &input.id.clone()).await;
+        Ok(league)
+    }
 
-            if let Some(league) = maybe_league {
-                if current_user.id != league.owner {
-                    return Err("Can't update a league you don't own".into())
-                }
+    async fn join_league(&self, ctx: &Context<'_>, id: ID) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-                League::update(&db, &input.id.clone(), input).await?;
+        let league = League::add_manager(db, id.to_string(), current_user.id.clone()).await?;
 
-                Ok(league)
-            } else {
-                Err("League doesn't exist!".into())
-            }
-        } else {
-            Err("Unable to update league".into())
-        }
+        Ok(league)
     }
 
-    async fn join_league(&self, ctx: &Context<'_>, id: ID) -> Result<League, Error> {
+    /// Fills an open spot in the league with a computer manager and its team
+    async fn add_computer_manager(
+        &self, ctx: &Context<'_>,
+        league_id: ID,
+        name: String,
+        strategy: ComputerStrategy,
+    ) -> Result<League, Error> {
         let db: &Database = ctx.data()?;
 
-        let redis_client: &redis::Client = ctx.data()?;
+        let league = find_commissioned_league(ctx, db, &league_id, "Only the commissioner can add computer managers").await?;
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        if league.managers.len() as i64 >= league.max_players {
+            return Err("League is full".into())
+        }
 
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        let manager_id = create_computer_manager(&name).await?;
+        let league = League::add_manager(db, league_id.to_string(), manager_id.clone()).await?;
 
-        if let Some(current_user) = maybe_current_user {
-            let league = League::add_manager(&db, id.to_string(), current_user.id.clone()).await?;
+        create_computer_team(&name, &league_id, &manager_id, strategy).await?;
 
-            Ok(league)
-        } else {
-            Err("Unable to join league".into())
-        }
+        Ok(league)
     }
 
     async fn set_league_state(&self, ctx: &Context<'_>, id: ID, state: LeagueState) -> Result<League, Error> {
         let db: &Database = ctx.data()?;
 
-        if let Ok(league) = League::set_league_state(&db, id.to_string(), state).await {
+        let league = find_commissioned_league(ctx, db, &id, "Only the commissioner can change the league state").await?;
+
+        if state == LeagueState::SeasonStart {
+            check_rosters(&league).await?;
+        }
+
+        if let Ok(league) = League::set_league_state(db, id.to_string(), state).await {
             Ok(league)
         } else {
             Err("Cannot update league state".into())
         }
     }
 
+    /// Applies keeper or dynasty rules to every team and opens the next season's draft
+    async fn rollover_season(&self, ctx: &Context<'_>, id: ID) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+
+        let league = find_commissioned_league(ctx, db, &id, "Only the commissioner can start a new season").await?;
+
+        if league.state != LeagueState::SeasonEnd {
+            return Err("The season hasn't ended yet".into())
+        }
+
+        // Every step is keyed on the season being started, so retrying after a failure
+        // picks up where the last attempt stopped instead of repeating it
+        let season = League::begin_rollover(db, id.to_string()).await?;
+
+        // Retirements and free agency come first so departed keepers aren't charged for
+        age_players(&league, season).await?;
+        expire_contracts(&league, season).await?;
+        rollover_teams(&league, season).await?;
+        // Everyone is a year older, so their prices follow
+        reprice_players(&league).await?;
+
+        let league = League::rollover(db, id.to_string()).await?;
+
+        Ok(league)
+    }
+
+    /// Free agents sign with the best offer that meets their asking price
+    async fn sign_free_agents(&self, ctx: &Context<'_>, id: ID) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+
+        let league = find_commissioned_league(ctx, db, &id, "Only the commissioner can run free agency").await?;
+
+        sign_free_agents(&league).await?;
+
+        Ok(league)
+    }
+
+    /// Brings every player's price up to date, called by the scheduler between game days
+    async fn reprice_players(&self, ctx: &Context<'_>, id: ID) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+
+        let league = find_commissioned_league(ctx, db, &id, "Only the commissioner can reprice players").await?;
+
+        reprice_players(&league).await?;
+
+        Ok(league)
+    }
+
+    /// Pays every team's players, called by the scheduler each game day
+    async fn run_payroll(&self, ctx: &Context<'_>, id: ID) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+
+        let league = find_commissioned_league(ctx, db, &id, "Only the commissioner can run payroll").await?;
+
+        if league.state != LeagueState::SeasonStart && league.state != LeagueState::Playoffs
+            && league.state != LeagueState::RealmSeries {
+            return Err("Payroll only runs during the season".into())
+        }
+
+        if !league.payroll_rules.is_due() {
+            return Ok(league)
+        }
+
+        run_team_payroll(&league).await?;
+
+        let league = League::payroll_paid(db, id.to_string()).await?;
+
+        Ok(league)
+    }
+
+    /// Called by the game simulator before it plays a game, so auto managed teams are set
+    /// up and both teams are legal to take the field
+    #[graphql(guard(ScopeGuard(scope = "ApiScope::RecordGame")))]
+    async fn prepare_game(&self, ctx: &Context<'_>, id: ID, home: ID, away: ID) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+
+        let league = League::find_by_id(db, &id).await.ok_or("League doesn't exist!")?;
+
+        if !matches!(league.state, LeagueState::SeasonStart | LeagueState::Playoffs | LeagueState::RealmSeries) {
+            return Err("Games are only played during the season".into())
+        }
+
+        prepare_teams(&home, &away).await?;
+
+        Ok(league)
+    }
+
+    /// Settles a simulated game, posting each team's income with the league's rates. Results
+    /// come from the game simulator, not from managers
+    #[graphql(guard(ScopeGuard(scope = "ApiScope::RecordGame")))]
+    async fn record_game(&self, ctx: &Context<'_>, id: ID, result: GameResultInput) -> Result<League, Error> {
+        let db: &Database = ctx.data()?;
+
+        let league = League::find_by_id(db, &id).await.ok_or("League doesn't exist!")?;
+
+        let playoff = match league.state {
+            LeagueState::SeasonStart => false,
+            LeagueState::Playoffs | LeagueState::RealmSeries => true,
+            _ => return Err("Games are only played during the season".into()),
+        };
+
+        settle_game(&league, &result, playoff).await?;
+
+        League::game_recorded(db, id.to_string()).await
+    }
+
+    #[graphql(guard(ScopeGuard(scope = "ApiScope::SetLeagueStatus")))]
     async fn set_league_status(&self, ctx: &Context<'_>, id: ID, status: LeagueStatus) -> Result<League, Error> {
         let db: &Database = ctx.data()?;
 
-        if let Ok(league) = League::set_league_status(&db, id.to_string(), status).await {
+        if let Ok(league) = League::set_league_status(db, id.to_string(), status).await {
             Ok(league)
         } else {
             Err("Cannot update league status".into())
         }
     }
 
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
     async fn add_manager_to_league(
         &self, ctx: &Context<'_>,
         league_id: ID,
@@ -299,6 +448,53 @@ impl Mutation {
             Err("Cannot insert user into league".into())
         }
     }
+
+    /// Called by the users service when an account is deleted. Leagues that have started keep
+    /// the user's team, run by the computer, so their history holds together. Ones that
+    /// haven't drop the user and their team. Leagues the user ran go to another manager, or
+    /// are deleted when nothing has been played in them. Returns the leagues that remain
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn user_deleted(&self, ctx: &Context<'_>, user: ID) -> Result<Vec<League>> {
+        let db: &Database = ctx.data()?;
+
+        let leagues = League::find_by_member_id(db, &user).await?;
+        let mut remaining = Vec::<League>::new();
+
+        for mut league in leagues {
+            let league_id = league.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+            let started = league.has_started();
+
+            manager_left(&league_id, &user, started).await?;
+
+            if !started {
+                league.managers.retain(|manager| manager != user.as_str());
+            }
+
+            if league.owner == user.as_str() {
+                let others: Vec<String> = league.managers.iter()
+                    .filter(|manager| manager.as_str() != user.as_str())
+                    .cloned()
+                    .collect();
+
+                match find_successor(&others).await? {
+                    Some(successor) => league.owner = successor,
+                    None if !started => {
+                        league_deleted(&league_id).await?;
+                        league.delete(db).await?;
+
+                        continue
+                    },
+                    // Nobody to take over, the league stays with the scrubbed account
+                    None => (),
+                }
+            }
+
+            league.save(db, None).await?;
+            remaining.push(league);
+        }
+
+        Ok(remaining)
+    }
 }
 
 
@@ -310,6 +506,7 @@ pub struct CreateLeagueInput {
     pub public: bool,
     pub password: Option<String>,
     pub max_players: i64,
+    pub designated_hitter: Option<bool>,
     pub manual_state: bool,
 }
 
@@ -320,6 +517,35 @@ pub struct UpdateLeagueInput {
     pub public: Option<bool>,
     pub password: Option<String>,
     pub max_players: Option<i64>,
+    pub designated_hitter: Option<bool>,
+    pub keepers_per_team: Option<i64>,
+    pub keeper_cost_inflation: Option<i64>,
+    pub keeper_deadline: Option<DateTime<Utc>>,
+    pub dynasty: Option<bool>,
+    pub salary_rate: Option<i64>,
+    pub pay_period: Option<PayPeriod>,
+    /// Zero or less removes the cap
+    pub salary_cap: Option<i64>,
+    pub missed_payroll_fine: Option<i64>,
+    pub release_refund: Option<RefundPolicy>,
+    pub economy: Option<EconomyRulesInput>,
     pub state: Option<LeagueState>,
-    pub status: Option<LeagueStatus>,
+}
+
+#[derive(Clone, InputObject)]
+pub struct EconomyRulesInput {
+    pub ticket_price: Option<i64>,
+    pub base_attendance: Option<i64>,
+    pub attendance_per_home_win: Option<i64>,
+    pub playoff_bonus: Option<i64>,
+    pub merchandise_rate: Option<i64>,
+}
+
+#[derive(Clone, InputObject)]
+pub struct GameResultInput {
+    pub game: ID,
+    pub home: ID,
+    pub away: ID,
+    pub home_score: i64,
+    pub away_score: i64,
 }
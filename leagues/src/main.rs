@@ -1,16 +1,19 @@
 #[macro_use]
 extern crate log;
 
+mod auth;
 mod config;
 mod graphql;
 mod models;
 mod routes;
+mod services;
 
 use crate::config::CONFIG;
 use crate::graphql::{AppSchema, Mutation, Query};
 use crate::models::League;
 use crate::routes::index;
 
+use common::auth::SessionSecret;
 use actix_web::{middleware, web, App, HttpServer};
 use async_graphql::{
     extensions::{apollo_persisted_queries::ApolloPersistedQueries, ApolloTracing, Logger, apollo_persisted_queries::LruCacheStorage},
@@ -74,6 +77,8 @@ async fn main() -> std::io::Result<()> {
     println!("called main()");
     init_logger();
 
+    common::client::set_service_credentials("leagues", &CONFIG.session.secret);
+
     let db = init_db().await;
     let redis = init_redis().await;
     let schema = init_graphql(&db, &redis);
@@ -82,6 +87,8 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(middleware::Logger::default())
             .data(db.clone())
+            .data(redis.clone())
+            .data(SessionSecret(CONFIG.session.secret.clone()))
             .data(schema.clone())
             .service(
                 web::resource("/").route(web::post().to(index))
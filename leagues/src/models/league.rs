@@ -1,12 +1,15 @@
 use std::fmt;
 
 use async_graphql::*;
+use chrono::{Duration, Utc};
 use futures::stream::TryStreamExt;
 use strum_macros::{EnumIter, EnumString};
 use serde::{Deserialize, Serialize};
 use wither::prelude::*;
 use wither::{bson::{doc, oid::ObjectId, Document}, bson, mongodb::Database};
 
+use common::enums::RefundPolicy;
+
 use crate::graphql::UpdateLeagueInput;
 
 #[derive(Copy, Clone, Debug, Eq, EnumIter, PartialEq, Enum, EnumString, Serialize, Deserialize)]
@@ -76,6 +79,10 @@ pub struct League {
     pub password: Option<String>,
     pub max_players: i64,
 
+    /// Pitchers don't bat when a designated hitter is used
+    #[serde(default)]
+    pub designated_hitter: bool,
+
     pub state: LeagueState,
     pub manual_state: bool,
 
@@ -93,15 +100,174 @@ pub struct League {
     pub games_per_season: Option<i64>,
     pub playoff_rounds: Option<i64>,
     pub realm_series_games: Option<i64>,
+
+    /// Seasons completed, bumped on each rollover
+    #[serde(default)]
+    pub season: i64,
+    /// The season a rollover in progress is moving into, kept until it finishes so every
+    /// step of a retried rollover is keyed on the same season
+    #[serde(default)]
+    pub rolling_over: Option<i64>,
+
+    // Keepers
+    #[serde(default)]
+    pub keeper_rules: KeeperRules,
+
+    // Payroll
+    #[serde(default)]
+    pub payroll_rules: PayrollRules,
+    /// How much of a player's cost a team gets back when it releases them
+    #[serde(default)]
+    pub release_refund: RefundPolicy,
+
+    // Income
+    #[serde(default)]
+    pub economy_rules: EconomyRules,
+}
+
+/// How much gold teams earn from their games
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EconomyRules {
+    /// Gold per fan at a home game
+    pub ticket_price: i64,
+    /// Percent of the ballpark that fills up before any home wins
+    pub base_attendance: i64,
+    /// Extra percent of the ballpark that fills up for every home win
+    pub attendance_per_home_win: i64,
+    /// Paid to the winner of a playoff game
+    pub playoff_bonus: i64,
+    /// Gold per point of charisma a star Bard or Paladin has above 15, per game
+    pub merchandise_rate: i64,
+}
+
+impl EconomyRules {
+    pub fn check(&self) -> Result<()> {
+        check_rule("Ticket price", self.ticket_price, 0, 100)?;
+        check_rule("Base attendance", self.base_attendance, 0, 100)?;
+        check_rule("Attendance per home win", self.attendance_per_home_win, 0, 100)?;
+        check_rule("Playoff bonus", self.playoff_bonus, 0, 1_000_000)?;
+        check_rule("Merchandise rate", self.merchandise_rate, 0, 10_000)
+    }
+}
+
+/// Keeps commissioners from setting rules that break the league's economy
+fn check_rule(name: &str, value: i64, min: i64, max: i64) -> Result<()> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{} must be between {} and {}", name, min, max).into())
+    }
+}
+
+impl Default for EconomyRules {
+    fn default() -> Self {
+        EconomyRules {
+            ticket_price: 2,
+            base_attendance: 40,
+            attendance_per_home_win: 2,
+            playoff_bonus: 10000,
+            merchandise_rate: 100,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Enum, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PayPeriod {
+    GameDay,
+    #[default]
+    Week,
+}
+
+/// How teams pay their players during the season
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct PayrollRules {
+    /// Percent of a player's cost paid out each pay period
+    pub salary_rate: i64,
+    pub pay_period: PayPeriod,
+    /// Total salary a team may pay per period, anything above is taxed
+    pub salary_cap: Option<i64>,
+    /// Charged on top when a team can't cover its payroll
+    pub missed_payroll_fine: i64,
+    #[graphql(skip)]
+    pub last_payroll: Option<bson::DateTime>,
+    /// When the league last recorded a game
+    #[graphql(skip)]
+    #[serde(default)]
+    pub last_game: Option<bson::DateTime>,
+}
+
+impl PayrollRules {
+    pub fn check(&self) -> Result<()> {
+        check_rule("Salary rate", self.salary_rate, 0, 100)?;
+        check_rule("Missed payroll fine", self.missed_payroll_fine, 0, 1_000_000)?;
+
+        match self.salary_cap {
+            Some(cap) => check_rule("Salary cap", cap, 1, 100_000_000),
+            None => Ok(()),
+        }
+    }
+
+    /// Game day payroll runs once for each batch of games recorded since the last one,
+    /// weekly payroll at most once a week
+    pub fn is_due(&self) -> bool {
+        match (self.pay_period, &self.last_payroll) {
+            (PayPeriod::GameDay, None) => self.last_game.is_some(),
+            (PayPeriod::GameDay, Some(last)) => match &self.last_game {
+                Some(game) => game.0 > last.0,
+                None => false,
+            },
+            (PayPeriod::Week, None) => true,
+            (PayPeriod::Week, Some(last)) => Utc::now() - last.0 >= Duration::days(7),
+        }
+    }
+}
+
+/// What carries over between seasons
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct KeeperRules {
+    /// Players each team may keep at rollover
+    pub keepers_per_team: i64,
+    /// Percent a keeper's cost goes up for every year they're kept
+    pub cost_inflation: i64,
+    #[graphql(skip)]
+    pub deadline: Option<bson::DateTime>,
+    /// Rosters carry over whole and keeper limits don't apply
+    pub dynasty: bool,
+}
+
+impl KeeperRules {
+    pub fn check(&self) -> Result<()> {
+        check_rule("Keepers per team", self.keepers_per_team, 0, 50)?;
+        check_rule("Keeper cost inflation", self.cost_inflation, 0, 100)
+    }
+
+    /// Keepers can be chosen until the deadline passes
+    pub fn is_open(&self) -> bool {
+        match &self.deadline {
+            Some(deadline) => Utc::now() < deadline.0,
+            None => true,
+        }
+    }
 }
 
 impl League {
+    /// Once a draft has happened the league has history worth keeping
+    pub fn has_started(&self) -> bool {
+        self.season > 0 || !matches!(self.state, LeagueState::Created | LeagueState::Manual)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn new_league(
         name: &str,
         description: &str,
         public: bool,
         password: Option<String>,
         max_players: i64,
+        designated_hitter: bool,
         manual_state: bool,
         owner_id: &str,
     ) -> Self {
@@ -126,6 +292,12 @@ impl League {
             games_per_season: None,
             playoff_rounds: None,
             realm_series_games: None,
+            season: 0,
+            rolling_over: None,
+            keeper_rules: KeeperRules::default(),
+            payroll_rules: PayrollRules::default(),
+            release_refund: RefundPolicy::default(),
+            economy_rules: EconomyRules::default(),
             status: LeagueStatus::PlayerGeneration,
             state,
             manual_state,
@@ -133,11 +305,12 @@ impl League {
             public,
             managers,
             max_players,
+            designated_hitter,
         }
     }
 
     pub async fn find_all(db: &Database, filter: Option<Document>) -> Result<Vec::<Self>> {
-        let cursor = League::find(&db, filter, None).await?;
+        let cursor = League::find(db, filter, None).await?;
         let leagues: Vec<League> = cursor.try_collect().await?;
 
         Ok(leagues)
@@ -145,7 +318,7 @@ impl League {
 
     pub async fn find_by_id(db: &Database, id: &ID) -> Option<Self> {
         let oid = ObjectId::with_string(id).expect("Can't get id from String");
-        League::find_one(&db, doc! { "_id": oid }, None).await.unwrap()
+        League::find_one(db, doc! { "_id": oid }, None).await.unwrap()
     }
 
     /* pub async fn find_by_name(db: &Database, name: &str) -> Option<Self> {
@@ -156,7 +329,17 @@ impl League {
 
     pub async fn find_by_user_id(db: &Database, user_id: &str) -> Result<Vec::<Self>> {
         //let oid = ObjectId::with_string(&user_id).expect("Can't get id from String");
-        let cursor = League::find(&db, doc! {"managers": user_id }, None).await?;
+        let cursor = League::find(db, doc! {"managers": user_id }, None).await?;
+
+        let leagues: Vec<League> = cursor.try_collect().await?;
+
+        Ok(leagues)
+    }
+
+    /// Leagues the user manages a team in or runs
+    pub async fn find_by_member_id(db: &Database, user_id: &str) -> Result<Vec::<Self>> {
+        let filter = doc! { "$or": [{ "managers": user_id }, { "owner": user_id }] };
+        let cursor = League::find(db, filter, None).await?;
 
         let leagues: Vec<League> = cursor.try_collect().await?;
 
@@ -165,7 +348,7 @@ impl League {
 
     pub async fn find_by_owner_id(db: &Database, owner_id: &str) -> Result<Vec::<Self>> {
         //let oid = ObjectId::with_string(&owner_id).expect("Can't get id from String");
-        let cursor = League::find(&db, doc! {"ownerId": owner_id }, None).await?;
+        let cursor = League::find(db, doc! {"ownerId": owner_id }, None).await?;
 
         let leagues: Vec<League> = cursor.try_collect().await?;
 
@@ -174,7 +357,7 @@ impl League {
 
     pub async fn update(db: &Database, id: &ID, input: UpdateLeagueInput) -> Result<Self> {
         let query = doc! {
-            "_id": ObjectId::with_string(&id)?
+            "_id": ObjectId::with_string(id)?
         };
 
         if let Some(mut league) = League::find_one(db, Some(query), None).await? {
@@ -196,14 +379,78 @@ impl League {
                 league.public = public;
             }
 
-            if let Some(status) = input.status {
-                league.status = status;
-            }
-
             if let Some(max_players) = input.max_players {
                 league.max_players = max_players;
             }
 
+            if let Some(designated_hitter) = input.designated_hitter {
+                league.designated_hitter = designated_hitter;
+            }
+
+            if let Some(keepers_per_team) = input.keepers_per_team {
+                league.keeper_rules.keepers_per_team = keepers_per_team;
+            }
+
+            if let Some(cost_inflation) = input.keeper_cost_inflation {
+                league.keeper_rules.cost_inflation = cost_inflation;
+            }
+
+            if let Some(deadline) = input.keeper_deadline {
+                league.keeper_rules.deadline = Some(bson::DateTime::from(deadline));
+            }
+
+            if let Some(dynasty) = input.dynasty {
+                league.keeper_rules.dynasty = dynasty;
+            }
+
+            if let Some(salary_rate) = input.salary_rate {
+                league.payroll_rules.salary_rate = salary_rate;
+            }
+
+            if let Some(pay_period) = input.pay_period {
+                league.payroll_rules.pay_period = pay_period;
+            }
+
+            if let Some(salary_cap) = input.salary_cap {
+                league.payroll_rules.salary_cap = if salary_cap > 0 { Some(salary_cap) } else { None };
+            }
+
+            if let Some(fine) = input.missed_payroll_fine {
+                league.payroll_rules.missed_payroll_fine = fine;
+            }
+
+            if let Some(release_refund) = input.release_refund {
+                league.release_refund = release_refund;
+            }
+
+            if let Some(economy) = input.economy {
+                let rules = &mut league.economy_rules;
+
+                if let Some(ticket_price) = economy.ticket_price {
+                    rules.ticket_price = ticket_price;
+                }
+
+                if let Some(base_attendance) = economy.base_attendance {
+                    rules.base_attendance = base_attendance;
+                }
+
+                if let Some(attendance_per_home_win) = economy.attendance_per_home_win {
+                    rules.attendance_per_home_win = attendance_per_home_win;
+                }
+
+                if let Some(playoff_bonus) = economy.playoff_bonus {
+                    rules.playoff_bonus = playoff_bonus;
+                }
+
+                if let Some(merchandise_rate) = economy.merchandise_rate {
+                    rules.merchandise_rate = merchandise_rate;
+                }
+            }
+
+            league.keeper_rules.check()?;
+            league.payroll_rules.check()?;
+            league.economy_rules.check()?;
+
             league.save(db, None).await?;
 
             Ok(league)
@@ -245,6 +492,90 @@ impl League {
         }
     }
 
+    /// Marks the league as rolling over into its next season, returning the season every
+    /// rollover step should be keyed on. A retry picks up the season already marked
+    pub async fn begin_rollover(db: &Database, id: String) -> Result<i64> {
+        let query = doc! {
+            "_id": ObjectId::with_string(&id)?
+        };
+
+        if let Some(mut league) = League::find_one(db, Some(query), None).await? {
+            if league.state != LeagueState::SeasonEnd {
+                return Err("The season hasn't ended yet".into())
+            }
+
+            if let Some(season) = league.rolling_over {
+                return Ok(season)
+            }
+
+            let season = league.season + 1;
+
+            league.rolling_over = Some(season);
+
+            league.save(db, None).await?;
+
+            Ok(season)
+        } else {
+            Err(format!("League with id: {:?} not found", &id).into())
+        }
+    }
+
+    /// Starts the next season once the current one has ended
+    pub async fn rollover(db: &Database, id: String) -> Result<Self> {
+        let query = doc! {
+            "_id": ObjectId::with_string(&id)?
+        };
+
+        if let Some(mut league) = League::find_one(db, Some(query), None).await? {
+            if league.state != LeagueState::SeasonEnd {
+                return Err("The season hasn't ended yet".into())
+            }
+
+            league.season = league.rolling_over.take().unwrap_or(league.season + 1);
+            league.state = LeagueState::Drafting;
+            league.keeper_rules.deadline = None;
+
+            league.save(db, None).await?;
+
+            Ok(league)
+        } else {
+            Err(format!("League with id: {:?} not found", &id).into())
+        }
+    }
+
+    pub async fn payroll_paid(db: &Database, id: String) -> Result<Self> {
+        let query = doc! {
+            "_id": ObjectId::with_string(&id)?
+        };
+
+        if let Some(mut league) = League::find_one(db, Some(query), None).await? {
+            league.payroll_rules.last_payroll = Some(bson::DateTime::from(Utc::now()));
+
+            league.save(db, None).await?;
+
+            Ok(league)
+        } else {
+            Err(format!("League with id: {:?} not found", &id).into())
+        }
+    }
+
+    /// Marks a game day, so game day payroll falls due again
+    pub async fn game_recorded(db: &Database, id: String) -> Result<Self> {
+        let query = doc! {
+            "_id": ObjectId::with_string(&id)?
+        };
+
+        if let Some(mut league) = League::find_one(db, Some(query), None).await? {
+            league.payroll_rules.last_game = Some(bson::DateTime::from(Utc::now()));
+
+            league.save(db, None).await?;
+
+            Ok(league)
+        } else {
+            Err(format!("League with id: {:?} not found", &id).into())
+        }
+    }
+
     pub async fn set_league_status(db: &Database, id: String, status: LeagueStatus) -> Result<Self> {
         let query = doc! {
             "_id": ObjectId::with_string(&id)?
@@ -263,3 +594,78 @@ impl League {
 
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days_ago(days: i64) -> Option<bson::DateTime> {
+        Some(bson::DateTime::from(Utc::now() - Duration::days(days)))
+    }
+
+    fn rules(pay_period: PayPeriod, last_payroll: Option<bson::DateTime>, last_game: Option<bson::DateTime>) -> PayrollRules {
+        PayrollRules {
+            pay_period,
+            last_payroll,
+            last_game,
+            ..PayrollRules::default()
+        }
+    }
+
+    #[test]
+    fn game_day_payroll_waits_for_a_game() {
+        assert!(!rules(PayPeriod::GameDay, None, None).is_due());
+        assert!(rules(PayPeriod::GameDay, None, days_ago(0)).is_due());
+    }
+
+    #[test]
+    fn game_day_payroll_runs_once_per_recorded_game_day() {
+        assert!(rules(PayPeriod::GameDay, days_ago(2), days_ago(1)).is_due());
+        assert!(!rules(PayPeriod::GameDay, days_ago(1), days_ago(2)).is_due());
+        assert!(!rules(PayPeriod::GameDay, days_ago(1), None).is_due());
+    }
+
+    #[test]
+    fn weekly_payroll_runs_at_most_once_a_week() {
+        assert!(rules(PayPeriod::Week, None, None).is_due());
+        assert!(!rules(PayPeriod::Week, days_ago(3), None).is_due());
+        assert!(rules(PayPeriod::Week, days_ago(8), None).is_due());
+    }
+
+    #[test]
+    fn keepers_close_at_the_deadline() {
+        let mut rules = KeeperRules::default();
+        assert!(rules.is_open());
+
+        rules.deadline = days_ago(1);
+        assert!(!rules.is_open());
+
+        rules.deadline = days_ago(-1);
+        assert!(rules.is_open());
+    }
+
+    #[test]
+    fn default_rules_pass_their_checks() {
+        assert!(KeeperRules::default().check().is_ok());
+        assert!(PayrollRules::default().check().is_ok());
+        assert!(EconomyRules::default().check().is_ok());
+    }
+
+    #[test]
+    fn negative_or_absurd_rules_are_rejected() {
+        let economy = EconomyRules { ticket_price: -1, ..EconomyRules::default() };
+        assert!(economy.check().is_err());
+
+        let economy = EconomyRules { base_attendance: 500, ..EconomyRules::default() };
+        assert!(economy.check().is_err());
+
+        let payroll = PayrollRules { salary_rate: 101, ..PayrollRules::default() };
+        assert!(payroll.check().is_err());
+
+        let payroll = PayrollRules { missed_payroll_fine: -50, ..PayrollRules::default() };
+        assert!(payroll.check().is_err());
+
+        let keepers = KeeperRules { cost_inflation: -10, ..KeeperRules::default() };
+        assert!(keepers.check().is_err());
+    }
+}
@@ -0,0 +1,442 @@
+use async_graphql::{Result, ID};
+use serde_json::json;
+
+use common::client::query_service;
+use common::enums::ComputerStrategy;
+
+use crate::config::CONFIG;
+use crate::graphql::GameResultInput;
+use crate::models::League;
+
+const LEAGUE_ROSTER_ISSUES: &str = r#"
+query LeagueRosterIssues($league: ID!) {
+    leagueRosterIssues(league: $league) {
+        name
+        issues {
+            message
+        }
+    }
+}
+"#;
+
+const CREATE_COMPUTER_MANAGER: &str = r#"
+mutation CreateComputerManager($username: String!) {
+    createComputerManager(username: $username) {
+        id
+    }
+}
+"#;
+
+const CREATE_COMPUTER_TEAM: &str = r#"
+mutation CreateComputerTeam($name: String!, $leagueId: ID!, $owner: ID!, $strategy: ComputerStrategy!) {
+    createComputerTeam(name: $name, leagueId: $leagueId, owner: $owner, strategy: $strategy) {
+        id
+    }
+}
+"#;
+
+const ROLLOVER_LEAGUE: &str = r#"
+mutation RolloverLeague($league: ID!, $season: Int!, $keepersPerTeam: Int!, $costInflation: Int!, $dynasty: Boolean!) {
+    rolloverLeague(league: $league, season: $season, keepersPerTeam: $keepersPerTeam, costInflation: $costInflation, dynasty: $dynasty) {
+        id
+    }
+}
+"#;
+
+const AGE_PLAYERS: &str = r#"
+mutation AgePlayers($league: ID!, $season: Int!) {
+    agePlayers(league: $league, season: $season) {
+        id
+    }
+}
+"#;
+
+const EXPIRE_CONTRACTS: &str = r#"
+mutation ExpireContracts($league: ID!, $season: Int!) {
+    expireContracts(league: $league, season: $season) {
+        id
+    }
+}
+"#;
+
+const REPRICE_PLAYERS: &str = r#"
+mutation RepricePlayers($league: ID!) {
+    repricePlayers(league: $league) {
+        player
+    }
+}
+"#;
+
+const DROP_PLAYERS: &str = r#"
+mutation DropPlayers($league: ID!, $players: [ID!]!) {
+    dropPlayers(league: $league, players: $players) {
+        id
+    }
+}
+"#;
+
+const SIGN_FREE_AGENTS: &str = r#"
+mutation SignFreeAgents($league: ID!) {
+    signFreeAgents(league: $league) {
+        player
+        team
+    }
+}
+"#;
+
+const ADD_SIGNED_PLAYERS: &str = r#"
+mutation AddSignedPlayers($league: ID!, $signings: [SigningInput!]!) {
+    addSignedPlayers(league: $league, signings: $signings) {
+        id
+    }
+}
+"#;
+
+const RUN_PAYROLL: &str = r#"
+mutation RunPayroll($league: ID!, $salaryRate: Int!, $salaryCap: Int, $fine: Int!) {
+    runPayroll(league: $league, salaryRate: $salaryRate, salaryCap: $salaryCap, fine: $fine) {
+        team
+    }
+}
+"#;
+
+const PREPARE_FOR_GAME: &str = r#"
+mutation PrepareForGame($team: ID!) {
+    prepareForGame(team: $team) {
+        id
+    }
+}
+"#;
+
+const SETTLE_GAME: &str = r#"
+mutation SettleGame($league: ID!, $result: GameResult!, $rates: EconomyRates!) {
+    settleGame(league: $league, result: $result, rates: $rates) {
+        team
+    }
+}
+"#;
+
+const USER_STANDING: &str = r#"
+query UserStanding($id: ID!) {
+    user(id: $id) {
+        computer
+        deleted
+    }
+}
+"#;
+
+const MANAGER_LEFT: &str = r#"
+mutation ManagerLeft($league: ID!, $user: ID!, $handOff: Boolean!) {
+    managerLeft(league: $league, user: $user, handOff: $handOff) {
+        id
+    }
+}
+"#;
+
+const TEAMS_LEAGUE_DELETED: &str = r#"
+mutation LeagueDeleted($league: ID!) {
+    leagueDeleted(league: $league)
+}
+"#;
+
+const PLAYERS_LEAGUE_DELETED: &str = r#"
+mutation LeagueDeleted($league: ID!) {
+    leagueDeleted(league: $league)
+}
+"#;
+
+/// Re-runs the auto manager for both teams and checks they're legal to take the field
+pub async fn prepare_teams(home: &ID, away: &ID) -> Result<()> {
+    for team in [home, away].iter() {
+        query_service(
+            &CONFIG.services.teams,
+            PREPARE_FOR_GAME,
+            json!({ "team": team.to_string() }),
+        ).await?;
+    }
+
+    Ok(())
+}
+
+/// Asks the teams service whether every team in the league can take the field
+pub async fn check_rosters(league: &League) -> Result<()> {
+    let league_id = league_id(league)?;
+
+    let data = query_service(
+        &CONFIG.services.teams,
+        LEAGUE_ROSTER_ISSUES,
+        json!({ "league": league_id }),
+    ).await?;
+
+    let teams = data["leagueRosterIssues"].as_array().cloned().unwrap_or_default();
+
+    if teams.is_empty() {
+        return Ok(())
+    }
+
+    let problems: Vec<String> = teams.iter()
+        .map(|team| {
+            let messages: Vec<&str> = team["issues"].as_array()
+                .map(|issues| issues.iter().filter_map(|issue| issue["message"].as_str()).collect())
+                .unwrap_or_default();
+
+            format!("{} ({})", team["name"].as_str().unwrap_or_default(), messages.join(", "))
+        })
+        .collect();
+
+    Err(format!("Teams with illegal rosters: {}", problems.join("; ")).into())
+}
+
+/// Creates a computer manager account in the users service and returns its ID
+pub async fn create_computer_manager(username: &str) -> Result<String> {
+    let data = query_service(
+        &CONFIG.services.users,
+        CREATE_COMPUTER_MANAGER,
+        json!({ "username": username }),
+    ).await?;
+
+    data["createComputerManager"]["id"].as_str()
+        .map(String::from)
+        .ok_or_else(|| "Users service didn't return the computer manager".into())
+}
+
+pub async fn create_computer_team(
+    name: &str,
+    league_id: &str,
+    owner_id: &str,
+    strategy: ComputerStrategy,
+) -> Result<()> {
+    query_service(
+        &CONFIG.services.teams,
+        CREATE_COMPUTER_TEAM,
+        json!({
+            "name": name,
+            "leagueId": league_id,
+            "owner": owner_id,
+            "strategy": strategy,
+        }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Has the teams service release everyone who isn't kept and charge for keepers
+pub async fn rollover_teams(league: &League, season: i64) -> Result<()> {
+    let league_id = league_id(league)?;
+
+    let rules = &league.keeper_rules;
+
+    query_service(
+        &CONFIG.services.teams,
+        ROLLOVER_LEAGUE,
+        json!({
+            "league": league_id,
+            "season": season,
+            "keepersPerTeam": rules.keepers_per_team,
+            "costInflation": rules.cost_inflation,
+            "dynasty": rules.dynasty,
+        }),
+    ).await?;
+
+    Ok(())
+}
+
+fn league_id(league: &League) -> Result<String> {
+    match &league.id {
+        Some(id) => Ok(id.to_string()),
+        None => Err("League hasn't been saved".into()),
+    }
+}
+
+/// Takes players who left the league's teams off their rosters
+async fn drop_players(league_id: &str, departed: &serde_json::Value) -> Result<()> {
+    let players: Vec<String> = departed.as_array()
+        .map(|players| {
+            players.iter()
+                .filter_map(|player| player["id"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if players.is_empty() {
+        return Ok(())
+    }
+
+    query_service(
+        &CONFIG.services.teams,
+        DROP_PLAYERS,
+        json!({ "league": league_id, "players": players }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Ages the league's players into `season` and takes anyone who retired off their team
+pub async fn age_players(league: &League, season: i64) -> Result<()> {
+    let league_id = league_id(league)?;
+
+    let data = query_service(
+        &CONFIG.services.players,
+        AGE_PLAYERS,
+        json!({ "league": league_id, "season": season }),
+    ).await?;
+
+    drop_players(&league_id, &data["agePlayers"]).await
+}
+
+/// Counts down the league's contracts and takes players who became free agents off their team
+pub async fn expire_contracts(league: &League, season: i64) -> Result<()> {
+    let league_id = league_id(league)?;
+
+    let data = query_service(
+        &CONFIG.services.players,
+        EXPIRE_CONTRACTS,
+        json!({ "league": league_id, "season": season }),
+    ).await?;
+
+    drop_players(&league_id, &data["expireContracts"]).await
+}
+
+/// Recomputes the price of every player in the league, returning how many changed
+pub async fn reprice_players(league: &League) -> Result<usize> {
+    let league_id = league_id(league)?;
+
+    let data = query_service(
+        &CONFIG.services.players,
+        REPRICE_PLAYERS,
+        json!({ "league": league_id }),
+    ).await?;
+
+    Ok(data["repricePlayers"].as_array().map(|changes| changes.len()).unwrap_or(0))
+}
+
+/// Settles free agent offers and puts everyone who signed on their new team's roster,
+/// returning how many players signed
+pub async fn sign_free_agents(league: &League) -> Result<usize> {
+    let league_id = league_id(league)?;
+
+    let data = query_service(
+        &CONFIG.services.players,
+        SIGN_FREE_AGENTS,
+        json!({ "league": league_id }),
+    ).await?;
+
+    let signings = data["signFreeAgents"].clone();
+    let count = signings.as_array().map(|signings| signings.len()).unwrap_or(0);
+
+    if count == 0 {
+        return Ok(0)
+    }
+
+    query_service(
+        &CONFIG.services.teams,
+        ADD_SIGNED_PLAYERS,
+        json!({ "league": league_id, "signings": signings }),
+    ).await?;
+
+    Ok(count)
+}
+
+/// Has the teams service pay every team's salaries for one pay period
+pub async fn run_team_payroll(league: &League) -> Result<()> {
+    let league_id = league_id(league)?;
+
+    let rules = &league.payroll_rules;
+
+    query_service(
+        &CONFIG.services.teams,
+        RUN_PAYROLL,
+        json!({
+            "league": league_id,
+            "salaryRate": rules.salary_rate,
+            "salaryCap": rules.salary_cap,
+            "fine": rules.missed_payroll_fine,
+        }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Has the teams service record a finished game and pay out its income
+pub async fn settle_game(league: &League, result: &GameResultInput, playoff: bool) -> Result<()> {
+    let league_id = league_id(league)?;
+
+    let rules = &league.economy_rules;
+
+    query_service(
+        &CONFIG.services.teams,
+        SETTLE_GAME,
+        json!({
+            "league": league_id,
+            "result": {
+                "game": result.game.to_string(),
+                "home": result.home.to_string(),
+                "away": result.away.to_string(),
+                "homeScore": result.home_score,
+                "awayScore": result.away_score,
+                "playoff": playoff,
+            },
+            "rates": {
+                "ticketPrice": rules.ticket_price,
+                "baseAttendance": rules.base_attendance,
+                "attendancePerHomeWin": rules.attendance_per_home_win,
+                "playoffBonus": rules.playoff_bonus,
+                "merchandiseRate": rules.merchandise_rate,
+            },
+        }),
+    ).await?;
+
+    Ok(())
+}
+
+/// The first of the managers who is a person with an account still open, to take over as
+/// commissioner
+pub async fn find_successor(managers: &[String]) -> Result<Option<String>> {
+    for manager in managers.iter() {
+        let data = query_service(
+            &CONFIG.services.users,
+            USER_STANDING,
+            json!({ "id": manager }),
+        ).await?;
+
+        let user = &data["user"];
+
+        if user["computer"] == false && user["deleted"] == false {
+            return Ok(Some(manager.clone()))
+        }
+    }
+
+    Ok(None)
+}
+
+/// Has the teams service hand a departing manager's team to the computer, or remove it
+pub async fn manager_left(league_id: &str, user_id: &str, hand_off: bool) -> Result<()> {
+    query_service(
+        &CONFIG.services.teams,
+        MANAGER_LEFT,
+        json!({
+            "league": league_id,
+            "user": user_id,
+            "handOff": hand_off,
+        }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Removes the league's teams and players from their services
+pub async fn league_deleted(league_id: &str) -> Result<()> {
+    query_service(
+        &CONFIG.services.teams,
+        TEAMS_LEAGUE_DELETED,
+        json!({ "league": league_id }),
+    ).await?;
+
+    query_service(
+        &CONFIG.services.players,
+        PLAYERS_LEAGUE_DELETED,
+        json!({ "league": league_id }),
+    ).await?;
+
+    Ok(())
+}
@@ -32,6 +32,38 @@ pub struct ServerConfig {
     pub port: u16,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServicesConfig {
+    pub teams: String,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        ServicesConfig {
+            teams: String::from("http://localhost:8002"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScoutingConfig {
+    /// Let managers see the hidden traits of their own players without scouting them
+    pub owner_sees_hidden_traits: bool,
+    /// Percent chance per game that each unrevealed hidden trait shows itself on the field
+    pub game_reveal_chance: u32,
+}
+
+impl Default for ScoutingConfig {
+    fn default() -> Self {
+        ScoutingConfig {
+            owner_sees_hidden_traits: true,
+            game_reveal_chance: 5,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
@@ -40,6 +72,10 @@ pub struct Settings {
     pub server: ServerConfig,
     pub redis: RedisConfig,
     pub session: SessionConfig,
+    #[serde(default)]
+    pub services: ServicesConfig,
+    #[serde(default)]
+    pub scouting: ScoutingConfig,
 }
 
 impl Settings {
@@ -1,4 +1,9 @@
+use async_graphql::guard::Guard;
+use common::auth::{RoleGuard, ScopeGuard};
 use crate::models::*;
+use crate::services::fetch_owned_teams;
+use futures::lock::Mutex;
+use crate::valuation::PriceChange;
 use common::*;
 use async_graphql::*;
 use strum::IntoEnumIterator;
@@ -8,12 +13,22 @@ use wither::{mongodb::Database, bson::Document};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 
-use common::enums::{Class, Race, Gender};
-use common::filter::process_filter;
+use chrono::{DateTime, Utc};
+
+use common::enums::{ApiScope, Class, ContractOption, Race, Gender, Role};
+use common::morale::{chemistry, chemistry_modifier, morale_modifier};
+use common::progression::experience_for_level;
+use common::filter::{process_filter, uses_keys};
 use common::meta::MetaSelect;
 
 pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
 
+/// Experience for taking part in a game, and the extra for winning it
+const GAME_EXPERIENCE: i64 = 100;
+const WIN_EXPERIENCE: i64 = 50;
+/// Experience for a farm player each time their team plays, minor leaguers develop slower
+const FARM_EXPERIENCE: i64 = 40;
+
 #[Object]
 impl Player {
     async fn id(&self) -> ID {
@@ -33,11 +48,7 @@ impl Player {
     }
 
     async fn team(&self) -> Option<Team> {
-        if let Some(team) = &self.team {
-            Some(Team { id: ID::from(team) })
-        } else {
-            None //ID::from("")
-        }
+        self.team.as_ref().map(|team| Team { id: ID::from(team) })
     }
 
     async fn cost(&self) -> i64 {
@@ -96,15 +107,208 @@ impl Player {
         self.traits.clone()
     }
 
-    async fn hidden_traits(&self) -> Option<Vec::<enums::Trait>> {
-        if let Some(traits) = &self.hidden_traits {
-            Some(traits.clone())
-        } else {
-            None
+    /// Only the hidden traits the viewer has scouted, seen in a game or, depending on the
+    /// server, owns the player for
+    async fn hidden_traits(&self, ctx: &Context<'_>) -> Result<Option<Vec::<enums::Trait>>> {
+        if self.hidden_traits.is_none() {
+            return Ok(None)
+        }
+
+        let teams = viewer_teams(ctx).await?;
+
+        Ok(Some(self.visible_hidden_traits(&teams)))
+    }
+
+    async fn morale(&self) -> i64 {
+        self.morale
+    }
+
+    /// Added to the player's rolls by the game simulator
+    async fn morale_modifier(&self) -> i64 {
+        morale_modifier(self.morale)
+    }
+
+    /// Newest first
+    async fn morale_events(&self, ctx: &Context<'_>, #[graphql(default = 20)] limit: i64) -> Result<Vec<MoraleEvent>> {
+        let db: &Database = ctx.data()?;
+
+        match &self.id {
+            Some(id) => MoraleEvent::find_latest(db, "player", &id.to_string(), limit.min(100)).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn contract(&self) -> Option<&Contract> {
+        self.contract.as_ref()
+    }
+
+    /// Whether the player is out of contract and can be bid on
+    async fn free_agent(&self) -> bool {
+        self.team.is_none() && !self.retired
+    }
+
+    /// The least the player would sign for on the given terms
+    async fn asking_salary(&self, terms: ContractTermsInput) -> i64 {
+        self.asking_salary_for(&terms.into())
+    }
+
+    /// How many hidden traits the player has, known or not
+    async fn hidden_trait_count(&self) -> i64 {
+        self.hidden_traits.as_ref().map(|traits| traits.len() as i64).unwrap_or(0)
+    }
+
+    /// Whether the viewer's scouts are still looking at this player
+    async fn scouting_in_progress(&self, ctx: &Context<'_>) -> Result<bool> {
+        let teams = viewer_teams(ctx).await?;
+
+        Ok(self.scouting.iter().any(|report| teams.contains(&report.team) && report.in_progress()))
+    }
+
+    async fn level(&self) -> i64 {
+        self.level
+    }
+
+    async fn experience(&self) -> i64 {
+        self.experience
+    }
+
+    async fn age(&self) -> Option<i64> {
+        self.age
+    }
+
+    async fn retired(&self) -> bool {
+        self.retired
+    }
+
+    /// None once the player is at the max level
+    async fn experience_to_next_level(&self) -> Option<i64> {
+        experience_for_level(self.level + 1).map(|needed| (needed - self.experience).max(0))
+    }
+
+    /// Experience earned and levels gained, newest first
+    async fn progression(
+        &self, ctx: &Context<'_>,
+        #[graphql(default)] offset: i64,
+        #[graphql(default = 20)] limit: i64,
+    ) -> Result<Vec<Progression>> {
+        let db: &Database = ctx.data()?;
+
+        match &self.id {
+            Some(id) => Progression::find_by_player(db, &id.to_string(), offset, limit.min(100)).await,
+            None => Ok(Vec::new()),
         }
     }
 }
 
+#[Object]
+impl MoraleEvent {
+    async fn player(&self) -> ID {
+        ID::from(&self.player)
+    }
+
+    async fn team(&self) -> ID {
+        ID::from(&self.team)
+    }
+
+    async fn game(&self) -> Option<ID> {
+        self.game.as_ref().map(ID::from)
+    }
+
+    async fn kind(&self) -> MoraleEventKind {
+        self.kind
+    }
+
+    async fn morale(&self) -> i64 {
+        self.morale
+    }
+
+    async fn message(&self) -> &str {
+        &self.message
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.0
+    }
+}
+
+#[Object]
+impl Progression {
+    async fn source(&self) -> ExperienceSource {
+        self.source
+    }
+
+    async fn experience(&self) -> i64 {
+        self.experience
+    }
+
+    async fn total_experience(&self) -> i64 {
+        self.total_experience
+    }
+
+    async fn level(&self) -> i64 {
+        self.level
+    }
+
+    async fn level_ups(&self) -> &Vec<LevelUp> {
+        &self.level_ups
+    }
+
+    async fn game(&self) -> Option<ID> {
+        self.game.as_ref().map(ID::from)
+    }
+
+    async fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.0
+    }
+}
+
+/// Filtering or sorting on these would leak hidden traits, the operators because they can run
+/// code against the whole document
+const HIDDEN_FILTER_KEYS: [&str; 10] = [
+    "hiddenTraits", "scouting",
+    "_where", "$where", "_expr", "$expr", "_function", "$function", "_jsonSchema", "$jsonSchema",
+];
+
+/// The viewer's teams, fetched from the teams service the first time a player in the response
+/// needs them
+#[derive(Default)]
+pub struct ViewerTeams(Mutex<Option<Vec<String>>>);
+
+/// Teams managed by whoever is asking, empty for anonymous requests
+async fn viewer_teams(ctx: &Context<'_>) -> Result<Vec<String>> {
+    let current_user = match ctx.data_opt::<CurrentUser>() {
+        Some(current_user) => current_user,
+        None => return Ok(Vec::new()),
+    };
+
+    let cache = match ctx.data_opt::<ViewerTeams>() {
+        Some(cache) => cache,
+        None => return fetch_owned_teams(&current_user.id).await,
+    };
+
+    let mut teams = cache.0.lock().await;
+
+    if teams.is_none() {
+        *teams = Some(fetch_owned_teams(&current_user.id).await?);
+    }
+
+    Ok(teams.clone().unwrap_or_default())
+}
+
+async fn team_chemistry(db: &Database, team_id: &str) -> Result<i64> {
+    let players = Player::find_by_team(db, team_id).await?;
+
+    let members: Vec<(i64, Vec<enums::Trait>)> = players.iter()
+        .map(|player| (player.morale, player.all_traits()))
+        .collect();
+
+    Ok(chemistry(&members))
+}
+
 pub struct League {
     pub id: ID,
 }
@@ -125,6 +329,18 @@ impl Query {
     {
         let db: &Database = ctx.data().expect("Can't connect to database");
 
+        let is_admin = ctx.data_opt::<CurrentUser>().map(CurrentUser::is_admin).unwrap_or(false);
+
+        if !is_admin {
+            let leaks = |map: &Option<HashMap<String, serde_json::Value>>| {
+                map.as_ref().map(|map| uses_keys(map, &HIDDEN_FILTER_KEYS)).unwrap_or(false)
+            };
+
+            if leaks(&filter) || leaks(&sort) {
+                return Err("Players can't be filtered or sorted by hidden traits or with $where, $expr or $function".into())
+            }
+        }
+
         let filter = if let Some(filter) = filter {
             let filter = process_filter(filter)?;
             Some(filter)
@@ -157,6 +373,22 @@ impl Query {
         }
     }
 
+    /// Everything stopping the team from trading these players away
+    async fn trade_issues(&self, ctx: &Context<'_>, team: ID, players: Vec<ID>) -> Result<Vec<String>> {
+        let db: &Database = ctx.data()?;
+
+        let mut issues = Vec::<String>::new();
+
+        for id in players.iter() {
+            match Player::find_by_id(db, id).await {
+                Some(player) => issues.extend(player.trade_issues(&team)),
+                None => issues.push(format!("Player with id: {:?} not found", id.as_str())),
+            }
+        }
+
+        Ok(issues)
+    }
+
     #[graphql(entity)]
     async fn find_league_by_id(&self, id: ID) -> League {
         League { id }
@@ -263,6 +495,27 @@ impl Team {
             Err("Can't get players for team".into())
         }
     }
+
+    /// 0 to 100, from the players' morale and how their personalities mix
+    async fn chemistry(&self, ctx: &Context<'_>) -> Result<i64> {
+        let db: &Database = ctx.data()?;
+
+        team_chemistry(db, &self.id).await
+    }
+
+    /// Added to every player's rolls by the game simulator
+    async fn chemistry_modifier(&self, ctx: &Context<'_>) -> Result<i64> {
+        let db: &Database = ctx.data()?;
+
+        Ok(chemistry_modifier(team_chemistry(db, &self.id).await?))
+    }
+
+    /// Newest first
+    async fn morale_events(&self, ctx: &Context<'_>, #[graphql(default = 20)] limit: i64) -> Result<Vec<MoraleEvent>> {
+        let db: &Database = ctx.data()?;
+
+        MoraleEvent::find_latest(db, "team", &self.id, limit.min(100)).await
+    }
 }
 
 
@@ -270,40 +523,186 @@ pub struct Mutation;
 
 #[Object(extends, cache_control(max_age = 60))]
 impl Mutation {
+    /// Called by the player generator with an integration key
+    #[graphql(guard(ScopeGuard(scope = "ApiScope::CreatePlayer")))]
     async fn create_player(&self, ctx: &Context<'_>, input: CreatePlayerInput) -> Result<Player, Error> {
         let db: &Database = ctx.data()?;
 
-        // TODO: Restrict to integrations role only
-        //
-        /*let redis_client: &redis::Client = ctx.data()?;
+        let mut new_player = Player::new_player(input);
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        new_player.save(db, None).await?;
 
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        Ok(new_player)
+    }
 
-        if maybe_current_user.is_none() {
-            return Err("Creating players requires authentication".into())
-        }*/
+    /// Signs the player to the team on a standard contract unless they already have one with it
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn set_team(&self, ctx: &Context<'_>, player: ID, team: ID) -> Result<Player> {
+        let db: &Database = ctx.data()?;
 
-        let mut new_player = Player::new_player(input);
+        Player::set_team(db, &player, &team).await
+    }
 
-        new_player.save(&db, None).await?;
+    /// Called by the teams service for contract extensions and free agent bids
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn negotiate_contract(
+        &self, ctx: &Context<'_>,
+        player: ID,
+        team: ID,
+        league: ID,
+        terms: ContractTermsInput,
+    ) -> Result<NegotiationResult> {
+        let db: &Database = ctx.data()?;
 
-        Ok(new_player)
+        Player::negotiate(db, &player, &team, &league, terms.into()).await
     }
 
-    async fn set_team(&self, ctx: &Context<'_>, player: ID, team: ID) -> Result<Player> {
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn exercise_option(&self, ctx: &Context<'_>, player: ID, team: ID, exercise: bool) -> Result<Player> {
         let db: &Database = ctx.data()?;
 
-        if let Some(mut player) = Player::find_by_id(db, &player).await {
-            player.team = Some(team.to_string());
-            player.save(db, None).await?;
+        Player::exercise_option(db, &player, &team, exercise).await
+    }
 
-            Ok(player)
-        } else {
-            Err("Can't get player for id".into())
+    /// Called by the leagues service at season rollover, returns the players who became free
+    /// agents along with the team they left
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn expire_contracts(&self, ctx: &Context<'_>, league: ID, season: i64) -> Result<Vec<Player>> {
+        let db: &Database = ctx.data()?;
+
+        Player::expire_contracts(db, &league, season).await
+    }
+
+    /// Called by the leagues service to settle free agent offers
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn sign_free_agents(&self, ctx: &Context<'_>, league: ID) -> Result<Vec<Signing>> {
+        let db: &Database = ctx.data()?;
+
+        Player::sign_free_agents(db, &league).await
+    }
+
+    /// Returns a player to the league's free agent pool
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn release_player(&self, ctx: &Context<'_>, player: ID) -> Result<Player> {
+        let db: &Database = ctx.data()?;
+
+        Player::release(db, &player).await
+    }
+
+    /// Called by the leagues service at season rollover, returns the players who retired along
+    /// with the team they retired from
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn age_players(&self, ctx: &Context<'_>, league: ID, season: i64) -> Result<Vec<Player>> {
+        let db: &Database = ctx.data()?;
+
+        Player::age_league(db, &league, season).await
+    }
+
+    /// Recomputes every price in the league, called by the leagues service on its schedule
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn reprice_players(&self, ctx: &Context<'_>, league: ID) -> Result<Vec<PriceChange>> {
+        let db: &Database = ctx.data()?;
+
+        Player::reprice_league(db, &league).await
+    }
+
+    /// Called by the teams service after each game for every team that played
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn apply_game_morale(
+        &self, ctx: &Context<'_>,
+        team: ID,
+        game: ID,
+        played: Vec<ID>,
+        won: bool,
+    ) -> Result<Vec<MoraleEvent>> {
+        let db: &Database = ctx.data()?;
+
+        let played: Vec<String> = played.iter().map(|id| id.to_string()).collect();
+
+        Player::apply_game_morale(db, &team, Some(game.to_string()), &played, won).await
+    }
+
+    /// Called by the teams service once the team has paid for scouting
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn scout_player(&self, ctx: &Context<'_>, player: ID, team: ID, league: ID, days: i64) -> Result<Player> {
+        let db: &Database = ctx.data()?;
+
+        Player::scout(db, &player, &team, &league, days).await
+    }
+
+    /// Called by the game simulator when a hidden trait shows itself, revealing it to everyone
+    #[graphql(guard(ScopeGuard(scope = "ApiScope::RevealTrait")))]
+    async fn reveal_trait(&self, ctx: &Context<'_>, player: ID, hidden_trait: enums::Trait) -> Result<Player> {
+        let db: &Database = ctx.data()?;
+
+        Player::reveal_trait(db, &player, hidden_trait).await
+    }
+
+    /// Experience for an achievement or a manual adjustment
+    #[graphql(guard(ScopeGuard(scope = "ApiScope::AwardExperience")))]
+    async fn award_experience(
+        &self, ctx: &Context<'_>,
+        player: ID,
+        experience: i64,
+        #[graphql(default_with = "ExperienceSource::Achievement")] source: ExperienceSource,
+        note: Option<String>,
+    ) -> Result<Player> {
+        let db: &Database = ctx.data()?;
+
+        Player::award_experience(db, &player, experience, source, None, note).await
+    }
+
+    /// Called by the teams service for everyone who played in a game
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn award_game_experience(
+        &self, ctx: &Context<'_>,
+        game: ID,
+        players: Vec<ID>,
+        #[graphql(default)] won: bool,
+    ) -> Result<Vec<Player>> {
+        let db: &Database = ctx.data()?;
+
+        let experience = if won { GAME_EXPERIENCE + WIN_EXPERIENCE } else { GAME_EXPERIENCE };
+        let mut awarded = Vec::<Player>::new();
+
+        for player in players.iter() {
+            awarded.push(Player::award_experience(
+                db, player, experience,
+                ExperienceSource::GamePlayed,
+                Some(game.to_string()),
+                None,
+            ).await?);
         }
+
+        Ok(awarded)
+    }
+
+    /// Called by the teams service for the farm players of each team that played
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn award_farm_experience(&self, ctx: &Context<'_>, game: ID, players: Vec<ID>) -> Result<Vec<Player>> {
+        let db: &Database = ctx.data()?;
+
+        let mut awarded = Vec::<Player>::new();
+
+        for player in players.iter() {
+            awarded.push(Player::award_experience(
+                db, player, FARM_EXPERIENCE,
+                ExperienceSource::Development,
+                Some(game.to_string()),
+                None,
+            ).await?);
+        }
+
+        Ok(awarded)
+    }
+
+    /// Called by the leagues service when a league that never started is deleted, returns
+    /// how many players went with it
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn league_deleted(&self, ctx: &Context<'_>, league: ID) -> Result<u64> {
+        let db: &Database = ctx.data()?;
+
+        Player::delete_by_league(db, &league).await
     }
 }
 
@@ -328,4 +727,61 @@ pub struct CreatePlayerInput {
 
     pub traits: Vec::<enums::Trait>,
     pub hidden_traits: Option<Vec::<enums::Trait>>,
+
+    /// Rolled from the race's debut ages when left out
+    pub age: Option<i64>,
+}
+
+#[derive(Clone, InputObject)]
+pub struct ContractTermsInput {
+    pub years: i64,
+    /// Gold per pay period
+    pub salary: i64,
+    pub option: Option<ContractOption>,
+    #[graphql(default)]
+    pub no_trade: bool,
+}
+
+impl From<ContractTermsInput> for ContractTerms {
+    fn from(input: ContractTermsInput) -> Self {
+        ContractTerms {
+            years: input.years,
+            salary: input.salary,
+            option: input.option,
+            no_trade: input.no_trade,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sdl() -> String {
+        Schema::build(Query, Mutation, EmptySubscription).finish().sdl()
+    }
+
+    fn query_type(sdl: &str) -> &str {
+        let start = sdl.find("type Query").expect("Schema has no Query type");
+        let end = sdl[start..].find('}').expect("Query type isn't closed");
+
+        &sdl[start..start + end]
+    }
+
+    #[test]
+    fn trade_issues_is_a_query_field() {
+        let sdl = sdl();
+
+        assert!(query_type(&sdl).contains("tradeIssues(team: ID!, players: [ID!]!): [String!]!"));
+    }
+
+    #[test]
+    fn leagues_stay_entities() {
+        let sdl = sdl();
+
+        assert!(!query_type(&sdl).contains("findLeagueById"));
+        let entities = sdl.lines().find(|line| line.starts_with("union _Entity")).expect("Schema has no entities");
+
+        assert!(entities.contains("League"));
+    }
 }
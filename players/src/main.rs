@@ -5,12 +5,15 @@ mod config;
 mod graphql;
 mod models;
 mod routes;
+mod services;
+mod valuation;
 
 use crate::config::CONFIG;
 use crate::graphql::{AppSchema, Mutation, Query};
-use crate::models::Player;
+use crate::models::{MoraleEvent, Player, Progression};
 use crate::routes::index;
 
+use common::auth::SessionSecret;
 use actix_web::{middleware, web, App, HttpServer};
 use async_graphql::{
     extensions::{apollo_persisted_queries::ApolloPersistedQueries, ApolloTracing, Logger, apollo_persisted_queries::LruCacheStorage},
@@ -32,6 +35,14 @@ async fn init_db() -> Database {
         .await
         .expect("Failed syncing indexes");
 
+    Progression::sync(&db)
+        .await
+        .expect("Failed syncing indexes");
+
+    MoraleEvent::sync(&db)
+        .await
+        .expect("Failed syncing indexes");
+
     db
 }
 
@@ -74,6 +85,8 @@ async fn main() -> std::io::Result<()> {
     println!("called main()");
     init_logger();
 
+    common::client::set_service_credentials("players", &CONFIG.session.secret);
+
     let db = init_db().await;
     let redis = init_redis().await;
     let schema = init_graphql(&db, &redis);
@@ -82,6 +95,8 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(middleware::Logger::default())
             .data(db.clone())
+            .data(redis.clone())
+            .data(SessionSecret(CONFIG.session.secret.clone()))
             .data(schema.clone())
             .service(
                 web::resource("/").route(web::post().to(index))
@@ -0,0 +1,381 @@
+use async_graphql::*;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::{bson::{self, doc}, mongodb::Database};
+
+use common::enums::{ContractOption, Trait};
+
+use crate::models::Player;
+use crate::services::fetch_team_gold;
+
+use std::collections::HashMap;
+
+pub const MAX_CONTRACT_YEARS: i64 = 5;
+
+/// Percent of a player's cost they ask for each pay period on a plain one year deal
+const ASKING_RATE: i64 = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Contract {
+    pub team: String,
+    pub years: i64,
+    pub years_remaining: i64,
+    /// Gold per pay period, players signed without one are paid at the league's rate
+    pub salary: Option<i64>,
+    /// One extra year either side can add when the contract runs out
+    pub option: Option<ContractOption>,
+    /// Set once the team decides on a team option
+    pub option_exercised: Option<bool>,
+    pub no_trade: bool,
+    #[graphql(skip)]
+    pub signed_at: bson::DateTime,
+}
+
+/// The terms a team puts to a player
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractTerms {
+    pub years: i64,
+    pub salary: i64,
+    pub option: Option<ContractOption>,
+    pub no_trade: bool,
+}
+
+/// An offer to a free agent, settled when the commissioner runs free agency signings
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractOffer {
+    pub team: String,
+    pub terms: ContractTerms,
+}
+
+#[derive(Clone, SimpleObject)]
+pub struct NegotiationResult {
+    pub accepted: bool,
+    /// The least the player would take on these terms
+    pub asking_salary: i64,
+    pub message: String,
+}
+
+#[derive(Clone, SimpleObject)]
+pub struct Signing {
+    pub player: ID,
+    pub team: ID,
+}
+
+impl ContractTerms {
+    pub fn validate(&self) -> Result<()> {
+        if self.years < 1 || self.years > MAX_CONTRACT_YEARS {
+            return Err(format!("Contracts run from 1 to {} years", MAX_CONTRACT_YEARS).into())
+        }
+
+        if self.salary < 0 {
+            return Err("Salary can't be negative".into())
+        }
+
+        Ok(())
+    }
+}
+
+impl Contract {
+    pub fn sign(team_id: &str, terms: &ContractTerms) -> Self {
+        Contract {
+            team: team_id.to_string(),
+            years: terms.years,
+            years_remaining: terms.years,
+            salary: Some(terms.salary),
+            option: terms.option,
+            option_exercised: None,
+            no_trade: terms.no_trade,
+            signed_at: bson::DateTime::from(Utc::now()),
+        }
+    }
+
+    /// The one year deal at the league's rate players get when drafted or picked up
+    pub fn standard(team_id: &str) -> Self {
+        Contract {
+            team: team_id.to_string(),
+            years: 1,
+            years_remaining: 1,
+            salary: None,
+            option: None,
+            option_exercised: None,
+            no_trade: false,
+            signed_at: bson::DateTime::from(Utc::now()),
+        }
+    }
+}
+
+impl Player {
+    /// The least the player will sign for on the given terms. Longer deals, player options and
+    /// no-trade clauses make them cheaper, team options and a bad mood make them dearer
+    pub fn asking_salary_for(&self, terms: &ContractTerms) -> i64 {
+        let mut percent: i64 = 100;
+
+        percent -= (terms.years.min(MAX_CONTRACT_YEARS) - 1) * 5;
+
+        match terms.option {
+            Some(ContractOption::Player) => percent -= 10,
+            Some(ContractOption::Team) => percent += 10,
+            None => {},
+        }
+
+        if terms.no_trade {
+            percent -= 10;
+        }
+
+        if self.public_traits().contains(&Trait::Greedy) {
+            percent += 25;
+        }
+
+        if self.morale < 30 {
+            percent += 10;
+        } else if self.morale > 70 {
+            percent -= 10;
+        }
+
+        self.cost * ASKING_RATE / 100 * percent / 100
+    }
+
+    fn offer_ratio(&self, terms: &ContractTerms) -> i64 {
+        terms.salary * 100 / self.asking_salary_for(terms).max(1)
+    }
+
+    /// Offers an extension to one of the team's players, or a bid on a free agent
+    pub async fn negotiate(
+        db: &Database,
+        id: &str,
+        team_id: &str,
+        league_id: &str,
+        terms: ContractTerms,
+    ) -> Result<NegotiationResult> {
+        terms.validate()?;
+
+        let mut player = match Player::find_by_id(db, id).await {
+            Some(player) => player,
+            None => return Err(format!("Player with id: {:?} not found", id).into()),
+        };
+
+        if player.league != league_id {
+            return Err("Teams can only negotiate with players in their own league".into())
+        }
+
+        if player.retired {
+            return Err(format!("{} has retired", player.name).into())
+        }
+
+        let asking_salary = player.asking_salary_for(&terms);
+
+        match player.team.clone() {
+            Some(team) if team == team_id => {
+                if terms.salary < asking_salary {
+                    return Ok(NegotiationResult {
+                        accepted: false,
+                        asking_salary,
+                        message: format!("{} wants at least {} gold on those terms", player.name, asking_salary),
+                    })
+                }
+
+                player.contract = Some(Contract::sign(team_id, &terms));
+                player.save(db, None).await?;
+
+                Ok(NegotiationResult {
+                    accepted: true,
+                    asking_salary,
+                    message: format!("{} signed for {} years", player.name, terms.years),
+                })
+            },
+            Some(_) => Err(format!("{} is under contract with another team", player.name).into()),
+            None => {
+                player.offers.retain(|offer| offer.team != team_id);
+                player.offers.push(ContractOffer {
+                    team: team_id.to_string(),
+                    terms,
+                });
+
+                player.save(db, None).await?;
+
+                Ok(NegotiationResult {
+                    accepted: false,
+                    asking_salary,
+                    message: format!("Offer made, {} will decide when free agency signings run", player.name),
+                })
+            },
+        }
+    }
+
+    /// Records the team's decision on a team option
+    pub async fn exercise_option(db: &Database, id: &str, team_id: &str, exercise: bool) -> Result<Self> {
+        let mut player = match Player::find_by_id(db, id).await {
+            Some(player) => player,
+            None => return Err(format!("Player with id: {:?} not found", id).into()),
+        };
+
+        match player.contract.as_mut() {
+            Some(contract) if contract.team == team_id && contract.option == Some(ContractOption::Team) => {
+                contract.option_exercised = Some(exercise);
+            },
+            _ => return Err(format!("{} has no team option with this team", player.name).into()),
+        }
+
+        player.save(db, None).await?;
+
+        Ok(player)
+    }
+
+    /// Counts down every contract in the league at the rollover into `season`, sending players
+    /// whose deal ran out to free agency. Returns those players with the team they left, contracts
+    /// already counted down for `season` aren't touched again
+    pub async fn expire_contracts(db: &Database, league_id: &str, season: i64) -> Result<Vec<Self>> {
+        let filter = doc! {
+            "league": league_id,
+            "retired": { "$ne": true },
+            "$or": [ { "team": { "$ne": null } }, { "contractSeason": season } ],
+        };
+
+        let cursor = Player::find(db, filter, None).await?;
+        let players: Vec<Player> = cursor.try_collect().await?;
+
+        let mut departed = Vec::<Player>::new();
+
+        for mut player in players.into_iter() {
+            if player.contract_season == Some(season) {
+                // Left in an earlier attempt at this rollover, their team may not know yet
+                if player.team.is_none() {
+                    departed.push(player);
+                }
+
+                continue;
+            }
+
+            let morale = player.morale;
+            let team = player.team.clone();
+
+            player.contract_season = Some(season);
+
+            let expired = match player.contract.as_mut() {
+                None => true,
+                Some(contract) => {
+                    contract.years_remaining -= 1;
+
+                    if contract.years_remaining > 0 {
+                        false
+                    } else {
+                        // A happy player takes up their option, an unhappy one walks
+                        let extend = match contract.option.take() {
+                            Some(ContractOption::Team) => contract.option_exercised.unwrap_or(false),
+                            Some(ContractOption::Player) => morale >= 50,
+                            None => false,
+                        };
+
+                        if extend {
+                            contract.years_remaining = 1;
+                        }
+
+                        !extend
+                    }
+                },
+            };
+
+            if expired {
+                player.team = None;
+                player.contract = None;
+            }
+
+            player.save(db, None).await?;
+
+            if expired {
+                player.team = team;
+                departed.push(player);
+            }
+        }
+
+        Ok(departed)
+    }
+
+    /// Free agents take the best offer that meets their asking price from a team that can
+    /// still pay it for a pay period on top of the contracts it already has
+    pub async fn sign_free_agents(db: &Database, league_id: &str) -> Result<Vec<Signing>> {
+        let filter = doc! { "league": league_id, "team": null, "retired": { "$ne": true }, "offers.0": { "$exists": true } };
+
+        let cursor = Player::find(db, filter, None).await?;
+        let players: Vec<Player> = cursor.try_collect().await?;
+
+        let mut budgets = Player::salary_budgets(db, league_id).await?;
+        let mut signings = Vec::<Signing>::new();
+
+        for mut player in players.into_iter() {
+            let best = player.offers.iter()
+                .filter(|offer| player.offer_ratio(&offer.terms) >= 100)
+                .filter(|offer| budgets.get(&offer.team).is_some_and(|budget| *budget >= offer.terms.salary))
+                .max_by_key(|offer| player.offer_ratio(&offer.terms))
+                .cloned();
+
+            player.offers.clear();
+
+            if let Some(offer) = &best {
+                player.team = Some(offer.team.clone());
+                player.contract = Some(Contract::sign(&offer.team, &offer.terms));
+
+                if let Some(budget) = budgets.get_mut(&offer.team) {
+                    *budget -= offer.terms.salary;
+                }
+            }
+
+            player.save(db, None).await?;
+
+            if let (Some(offer), Some(id)) = (best, &player.id) {
+                signings.push(Signing {
+                    player: ID::from(id.to_string()),
+                    team: ID::from(offer.team),
+                });
+            }
+        }
+
+        Ok(signings)
+    }
+
+    /// Gold each of the league's teams has left after a pay period of its contracted salaries
+    async fn salary_budgets(db: &Database, league_id: &str) -> Result<HashMap<String, i64>> {
+        let mut budgets = fetch_team_gold(league_id).await?;
+
+        let filter = doc! { "league": league_id, "retired": { "$ne": true }, "team": { "$ne": null } };
+
+        let cursor = Player::find(db, filter, None).await?;
+        let players: Vec<Player> = cursor.try_collect().await?;
+
+        for player in players.iter() {
+            let salary = player.contract.as_ref().and_then(|contract| contract.salary).unwrap_or(0);
+
+            if let Some(budget) = player.team.as_ref().and_then(|team| budgets.get_mut(team)) {
+                *budget -= salary;
+            }
+        }
+
+        Ok(budgets)
+    }
+
+    /// Why the player can't be traded away by the team, empty if they can
+    pub fn trade_issues(&self, team_id: &str) -> Vec<String> {
+        let mut issues = Vec::<String>::new();
+
+        if self.retired {
+            issues.push(format!("{} has retired", self.name));
+        }
+
+        if self.team.as_deref() != Some(team_id) {
+            issues.push(format!("{} doesn't play for this team", self.name));
+        }
+
+        match &self.contract {
+            None => issues.push(format!("{} isn't under contract", self.name)),
+            Some(contract) if contract.no_trade => issues.push(format!("{} has a no-trade clause", self.name)),
+            Some(_) => {},
+        }
+
+        issues
+    }
+}
@@ -1,3 +1,9 @@
+pub mod contract;
+pub mod morale;
 pub mod player;
+pub mod progression;
 
+pub use contract::*;
+pub use morale::*;
 pub use player::*;
+pub use progression::*;
@@ -0,0 +1,130 @@
+use async_graphql::*;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::{bson::{self, doc, oid::ObjectId, Document}, mongodb::{Database, options::FindOptions}};
+
+use common::enums::Trait;
+
+use crate::models::Player;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MoraleEventKind {
+    /// A Greedy player wants more money
+    RaiseDemand,
+    /// A Belligerent player picked a fight in the clubhouse
+    ClubhouseFight,
+    /// A Hot Tempered player is sulking after a loss
+    Sulking,
+    /// A Lazy player has stopped showing up to practice
+    SkippingPractice,
+    /// A player is riding high
+    Inspired,
+}
+
+/// Something a player's mood led to, for the team to deal with
+#[derive(Clone, Debug, Model, Serialize, Deserialize)]
+#[model(
+    collection_name = "morale_events",
+    index(keys = r#"doc!{"team": 1, "createdAt": -1}"#),
+    index(keys = r#"doc!{"player": 1, "createdAt": -1}"#)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MoraleEvent {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub player: String,
+    pub team: String,
+    pub game: Option<String>,
+
+    pub kind: MoraleEventKind,
+    pub morale: i64,
+    pub message: String,
+
+    pub created_at: bson::DateTime,
+}
+
+/// Morale a player has to fall below, or rise above for Inspired, to set an event off
+fn threshold(kind: MoraleEventKind) -> i64 {
+    match kind {
+        MoraleEventKind::RaiseDemand => 30,
+        MoraleEventKind::ClubhouseFight => 25,
+        MoraleEventKind::Sulking => 30,
+        MoraleEventKind::SkippingPractice => 20,
+        MoraleEventKind::Inspired => 85,
+    }
+}
+
+impl MoraleEvent {
+    /// Events are raised once, when a player's morale crosses the line, not every game after
+    pub fn triggered(player: &Player, before: i64, won: bool) -> Vec<MoraleEventKind> {
+        let after = player.morale;
+        let has = |t: Trait| player.all_traits().contains(&t);
+        let fell_below = |kind| before >= threshold(kind) && after < threshold(kind);
+
+        let mut kinds = Vec::<MoraleEventKind>::new();
+
+        if has(Trait::Greedy) && fell_below(MoraleEventKind::RaiseDemand) {
+            kinds.push(MoraleEventKind::RaiseDemand);
+        }
+
+        if has(Trait::Belligerent) && fell_below(MoraleEventKind::ClubhouseFight) {
+            kinds.push(MoraleEventKind::ClubhouseFight);
+        }
+
+        if has(Trait::HotTemper) && !won && fell_below(MoraleEventKind::Sulking) {
+            kinds.push(MoraleEventKind::Sulking);
+        }
+
+        if has(Trait::Lazy) && fell_below(MoraleEventKind::SkippingPractice) {
+            kinds.push(MoraleEventKind::SkippingPractice);
+        }
+
+        let inspired = threshold(MoraleEventKind::Inspired);
+
+        if before <= inspired && after > inspired {
+            kinds.push(MoraleEventKind::Inspired);
+        }
+
+        kinds
+    }
+
+    pub fn new_event(player: &Player, team_id: &str, game: Option<String>, kind: MoraleEventKind) -> Self {
+        let message = match kind {
+            MoraleEventKind::RaiseDemand => format!("{} is demanding a raise", player.name),
+            MoraleEventKind::ClubhouseFight => format!("{} started a fight in the clubhouse", player.name),
+            MoraleEventKind::Sulking => format!("{} is sulking after the loss", player.name),
+            MoraleEventKind::SkippingPractice => format!("{} has stopped showing up to practice", player.name),
+            MoraleEventKind::Inspired => format!("{} is inspired", player.name),
+        };
+
+        MoraleEvent {
+            id: None,
+            player: player.id.as_ref().map(|id| id.to_string()).unwrap_or_default(),
+            team: team_id.to_string(),
+            game,
+            kind,
+            morale: player.morale,
+            message,
+            created_at: bson::DateTime::from(Utc::now()),
+        }
+    }
+
+    /// Newest first, for a team or a player
+    pub async fn find_latest(db: &Database, field: &str, id: &str, limit: i64) -> Result<Vec<Self>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "createdAt": -1 })
+            .limit(limit.max(0))
+            .build();
+
+        let mut filter = Document::new();
+        filter.insert(field, id);
+
+        let cursor = MoraleEvent::find(db, filter, options).await?;
+        let events: Vec<MoraleEvent> = cursor.try_collect().await?;
+
+        Ok(events)
+    }
+}
@@ -1,11 +1,19 @@
 use async_graphql::*;
 use futures::stream::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use chrono::{Duration, Utc};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use wither::prelude::*;
-use wither::{bson::{doc, oid::ObjectId, Document}, mongodb::{Database, options::FindOptions}};
+use wither::{bson::{self, doc, oid::ObjectId, Document}, mongodb::{Database, options::FindOptions}};
 
-use common::enums;
+use common::aging::{career_stage, debut_age, retirement_chance, CareerStage};
+use common::enums::{self, Ability};
+use common::morale::{clamp_morale, game_morale_change, DEFAULT_MORALE};
+use common::progression::{ability_increases, key_abilities, level_for_experience, roll_max_health, MAX_ABILITY_SCORE, MAX_LEVEL};
+use common::structs::Abilities;
+use crate::config::CONFIG;
 use crate::graphql::CreatePlayerInput;
+use crate::models::{AbilityIncrease, Contract, ContractOffer, ExperienceSource, LevelUp, MoraleEvent, Progression};
 
 /// Player representation
 #[derive(Clone, Debug, Model, Serialize, Deserialize)]
@@ -42,6 +50,84 @@ pub struct Player {
 
     pub traits: Vec::<enums::Trait>,
     pub hidden_traits: Option<Vec::<enums::Trait>>,
+
+    #[serde(default = "first_level")]
+    pub level: i64,
+    #[serde(default)]
+    pub experience: i64,
+    /// Experience earned since the last offseason
+    #[serde(default)]
+    pub season_experience: i64,
+
+    /// Missing for players created before aging, they get a debut age at the next rollover
+    #[serde(default)]
+    pub age: Option<i64>,
+    /// Retired players stay around for history but can't be signed
+    #[serde(default)]
+    pub retired: bool,
+    /// The season this player was last aged into, so a retried rollover doesn't age them twice
+    #[serde(default)]
+    pub aged_season: Option<i64>,
+    /// The season this player's contract was last counted down for
+    #[serde(default)]
+    pub contract_season: Option<i64>,
+
+    /// Hidden traits everyone knows about after they showed in a game
+    #[serde(default)]
+    pub revealed_traits: Vec<enums::Trait>,
+    #[serde(default)]
+    pub scouting: Vec<ScoutingReport>,
+
+    #[serde(default = "default_morale")]
+    pub morale: i64,
+
+    #[serde(default)]
+    pub contract: Option<Contract>,
+    /// Offers from teams while the player is a free agent
+    #[serde(default)]
+    pub offers: Vec<ContractOffer>,
+}
+
+fn default_morale() -> i64 {
+    DEFAULT_MORALE
+}
+
+/// What one team has learned about a player's hidden traits
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoutingReport {
+    pub team: String,
+    pub revealed: Vec<enums::Trait>,
+    /// A trait the scouts will report once `ready_at` has passed
+    pub pending: Option<enums::Trait>,
+    pub ready_at: Option<bson::DateTime>,
+}
+
+impl ScoutingReport {
+    fn is_ready(&self) -> bool {
+        self.ready_at.map(|ready_at| ready_at.0 <= Utc::now()).unwrap_or(true)
+    }
+
+    /// Traits this team knows, counting a finished scouting trip
+    pub fn known(&self) -> Vec<enums::Trait> {
+        let mut known = self.revealed.clone();
+
+        if let Some(pending) = self.pending {
+            if self.is_ready() {
+                known.push(pending);
+            }
+        }
+
+        known
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.pending.is_some() && !self.is_ready()
+    }
+}
+
+fn first_level() -> i64 {
+    1
 }
 
 impl Player {
@@ -49,10 +135,11 @@ impl Player {
         input: CreatePlayerInput,
     ) -> Self {
         let health = input.max_health;
+        let age = input.age.unwrap_or_else(|| debut_age(&mut rand::thread_rng(), input.race));
 
         Player {
             id: None,
-            name: String::from(input.name),
+            name: input.name,
             league: String::from(input.league),
             team: None,
             cost: input.cost,
@@ -70,13 +157,25 @@ impl Player {
             traits: input.traits,
             hidden_traits: input.hidden_traits,
             health,
+            level: first_level(),
+            experience: 0,
+            season_experience: 0,
+            age: Some(age),
+            retired: false,
+            aged_season: None,
+            contract_season: None,
+            revealed_traits: Vec::new(),
+            scouting: Vec::new(),
+            morale: DEFAULT_MORALE,
+            contract: None,
+            offers: Vec::new(),
         }
     }
 
     pub async fn find_all(db: &Database, filter: Option<Document>, sort: Option<Document>) -> Result<Vec::<Self>> {
         let options = FindOptions::builder().sort(sort).build();
 
-        let cursor = Player::find(&db, filter, options).await?;
+        let cursor = Player::find(db, filter, options).await?;
 
 
         let players: Vec<Player> = cursor.try_collect().await?;
@@ -86,23 +185,31 @@ impl Player {
 
     pub async fn find_by_id(db: &Database, id: &str) -> Option<Self> {
         let id = ObjectId::with_string(id).expect("Can't get id from String");
-        Player::find_one(&db, doc! { "_id": id }, None).await.unwrap()
+        Player::find_one(db, doc! { "_id": id }, None).await.unwrap()
     }
 
     pub async fn find_by_league(db: &Database, league_id: &str) -> Result<Vec::<Self>> {
-        let cursor = Player::find(&db, doc!{ "league": league_id }, None).await?;
+        let cursor = Player::find(db, doc!{ "league": league_id }, None).await?;
         let players: Vec<Player> = cursor.try_collect().await?;
 
         Ok(players)
     }
 
+    /// Returns how many players were removed
+    pub async fn delete_by_league(db: &Database, league_id: &str) -> Result<u64> {
+        let result = Player::delete_many(db, doc!{ "league": league_id }, None).await?;
+
+        Ok(result.deleted_count as u64)
+    }
+
     pub async fn find_by_team(db: &Database, team_id: &str) -> Result<Vec::<Self>> {
-        let cursor = Player::find(&db, doc!{ "team": team_id}, None).await?;
+        let cursor = Player::find(db, doc!{ "team": team_id}, None).await?;
         let players: Vec<Player> = cursor.try_collect().await?;
 
         Ok(players)
     }
 
+    #[allow(dead_code)]
     pub async fn set_league(db: &Database, id: &str, league_id: &str) -> Result<Self> {
         let query = doc! {
             "_id": ObjectId::with_string(id)?
@@ -126,6 +233,11 @@ impl Player {
 
         if let Some(mut player) = Player::find_one(db, Some(query), None).await? {
             player.team = Some(team_id.to_string());
+            player.offers.clear();
+
+            if player.contract.as_ref().map(|contract| contract.team != team_id).unwrap_or(true) {
+                player.contract = Some(Contract::standard(team_id));
+            }
 
             player.save(db, None).await?;
 
@@ -134,4 +246,371 @@ impl Player {
             Err(format!("Player with id: {:?} not found", id).into())
         }
     }
+
+    pub async fn release(db: &Database, id: &str) -> Result<Self> {
+        let query = doc! {
+            "_id": ObjectId::with_string(id)?
+        };
+
+        if let Some(mut player) = Player::find_one(db, Some(query), None).await? {
+            player.team = None;
+            player.contract = None;
+
+            player.save(db, None).await?;
+
+            Ok(player)
+        } else {
+            Err(format!("Player with id: {:?} not found", id).into())
+        }
+    }
+
+    pub fn abilities(&self) -> Abilities {
+        Abilities {
+            strength: self.strength,
+            dexterity: self.dexterity,
+            constitution: self.constitution,
+            intelligence: self.intelligence,
+            wisdom: self.wisdom,
+            charisma: self.charisma,
+        }
+    }
+
+    pub fn set_abilities(&mut self, abilities: &Abilities) {
+        self.strength = abilities.strength;
+        self.dexterity = abilities.dexterity;
+        self.constitution = abilities.constitution;
+        self.intelligence = abilities.intelligence;
+        self.wisdom = abilities.wisdom;
+        self.charisma = abilities.charisma;
+    }
+
+    /// Adds experience and applies every level it reaches, recording it in the player's progression
+    pub async fn award_experience(
+        db: &Database,
+        id: &str,
+        experience: i64,
+        source: ExperienceSource,
+        game: Option<String>,
+        note: Option<String>,
+    ) -> Result<Self> {
+        if experience <= 0 {
+            return Err("Experience awarded must be positive".into())
+        }
+
+        let mut player = match Player::find_by_id(db, id).await {
+            Some(player) => player,
+            None => return Err(format!("Player with id: {:?} not found", id).into()),
+        };
+
+        player.experience += experience;
+        player.season_experience += experience;
+
+        let reached = level_for_experience(player.experience).min(MAX_LEVEL);
+        let mut level_ups = Vec::<LevelUp>::new();
+
+        // Kept in its own scope, the thread rng can't be held across an await
+        {
+            let mut rng = rand::thread_rng();
+
+            while player.level < reached {
+                player.level += 1;
+
+                let mut abilities = player.abilities();
+                let increases = ability_increases(player.class, &abilities, player.level);
+
+                for (ability, amount) in increases.iter() {
+                    *abilities.get_mut(*ability) += amount;
+                }
+
+                player.set_abilities(&abilities);
+
+                // Rolled after the increases so a constitution bump counts straight away
+                let max_health = roll_max_health(&mut rng, player.class, player.constitution);
+                player.max_health += max_health;
+                player.health += max_health;
+
+                level_ups.push(LevelUp {
+                    level: player.level,
+                    max_health,
+                    abilities: increases.into_iter()
+                        .map(|(ability, amount)| AbilityIncrease { ability, amount })
+                        .collect(),
+                });
+            }
+
+            if source == ExperienceSource::GamePlayed {
+                player.reveal_on_field(&mut rng);
+            }
+        }
+
+        player.save(db, None).await?;
+
+        let mut entry = Progression {
+            id: None,
+            player: id.to_string(),
+            source,
+            experience,
+            total_experience: player.experience,
+            level: player.level,
+            level_ups,
+            game,
+            note,
+            created_at: Progression::now(),
+        };
+
+        entry.save(db, None).await?;
+
+        Ok(player)
+    }
+
+    /// One offseason for a single player: a year older, better or worse depending on where they
+    /// are in their career, and maybe retired. Returns whether they retired
+    pub fn age_one_season<R: Rng + ?Sized>(&mut self, rng: &mut R) -> bool {
+        let age = match self.age {
+            Some(age) => age + 1,
+            None => debut_age(rng, self.race),
+        };
+
+        self.age = Some(age);
+
+        match career_stage(self.race, age) {
+            CareerStage::Developing => {
+                let (primary, _) = key_abilities(self.class);
+                let mut abilities = self.abilities();
+                let score = abilities.get_mut(primary);
+
+                *score = (*score + 1).min(MAX_ABILITY_SCORE);
+
+                self.set_abilities(&abilities);
+            },
+            CareerStage::Prime => {},
+            CareerStage::Declining => {
+                let physical = [Ability::Strength, Ability::Dexterity, Ability::Constitution];
+                let mut abilities = self.abilities();
+                let score = abilities.get_mut(physical[rng.gen_range(0..physical.len())]);
+
+                *score = (*score - 1).max(3);
+
+                self.set_abilities(&abilities);
+
+                self.max_health = (self.max_health - 1).max(1);
+                self.health = self.health.min(self.max_health);
+            },
+        }
+
+        let played = self.season_experience > 0;
+        self.season_experience = 0;
+
+        if rng.gen_range(0..100) < retirement_chance(self.race, age, played) {
+            self.retired = true;
+            self.team = None;
+            self.contract = None;
+            self.offers.clear();
+        }
+
+        self.retired
+    }
+
+    /// Ages every active player in the league into `season` at rollover, returning those who
+    /// retired. Players already aged into `season` are left alone, those of them who retired are
+    /// returned again so a retried rollover still takes them off their team
+    pub async fn age_league(db: &Database, league_id: &str, season: i64) -> Result<Vec<Self>> {
+        let filter = doc! {
+            "league": league_id,
+            "$or": [ { "retired": { "$ne": true } }, { "agedSeason": season } ],
+        };
+
+        let cursor = Player::find(db, filter, None).await?;
+        let players: Vec<Player> = cursor.try_collect().await?;
+
+        let mut rng = StdRng::from_entropy();
+        let mut retired = Vec::<Player>::new();
+
+        for mut player in players.into_iter() {
+            if player.aged_season == Some(season) {
+                if player.retired {
+                    retired.push(player);
+                }
+
+                continue;
+            }
+
+            let team = player.team.clone();
+
+            player.aged_season = Some(season);
+
+            if player.age_one_season(&mut rng) {
+                player.save(db, None).await?;
+
+                // Keep the team they retired from so it can be told to drop them
+                player.team = team;
+                retired.push(player);
+            } else {
+                player.save(db, None).await?;
+            }
+        }
+
+        Ok(retired)
+    }
+
+    /// Hidden traits nobody outside the league office knows about yet
+    pub fn unrevealed_traits(&self) -> Vec<enums::Trait> {
+        self.hidden_traits.clone().unwrap_or_default().into_iter()
+            .filter(|hidden| !self.revealed_traits.contains(hidden))
+            .collect()
+    }
+
+    /// The hidden traits a viewer managing `teams` is allowed to see
+    pub fn visible_hidden_traits(&self, teams: &[String]) -> Vec<enums::Trait> {
+        let hidden = self.hidden_traits.clone().unwrap_or_default();
+
+        let owns_player = self.team.as_ref().map(|team| teams.contains(team)).unwrap_or(false);
+
+        if owns_player && CONFIG.scouting.owner_sees_hidden_traits {
+            return hidden;
+        }
+
+        let mut known = self.revealed_traits.clone();
+
+        for report in self.scouting.iter().filter(|report| teams.contains(&report.team)) {
+            known.extend(report.known());
+        }
+
+        hidden.into_iter().filter(|hidden| known.contains(hidden)).collect()
+    }
+
+    /// Hidden traits can surface during a game, revealing them to everyone
+    fn reveal_on_field<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for hidden in self.unrevealed_traits() {
+            if rng.gen_range(0..100) < CONFIG.scouting.game_reveal_chance {
+                self.revealed_traits.push(hidden);
+            }
+        }
+    }
+
+    /// Reveals a hidden trait to everyone, for when the game simulator sees it in action
+    pub async fn reveal_trait(db: &Database, id: &str, hidden: enums::Trait) -> Result<Self> {
+        let mut player = match Player::find_by_id(db, id).await {
+            Some(player) => player,
+            None => return Err(format!("Player with id: {:?} not found", id).into()),
+        };
+
+        if !player.hidden_traits.clone().unwrap_or_default().contains(&hidden) {
+            return Err(format!("{} doesn't have that hidden trait", player.name).into())
+        }
+
+        if !player.revealed_traits.contains(&hidden) {
+            player.revealed_traits.push(hidden);
+            player.save(db, None).await?;
+        }
+
+        Ok(player)
+    }
+
+    /// Sends the team's scouts after one hidden trait it doesn't know yet. With no days the
+    /// report comes back straight away, otherwise it's ready once they have passed
+    pub async fn scout(db: &Database, id: &str, team_id: &str, league_id: &str, days: i64) -> Result<Self> {
+        let mut player = match Player::find_by_id(db, id).await {
+            Some(player) => player,
+            None => return Err(format!("Player with id: {:?} not found", id).into()),
+        };
+
+        if player.league != league_id {
+            return Err("Teams can only scout players in their own league".into())
+        }
+
+        let index = match player.scouting.iter().position(|report| report.team == team_id) {
+            Some(index) => index,
+            None => {
+                player.scouting.push(ScoutingReport {
+                    team: team_id.to_string(),
+                    revealed: Vec::new(),
+                    pending: None,
+                    ready_at: None,
+                });
+
+                player.scouting.len() - 1
+            },
+        };
+
+        let hidden_traits = player.hidden_traits.clone().unwrap_or_default();
+        let revealed_traits = player.revealed_traits.clone();
+        let report = &mut player.scouting[index];
+
+        if report.in_progress() {
+            return Err("Scouts are already looking at this player".into())
+        }
+
+        // Fold a finished trip into the report before starting another
+        report.revealed = report.known();
+        report.pending = None;
+        report.ready_at = None;
+
+        let unknown: Vec<enums::Trait> = hidden_traits.into_iter()
+            .filter(|hidden| !revealed_traits.contains(hidden) && !report.revealed.contains(hidden))
+            .collect();
+
+        let found = match unknown.choose(&mut rand::thread_rng()) {
+            Some(found) => *found,
+            None => return Err("There is nothing left to learn about this player".into()),
+        };
+
+        if days > 0 {
+            report.pending = Some(found);
+            report.ready_at = Some(bson::DateTime::from(Utc::now() + Duration::days(days)));
+        } else {
+            report.revealed.push(found);
+        }
+
+        player.save(db, None).await?;
+
+        Ok(player)
+    }
+
+    /// Visible and hidden traits together, for the parts of the game that act on both
+    pub fn all_traits(&self) -> Vec<enums::Trait> {
+        let mut traits = self.traits.clone();
+        traits.extend(self.hidden_traits.clone().unwrap_or_default());
+
+        traits
+    }
+
+    /// Moves the morale of everyone on the team after a game, returning any events it set off
+    pub async fn apply_game_morale(
+        db: &Database,
+        team_id: &str,
+        game: Option<String>,
+        played: &[String],
+        won: bool,
+    ) -> Result<Vec<MoraleEvent>> {
+        let mut players = Player::find_by_team(db, team_id).await?;
+        let traits: Vec<Vec<enums::Trait>> = players.iter().map(Player::all_traits).collect();
+
+        let mut events = Vec::<MoraleEvent>::new();
+
+        for (index, player) in players.iter_mut().enumerate() {
+            let id = player.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+
+            let teammates: Vec<Vec<enums::Trait>> = traits.iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .map(|(_, traits)| traits.clone())
+                .collect();
+
+            let before = player.morale;
+            let change = game_morale_change(&traits[index], won, played.contains(&id), &teammates);
+
+            player.morale = clamp_morale(before + change);
+            player.save(db, None).await?;
+
+            for kind in MoraleEvent::triggered(player, before, won) {
+                let mut event = MoraleEvent::new_event(player, team_id, game.clone(), kind);
+                event.save(db, None).await?;
+
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
 }
@@ -0,0 +1,79 @@
+use async_graphql::*;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::{bson::{self, doc, oid::ObjectId}, mongodb::{Database, options::FindOptions}};
+
+use common::enums::Ability;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExperienceSource {
+    GamePlayed,
+    /// Playing in the farm system
+    Development,
+    Achievement,
+    Adjustment,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct AbilityIncrease {
+    pub ability: Ability,
+    pub amount: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelUp {
+    /// The level reached
+    pub level: i64,
+    pub max_health: i64,
+    pub abilities: Vec<AbilityIncrease>,
+}
+
+/// Append-only record of experience a player earned and the levels it bought them
+#[derive(Clone, Debug, Model, Serialize, Deserialize)]
+#[model(
+    collection_name = "progression",
+    index(keys = r#"doc!{"player": 1, "createdAt": -1}"#)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Progression {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub player: String,
+
+    pub source: ExperienceSource,
+    pub experience: i64,
+    /// The player's experience right after this entry
+    pub total_experience: i64,
+    pub level: i64,
+    pub level_ups: Vec<LevelUp>,
+
+    pub game: Option<String>,
+    pub note: Option<String>,
+
+    pub created_at: bson::DateTime,
+}
+
+impl Progression {
+    /// Newest first
+    pub async fn find_by_player(db: &Database, player_id: &str, offset: i64, limit: i64) -> Result<Vec<Self>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "createdAt": -1 })
+            .skip(offset.max(0))
+            .limit(limit.max(0))
+            .build();
+
+        let cursor = Progression::find(db, doc! { "player": player_id }, options).await?;
+        let entries: Vec<Progression> = cursor.try_collect().await?;
+
+        Ok(entries)
+    }
+
+    pub fn now() -> bson::DateTime {
+        bson::DateTime::from(Utc::now())
+    }
+}
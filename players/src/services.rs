@@ -0,0 +1,63 @@
+use async_graphql::Result;
+use serde_json::json;
+
+use std::collections::HashMap;
+
+use common::client::query_service;
+
+use crate::config::CONFIG;
+
+const OWNED_TEAMS: &str = r#"
+query OwnedTeams($filter: JSONObject) {
+    teams(filter: $filter) {
+        id
+    }
+}
+"#;
+
+const LEAGUE_TEAMS_GOLD: &str = r#"
+query LeagueTeamsGold($filter: JSONObject) {
+    teams(filter: $filter) {
+        id
+        gold
+    }
+}
+"#;
+
+/// How much gold each team in the league has, by team id
+pub async fn fetch_team_gold(league_id: &str) -> Result<HashMap<String, i64>> {
+    let data = query_service(
+        &CONFIG.services.teams,
+        LEAGUE_TEAMS_GOLD,
+        json!({ "filter": { "league": league_id } }),
+    ).await?;
+
+    let gold = data["teams"].as_array()
+        .map(|teams| {
+            teams.iter()
+                .filter_map(|team| Some((team["id"].as_str()?.to_string(), team["gold"].as_i64()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(gold)
+}
+
+/// Ids of every team the user manages
+pub async fn fetch_owned_teams(user_id: &str) -> Result<Vec<String>> {
+    let data = query_service(
+        &CONFIG.services.teams,
+        OWNED_TEAMS,
+        json!({ "filter": { "owner": user_id } }),
+    ).await?;
+
+    let teams = data["teams"].as_array()
+        .map(|teams| {
+            teams.iter()
+                .filter_map(|team| team["id"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(teams)
+}
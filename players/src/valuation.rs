@@ -0,0 +1,81 @@
+use async_graphql::*;
+use futures::stream::TryStreamExt;
+use wither::prelude::*;
+use wither::{bson::doc, mongodb::Database};
+
+use common::enums::Trait;
+use common::pricing::player_cost;
+
+use crate::models::Player;
+
+/// Season experience worth one percent on a player's price, about a game's worth
+const FORM_EXPERIENCE: i64 = 100;
+/// The most a good season can add to a player's price, in percent
+const MAX_FORM_BONUS: i64 = 25;
+
+#[derive(Clone, SimpleObject)]
+pub struct PriceChange {
+    pub player: ID,
+    pub name: String,
+    pub old_cost: i64,
+    pub new_cost: i64,
+}
+
+impl Player {
+    /// Traits the whole league knows about. Prices are public, so hidden traits only count once
+    /// they have been revealed
+    pub fn public_traits(&self) -> Vec<Trait> {
+        let mut traits = self.traits.clone();
+        traits.extend(self.revealed_traits.iter().copied());
+
+        traits
+    }
+
+    /// What the player is worth today from their abilities, known traits, age, level and how
+    /// much they've played this season
+    pub fn market_value(&self) -> i64 {
+        let cost = player_cost(
+            self.class,
+            self.race,
+            &self.abilities(),
+            &self.public_traits(),
+            self.age,
+            self.level,
+        );
+
+        let form = (self.season_experience / FORM_EXPERIENCE).clamp(0, MAX_FORM_BONUS);
+
+        cost * (100 + form) / 100
+    }
+
+    /// Reprices every active player in the league, returning the prices that moved
+    pub async fn reprice_league(db: &Database, league_id: &str) -> Result<Vec<PriceChange>> {
+        let filter = doc! { "league": league_id, "retired": { "$ne": true } };
+
+        let cursor = Player::find(db, filter, None).await?;
+        let players: Vec<Player> = cursor.try_collect().await?;
+
+        let mut changes = Vec::<PriceChange>::new();
+
+        for mut player in players.into_iter() {
+            let old_cost = player.cost;
+            let new_cost = player.market_value();
+
+            if old_cost == new_cost {
+                continue;
+            }
+
+            player.cost = new_cost;
+            player.save(db, None).await?;
+
+            changes.push(PriceChange {
+                player: ID::from(player.id.as_ref().map(|id| id.to_string()).unwrap_or_default()),
+                name: player.name.clone(),
+                old_cost,
+                new_cost,
+            });
+        }
+
+        Ok(changes)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod ownership;
+
+pub use ownership::*;
@@ -0,0 +1,15 @@
+use async_graphql::{Context, Result};
+use wither::mongodb::Database;
+
+use common::auth::require_owner;
+
+use crate::models::Team;
+
+/// Loads a team the current user is allowed to manage, their own unless they're an admin
+pub async fn find_owned_team(ctx: &Context<'_>, db: &Database, id: &str) -> Result<Team> {
+    let team = Team::find_by_id(db, id).await.ok_or("Can't find team by id")?;
+
+    require_owner(ctx, &team.owner, "You don't own this team")?;
+
+    Ok(team)
+}
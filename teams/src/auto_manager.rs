@@ -0,0 +1,283 @@
+use async_graphql::Result;
+use wither::mongodb::Database;
+
+use common::enums::{Class, Handedness, Position, Trait};
+
+use crate::config::CONFIG;
+use crate::farm::record_move;
+use crate::models::{Lineup, Roster, Team, TransactionReason};
+use crate::services::{fetch_team_players, PlayerSummary};
+
+/// Starting slots in the order they're filled, scarcest first
+const STARTING_SLOTS: [Position; 9] = [
+    Position::StartingPitcher,
+    Position::Catcher,
+    Position::Shortstop,
+    Position::SecondBase,
+    Position::CenterField,
+    Position::ThirdBase,
+    Position::FirstBase,
+    Position::LeftField,
+    Position::RightField,
+];
+
+/// Batting order slots from the most to the least important
+const BATTING_ORDER: [usize; 9] = [2, 3, 1, 0, 4, 5, 6, 7, 8];
+
+/// Players below half of their max health sit out
+pub fn is_injured(player: &PlayerSummary) -> bool {
+    player.health * 2 < player.max_health
+}
+
+fn class_fit(class: Class, position: Position) -> i64 {
+    match Roster::reserve_for(position) {
+        Position::ReliefPitcher => match class {
+            Class::Wizard => 4,
+            Class::Cleric => 3,
+            Class::Ranger => 2,
+            _ => 0,
+        },
+        Position::ReserveCatcher => match class {
+            Class::Paladin | Class::Fighter => 3,
+            Class::Cleric => 2,
+            _ => 0,
+        },
+        Position::InfieldReserve => match class {
+            Class::Rogue => 3,
+            Class::Ranger => 2,
+            Class::Bard => 1,
+            _ => 0,
+        },
+        Position::OutfieldReserve => match class {
+            Class::Ranger => 3,
+            Class::Rogue => 2,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+pub fn fielding_score(player: &PlayerSummary, position: Position) -> i64 {
+    let ability = match Roster::reserve_for(position) {
+        Position::ReliefPitcher => player.dexterity * 2 + player.wisdom + player.constitution,
+        Position::ReserveCatcher => player.constitution * 2 + player.wisdom + player.strength,
+        Position::InfieldReserve => player.dexterity * 2 + player.intelligence + player.strength,
+        Position::OutfieldReserve => player.dexterity * 2 + player.strength + player.constitution,
+        _ => 0,
+    };
+
+    let mut score = ability + class_fit(player.class, position);
+
+    if position == Position::StartingPitcher && player.traits.contains(&Trait::LightningArm) {
+        score += 3;
+    }
+
+    score
+}
+
+pub fn batting_score(player: &PlayerSummary) -> i64 {
+    let mut score = player.strength * 2 + player.dexterity + player.wisdom;
+
+    match player.class {
+        Class::Fighter | Class::Paladin => score += 3,
+        Class::Rogue => score += 1,
+        _ => {},
+    }
+
+    // Most pitchers throw right handed, so lefties and switch hitters see the ball better
+    if player.traits.contains(&Trait::Switch) {
+        score += 2;
+    } else if player.handedness == Handedness::Left {
+        score += 1;
+    }
+
+    if player.traits.contains(&Trait::SureShot) {
+        score += 2;
+    }
+
+    score
+}
+
+/// Builds a roster and batting order from the team's players, keeping at most `active_size` of
+/// them on the active roster. Returns whoever didn't make it, so they can go down to the farm
+pub fn optimize(players: &[PlayerSummary], designated_hitter: bool, active_size: usize) -> (Roster, Lineup, Vec<String>) {
+    let mut roster = Roster::default();
+    let mut available: Vec<&PlayerSummary> = players.iter().filter(|p| !is_injured(p)).collect();
+
+    for position in STARTING_SLOTS.iter() {
+        let best = available.iter()
+            .enumerate()
+            .max_by_key(|(_, player)| fielding_score(player, *position))
+            .map(|(index, _)| index);
+
+        if let Some(index) = best {
+            let player = available.remove(index);
+            let _ = roster.set_player(&player.id, *position);
+        }
+    }
+
+    // Whoever is left goes to the reserve list they fit best while there's room, healthy
+    // players and the best fielders first
+    let mut reserves: Vec<(&PlayerSummary, Position, i64)> = players.iter()
+        .filter(|player| !roster.contains(&player.id))
+        .map(|player| {
+            let reserve = [
+                Position::ReliefPitcher,
+                Position::ReserveCatcher,
+                Position::InfieldReserve,
+                Position::OutfieldReserve,
+            ]
+            .iter()
+            .max_by_key(|position| fielding_score(player, **position))
+            .copied()
+            .unwrap_or(Position::InfieldReserve);
+
+            (player, reserve, fielding_score(player, reserve))
+        })
+        .collect();
+
+    reserves.sort_by_key(|(player, _, score)| (is_injured(player), -score));
+
+    let open = active_size.saturating_sub(roster.player_ids().len());
+    let mut overflow = Vec::new();
+
+    for (index, (player, reserve, _)) in reserves.into_iter().enumerate() {
+        if index < open {
+            let _ = roster.set_player(&player.id, reserve);
+        } else {
+            overflow.push(player.id.clone());
+        }
+    }
+
+    let find = |id: &String| players.iter().find(|p| &p.id == id);
+
+    let mut batters: Vec<&PlayerSummary> = Vec::new();
+
+    for position in STARTING_SLOTS.iter() {
+        if designated_hitter && *position == Position::StartingPitcher {
+            continue;
+        }
+
+        if let Some(player) = roster.player_in(*position).and_then(find) {
+            batters.push(player);
+        }
+    }
+
+    if designated_hitter {
+        let hitter = players.iter()
+            .filter(|p| !is_injured(p))
+            .filter(|p| !batters.iter().any(|b| b.id == p.id))
+            .filter(|p| {
+                let position = roster.position_of(&p.id);
+                position != Some(Position::StartingPitcher) && position != Some(Position::ReliefPitcher)
+            })
+            .max_by_key(|p| batting_score(p));
+
+        if let Some(hitter) = hitter {
+            batters.push(hitter);
+        }
+    }
+
+    batters.sort_by_key(|p| -batting_score(p));
+
+    let mut lineup = Lineup::default();
+
+    {
+        let slots = lineup.slots_mut();
+
+        for (batter, slot) in batters.iter().zip(BATTING_ORDER.iter()) {
+            *slots[*slot] = Some(batter.id.clone());
+        }
+    }
+
+    (roster, lineup, overflow)
+}
+
+/// Re-optimizes the team's roster and lineup from its active players
+pub async fn auto_manage(db: &Database, team: &mut Team, designated_hitter: bool) -> Result<()> {
+    let team_id = match &team.id {
+        Some(id) => id.to_string(),
+        None => return Err("Team hasn't been saved".into()),
+    };
+
+    let mut players = fetch_team_players(&team_id).await?;
+
+    // Farm and injured list players aren't available until they're called up or activated
+    players.retain(|player| team.is_active(&player.id));
+
+    let (roster, lineup, overflow) = optimize(&players, designated_hitter, CONFIG.farm.active_size);
+
+    if team.farm.len() + overflow.len() > CONFIG.farm.reserve_size {
+        return Err(format!("{} has more players than its active roster and farm can hold", team.name).into())
+    }
+
+    team.roster = roster;
+    team.lineup = lineup;
+    team.farm.extend(overflow.iter().cloned());
+
+    team.save_changes(db).await?;
+
+    for player_id in overflow.iter() {
+        record_move(db, team, TransactionReason::SendDown, player_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs before each scheduled game: auto managed teams are re-optimized and
+/// every team has to be legal to take the field
+pub async fn prepare_for_game(db: &Database, team: &mut Team, designated_hitter: bool) -> Result<()> {
+    if team.auto_manage {
+        auto_manage(db, team, designated_hitter).await?;
+    }
+
+    team.ensure_legal(designated_hitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: usize, health: i64) -> PlayerSummary {
+        PlayerSummary {
+            id: id.to_string(),
+            cost: 1000,
+            class: Class::Fighter,
+            handedness: Handedness::Right,
+            health,
+            max_health: 10,
+            strength: 10,
+            dexterity: 10 + id as i64,
+            constitution: 10,
+            intelligence: 10,
+            wisdom: 10,
+            charisma: 10,
+            traits: Vec::new(),
+            contract: None,
+            league: None,
+            team: None,
+        }
+    }
+
+    #[test]
+    fn the_active_roster_is_capped() {
+        let players: Vec<PlayerSummary> = (0..15).map(|id| player(id, 10)).collect();
+
+        let (roster, _, overflow) = optimize(&players, false, 12);
+
+        assert_eq!(roster.player_ids().len(), 12);
+        assert_eq!(overflow.len(), 3);
+        assert!(overflow.iter().all(|id| !roster.contains(id)));
+    }
+
+    #[test]
+    fn injured_reserves_go_down_first() {
+        let mut players: Vec<PlayerSummary> = (0..11).map(|id| player(id, 10)).collect();
+        players.push(player(20, 2));
+
+        let (roster, _, overflow) = optimize(&players, false, 11);
+
+        assert_eq!(overflow, vec!["20".to_string()]);
+        assert!(!roster.contains("20"));
+    }
+}
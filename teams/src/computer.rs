@@ -0,0 +1,142 @@
+use async_graphql::Result;
+use wither::mongodb::Database;
+
+use common::enums::{ComputerStrategy, Position};
+
+use crate::auto_manager::{auto_manage, batting_score, fielding_score, is_injured};
+use crate::farm::place_new_player;
+use crate::models::{Roster, Team, TransactionDetails, TransactionReason};
+use crate::services::{assign_player, fetch_designated_hitter, fetch_free_agents, PlayerSummary};
+
+const RESERVE_GROUPS: [Position; 4] = [
+    Position::ReliefPitcher,
+    Position::ReserveCatcher,
+    Position::InfieldReserve,
+    Position::OutfieldReserve,
+];
+
+/// What a player is worth to a computer manager, ignoring price
+pub fn player_value(player: &PlayerSummary) -> i64 {
+    let fielding = RESERVE_GROUPS.iter()
+        .map(|position| fielding_score(player, *position))
+        .max()
+        .unwrap_or(0);
+
+    let mut value = fielding + batting_score(player);
+
+    if is_injured(player) {
+        value /= 2;
+    }
+
+    value
+}
+
+/// The reserve list the player fits best
+pub fn best_group(player: &PlayerSummary) -> Position {
+    RESERVE_GROUPS.iter()
+        .max_by_key(|position| fielding_score(player, **position))
+        .copied()
+        .unwrap_or(Position::InfieldReserve)
+}
+
+fn group_size(roster: &Roster, group: Position) -> usize {
+    roster.player_ids().iter()
+        .filter(|id| roster.position_of(id).map(Roster::reserve_for) == Some(group))
+        .count()
+}
+
+/// Picks the free agent the strategy wants most out of those the team can afford
+pub fn choose_pick<'a>(
+    strategy: ComputerStrategy,
+    roster: &Roster,
+    gold: i64,
+    free_agents: &'a [PlayerSummary],
+) -> Option<&'a PlayerSummary> {
+    let affordable = free_agents.iter().filter(|p| p.cost <= gold);
+
+    match strategy {
+        ComputerStrategy::BestAvailable => affordable.max_by_key(|p| player_value(p)),
+        ComputerStrategy::Balanced => {
+            let thinnest = RESERVE_GROUPS.iter()
+                .min_by_key(|group| group_size(roster, **group))
+                .copied()
+                .unwrap_or(Position::InfieldReserve);
+
+            affordable.max_by_key(|p| (best_group(p) == thinnest, player_value(p)))
+        },
+        ComputerStrategy::Bargain => affordable.max_by_key(|p| player_value(p) * 1000 / p.cost.max(1)),
+    }
+}
+
+/// The most the strategy will bid for a player in an auction
+pub fn max_bid(strategy: ComputerStrategy, roster: &Roster, gold: i64, player: &PlayerSummary) -> i64 {
+    let bid = match strategy {
+        ComputerStrategy::BestAvailable => player.cost * 3 / 2,
+        ComputerStrategy::Balanced => {
+            let group = best_group(player);
+            let needed = RESERVE_GROUPS.iter().all(|other| group_size(roster, group) <= group_size(roster, *other));
+
+            if needed { player.cost * 5 / 4 } else { player.cost }
+        },
+        ComputerStrategy::Bargain => player.cost * 9 / 10,
+    };
+
+    bid.min(gold).max(0)
+}
+
+/// Whether the computer takes a trade, gold is positive when it's paid to the computer team
+pub fn accepts_trade(
+    strategy: ComputerStrategy,
+    outgoing: &[PlayerSummary],
+    incoming: &[PlayerSummary],
+    gold: i64,
+) -> bool {
+    let value = |players: &[PlayerSummary]| -> i64 {
+        players.iter().map(|p| player_value(p) * 1000 + p.cost).sum()
+    };
+
+    let given = value(outgoing);
+    let received = value(incoming) + gold;
+
+    let margin = match strategy {
+        ComputerStrategy::BestAvailable => 100,
+        ComputerStrategy::Balanced => 110,
+        ComputerStrategy::Bargain => 120,
+    };
+
+    received * 100 >= given * margin
+}
+
+/// Makes the team's next draft pick and resets its lineup around the new player
+pub async fn draft_pick(db: &Database, team: &mut Team) -> Result<Option<PlayerSummary>> {
+    let strategy = team.computer.ok_or("Team isn't run by a computer manager")?;
+
+    let team_id = match &team.id {
+        Some(id) => id.to_string(),
+        None => return Err("Team hasn't been saved".into()),
+    };
+
+    let free_agents = fetch_free_agents(&team.league).await?;
+
+    let pick = match choose_pick(strategy, &team.roster, team.gold, &free_agents) {
+        Some(pick) => pick.clone(),
+        None => return Ok(None),
+    };
+
+    assign_player(&pick.id, &team_id).await?;
+
+    *team = Team::modify_gold(
+        db, &team_id, -pick.cost,
+        TransactionReason::Draft,
+        TransactionDetails::player(&pick.id),
+    ).await?;
+
+    // Goes to the farm when the active roster is full, auto manage only picks from active players
+    place_new_player(team, &pick)?;
+
+    let designated_hitter = fetch_designated_hitter(&team.league).await?;
+
+    auto_manage(db, team, designated_hitter).await?;
+
+    Ok(Some(pick))
+}
@@ -32,6 +32,79 @@ pub struct ServerConfig {
     pub port: u16,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServicesConfig {
+    pub leagues: String,
+    pub players: String,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        ServicesConfig {
+            leagues: String::from("http://localhost:8001"),
+            players: String::from("http://localhost:8003"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScoutingConfig {
+    /// Gold to have a hidden trait reported straight away
+    pub cost: i64,
+    /// Days a free scouting trip takes
+    pub days: i64,
+}
+
+impl Default for ScoutingConfig {
+    fn default() -> Self {
+        ScoutingConfig {
+            cost: 2500,
+            days: 3,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FarmConfig {
+    /// Most players a team can carry on its active roster
+    pub active_size: usize,
+    /// Most players a team can keep developing in its farm system
+    pub reserve_size: usize,
+    /// Call-ups from the farm each season, coming off the injured list doesn't count
+    pub call_ups_per_season: i64,
+    /// Shortest stay on the injured list
+    pub injured_list_days: i64,
+}
+
+impl Default for FarmConfig {
+    fn default() -> Self {
+        FarmConfig {
+            active_size: 20,
+            reserve_size: 10,
+            call_ups_per_season: 10,
+            injured_list_days: 10,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BallparkConfig {
+    /// Gold for each seat added, shrinking the ballpark isn't refunded
+    pub seat_cost: i64,
+}
+
+impl Default for BallparkConfig {
+    fn default() -> Self {
+        BallparkConfig {
+            seat_cost: 50,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
@@ -40,6 +113,14 @@ pub struct Settings {
     pub server: ServerConfig,
     pub redis: RedisConfig,
     pub session: SessionConfig,
+    #[serde(default)]
+    pub services: ServicesConfig,
+    #[serde(default)]
+    pub scouting: ScoutingConfig,
+    #[serde(default)]
+    pub farm: FarmConfig,
+    #[serde(default)]
+    pub ballpark: BallparkConfig,
 }
 
 impl Settings {
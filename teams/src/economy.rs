@@ -0,0 +1,293 @@
+use async_graphql::{InputObject, Result, SimpleObject, ID};
+use wither::mongodb::Database;
+
+use common::enums::Class;
+
+use crate::models::{SettledGame, Team, TransactionDetails, TransactionReason};
+use crate::services::{
+    apply_game_morale, award_farm_experience, award_game_experience, fetch_team_players, PlayerSummary,
+};
+
+/// Charisma a Bard or Paladin needs before fans buy their merchandise
+const STAR_CHARISMA: i64 = 15;
+
+/// A league's income settings as passed on by the leagues service
+#[derive(Clone, Debug, InputObject)]
+pub struct EconomyRates {
+    /// Gold per fan at a home game
+    pub ticket_price: i64,
+    /// Percent of the ballpark that fills up before any home wins
+    pub base_attendance: i64,
+    /// Extra percent of the ballpark that fills up for every home win
+    pub attendance_per_home_win: i64,
+    /// Paid to the winner of a playoff game
+    pub playoff_bonus: i64,
+    /// Gold per point of charisma above the star threshold, per game
+    pub merchandise_rate: i64,
+}
+
+/// The final score of a simulated game
+#[derive(Clone, Debug, InputObject)]
+pub struct GameResult {
+    pub game: ID,
+    pub home: ID,
+    pub away: ID,
+    pub home_score: i64,
+    pub away_score: i64,
+    #[graphql(default)]
+    pub playoff: bool,
+}
+
+/// What a team earned from one game
+#[derive(Clone, SimpleObject)]
+pub struct GameIncome {
+    pub team: ID,
+    pub gate_receipts: i64,
+    pub playoff_bonus: i64,
+    pub merchandise: i64,
+}
+
+/// Fans fill more of the ballpark the more the team wins at home
+pub fn gate_receipts(team: &Team, rates: &EconomyRates) -> i64 {
+    let percent = (rates.base_attendance + team.record.home_wins * rates.attendance_per_home_win)
+        .clamp(0, 100);
+
+    team.ballpark.capacity * percent / 100 * rates.ticket_price
+}
+
+/// Star Bards and Paladins sell jerseys whether the team wins or not
+pub fn merchandise(players: &[PlayerSummary], rates: &EconomyRates) -> i64 {
+    players.iter()
+        .filter(|player| player.class == Class::Bard || player.class == Class::Paladin)
+        .map(|player| (player.charisma - STAR_CHARISMA).max(0) * rates.merchandise_rate)
+        .sum()
+}
+
+fn record_result(team: &mut Team, home: bool, won: bool, playoff: bool) {
+    let record = &mut team.record;
+
+    if playoff {
+        if won { record.playoff_wins += 1 } else { record.playoff_losses += 1 }
+        return;
+    }
+
+    if won { record.wins += 1 } else { record.losses += 1 }
+
+    if home {
+        if won { record.home_wins += 1 } else { record.home_losses += 1 }
+    }
+}
+
+/// The starting pitcher and everyone in the batting order
+fn players_in_game(team: &Team) -> Vec<String> {
+    let mut players: Vec<String> = team.lineup.slots().iter()
+        .filter_map(|slot| (*slot).clone())
+        .collect();
+
+    if let Some(pitcher) = &team.roster.starting_pitcher {
+        if !players.contains(pitcher) {
+            players.push(pitcher.clone());
+        }
+    }
+
+    players
+}
+
+async fn post_income(
+    db: &Database,
+    team: &mut Team,
+    amount: i64,
+    reason: TransactionReason,
+    game: &ID,
+) -> Result<()> {
+    if amount <= 0 {
+        return Ok(())
+    }
+
+    let team_id = team.id.clone().ok_or("Team hasn't been saved")?.to_string();
+
+    *team = Team::modify_gold(
+        db, &team_id, amount, reason,
+        TransactionDetails {
+            game: Some(game.clone()),
+            ..TransactionDetails::default()
+        },
+    ).await?;
+
+    Ok(())
+}
+
+/// Run after each simulated game: updates both teams' records, hands out
+/// experience, moves morale and posts the gate, playoff and merchandise income to their ledgers.
+/// Each step is recorded as it's done, so a retry after a failure picks up where it stopped
+pub async fn settle_game(
+    db: &Database,
+    league_id: &str,
+    result: &GameResult,
+    rates: &EconomyRates,
+) -> Result<Vec<GameIncome>> {
+    if result.home == result.away {
+        return Err("A team can't play itself".into())
+    }
+
+    // Nothing is claimed until both teams check out, a bad result can be sent again fixed
+    for team_id in [&result.home, &result.away].iter() {
+        let team = Team::find_by_id(db, team_id).await.ok_or("Can't find team by id")?;
+
+        if team.league != league_id {
+            return Err(format!("{} doesn't play in this league", team.name).into())
+        }
+    }
+
+    let mut settled = SettledGame::claim(db, league_id, &result.game).await?;
+    let mut incomes = Vec::<GameIncome>::new();
+
+    for (team_id, home) in [(&result.home, true), (&result.away, false)].iter() {
+        let step = |name: &str| format!("{}:{}", name, team_id.as_str());
+
+        let mut team = Team::find_by_id(db, team_id).await.ok_or("Can't find team by id")?;
+
+        let (scored, allowed) = if *home {
+            (result.home_score, result.away_score)
+        } else {
+            (result.away_score, result.home_score)
+        };
+        let won = scored > allowed;
+
+        if !settled.is_done(&step("record")) {
+            record_result(&mut team, *home, won, result.playoff);
+            team.save_changes(db).await?;
+            settled.step_done(db, &step("record")).await?;
+        }
+
+        let played = players_in_game(&team);
+
+        if !settled.is_done(&step("experience")) {
+            award_game_experience(&result.game, &played, won).await?;
+            settled.step_done(db, &step("experience")).await?;
+        }
+
+        if !settled.is_done(&step("farm")) {
+            award_farm_experience(&result.game, &team.farm).await?;
+            settled.step_done(db, &step("farm")).await?;
+        }
+
+        if !settled.is_done(&step("morale")) {
+            apply_game_morale(team_id, &result.game, &played, won).await?;
+            settled.step_done(db, &step("morale")).await?;
+        }
+
+        let gate = if *home { gate_receipts(&team, rates) } else { 0 };
+        let bonus = if result.playoff && won { rates.playoff_bonus } else { 0 };
+
+        let players = fetch_team_players(team_id).await?;
+        let sales = merchandise(&players, rates);
+
+        let payouts = [
+            ("gate", gate, TransactionReason::GateReceipts),
+            ("bonus", bonus, TransactionReason::PlayoffBonus),
+            ("merchandise", sales, TransactionReason::Merchandise),
+        ];
+
+        for (name, amount, reason) in payouts.iter() {
+            if !settled.is_done(&step(name)) {
+                post_income(db, &mut team, *amount, *reason, &result.game).await?;
+                settled.step_done(db, &step(name)).await?;
+            }
+        }
+
+        incomes.push(GameIncome {
+            team: (*team_id).clone(),
+            gate_receipts: gate,
+            playoff_bonus: bonus,
+            merchandise: sales,
+        });
+    }
+
+    settled.finish(db).await?;
+
+    Ok(incomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::enums::Handedness;
+
+    fn rates() -> EconomyRates {
+        EconomyRates {
+            ticket_price: 2,
+            base_attendance: 50,
+            attendance_per_home_win: 10,
+            playoff_bonus: 1000,
+            merchandise_rate: 100,
+        }
+    }
+
+    fn player(class: Class, charisma: i64) -> PlayerSummary {
+        PlayerSummary {
+            id: String::from("player"),
+            cost: 1000,
+            class,
+            handedness: Handedness::Right,
+            health: 10,
+            max_health: 10,
+            strength: 10,
+            dexterity: 10,
+            constitution: 10,
+            intelligence: 10,
+            wisdom: 10,
+            charisma,
+            traits: Vec::new(),
+            contract: None,
+            league: None,
+            team: None,
+        }
+    }
+
+    #[test]
+    fn gate_receipts_grow_with_home_wins() {
+        let mut team = Team::new_team("Owls", "league", "owner");
+
+        assert_eq!(gate_receipts(&team, &rates()), 10000 * 50 / 100 * 2);
+
+        team.record.home_wins = 2;
+
+        assert_eq!(gate_receipts(&team, &rates()), 10000 * 70 / 100 * 2);
+    }
+
+    #[test]
+    fn gate_receipts_stop_at_a_full_ballpark() {
+        let mut team = Team::new_team("Owls", "league", "owner");
+        team.record.home_wins = 20;
+
+        assert_eq!(gate_receipts(&team, &rates()), 10000 * 2);
+    }
+
+    #[test]
+    fn merchandise_only_counts_star_bards_and_paladins() {
+        let players = vec![
+            player(Class::Bard, STAR_CHARISMA + 3),
+            player(Class::Paladin, STAR_CHARISMA + 1),
+            player(Class::Bard, STAR_CHARISMA - 5),
+            player(Class::Fighter, STAR_CHARISMA + 10),
+        ];
+
+        assert_eq!(merchandise(&players, &rates()), 4 * 100);
+    }
+
+    #[test]
+    fn playoff_games_are_kept_out_of_the_regular_record() {
+        let mut team = Team::new_team("Owls", "league", "owner");
+
+        record_result(&mut team, true, true, false);
+        record_result(&mut team, false, false, false);
+        record_result(&mut team, true, true, true);
+
+        assert_eq!(team.record.wins, 1);
+        assert_eq!(team.record.losses, 1);
+        assert_eq!(team.record.home_wins, 1);
+        assert_eq!(team.record.home_losses, 0);
+        assert_eq!(team.record.playoff_wins, 1);
+    }
+}
@@ -0,0 +1,151 @@
+use async_graphql::Result;
+use chrono::{Duration, Utc};
+use wither::bson;
+use wither::mongodb::Database;
+use wither::prelude::*;
+
+use common::enums::Position;
+
+use crate::auto_manager::is_injured;
+use crate::computer::best_group;
+use crate::config::CONFIG;
+use crate::models::{InjuredListEntry, Team, Transaction, TransactionDetails, TransactionReason};
+use crate::services::{fetch_player, PlayerSummary};
+
+fn team_id(team: &Team) -> Result<String> {
+    match &team.id {
+        Some(id) => Ok(id.to_string()),
+        None => Err("Team hasn't been saved".into()),
+    }
+}
+
+/// Roster moves go in the ledger next to gold movements, without any gold changing hands
+pub(crate) async fn record_move(db: &Database, team: &Team, reason: TransactionReason, player_id: &str) -> Result<()> {
+    let mut transaction = Transaction::new_transaction(
+        &team_id(team)?, 0, team.gold, reason,
+        TransactionDetails::player(player_id),
+    );
+
+    transaction.save(db, None).await?;
+
+    Ok(())
+}
+
+fn ensure_active_spot(team: &Team) -> Result<()> {
+    if team.active_roster_full() {
+        Err(format!("The active roster is full at {} players", CONFIG.farm.active_size).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Puts a newly signed or drafted player on the active roster, or in the farm once the
+/// active roster is full
+pub fn place_new_player(team: &mut Team, player: &PlayerSummary) -> Result<()> {
+    if !team.active_roster_full() {
+        return team.roster.set_player(&player.id, best_group(player))
+    }
+
+    if team.farm.len() >= CONFIG.farm.reserve_size {
+        return Err(format!("{} has no room on its roster or in its farm", team.name).into())
+    }
+
+    team.farm.push(player.id.clone());
+
+    Ok(())
+}
+
+/// Sends an active player down to the farm to develop
+pub async fn send_down(db: &Database, team: &mut Team, player_id: &str) -> Result<()> {
+    if !team.roster.contains(player_id) {
+        return Err("Player is not on the active roster".into())
+    }
+
+    if team.farm.len() >= CONFIG.farm.reserve_size {
+        return Err(format!("The farm is full at {} players", CONFIG.farm.reserve_size).into())
+    }
+
+    team.roster.remove_player(player_id);
+    team.lineup.remove_player(player_id);
+    team.farm.push(player_id.to_string());
+
+    team.save_changes(db).await?;
+
+    record_move(db, team, TransactionReason::SendDown, player_id).await
+}
+
+/// Brings a player up from the farm, into the reserve list they fit best unless a position
+/// is given. Each team only gets so many call-ups a season
+pub async fn call_up(db: &Database, team: &mut Team, player_id: &str, position: Option<Position>) -> Result<()> {
+    if !team.farm.iter().any(|id| id == player_id) {
+        return Err("Player is not in the farm".into())
+    }
+
+    if team.call_ups >= CONFIG.farm.call_ups_per_season {
+        return Err(format!("{} has used all {} call-ups this season", team.name, CONFIG.farm.call_ups_per_season).into())
+    }
+
+    ensure_active_spot(team)?;
+
+    let position = match position {
+        Some(position) => position,
+        None => best_group(&fetch_player(player_id).await?),
+    };
+
+    team.roster.set_player(player_id, position)?;
+    team.farm.retain(|id| id != player_id);
+    team.call_ups += 1;
+
+    team.save_changes(db).await?;
+
+    record_move(db, team, TransactionReason::CallUp, player_id).await
+}
+
+/// Moves a hurt player off the active roster, opening their spot until they're activated
+pub async fn place_on_injured_list(db: &Database, team: &mut Team, player_id: &str) -> Result<()> {
+    let position = team.roster.position_of(player_id).ok_or("Player is not on the active roster")?;
+
+    if !is_injured(&fetch_player(player_id).await?) {
+        return Err("Only injured players can go on the injured list".into())
+    }
+
+    let now = Utc::now();
+
+    team.roster.remove_player(player_id);
+    team.lineup.remove_player(player_id);
+    team.injured_list.push(InjuredListEntry {
+        player: player_id.to_string(),
+        position,
+        placed_at: bson::DateTime::from(now),
+        eligible_at: bson::DateTime::from(now + Duration::days(CONFIG.farm.injured_list_days)),
+    });
+
+    team.save_changes(db).await?;
+
+    record_move(db, team, TransactionReason::InjuredList, player_id).await
+}
+
+/// Brings a player back from the injured list once their minimum stay is over, into the
+/// position they left unless another is given
+pub async fn activate(db: &Database, team: &mut Team, player_id: &str, position: Option<Position>) -> Result<()> {
+    let index = team.injured_list.iter()
+        .position(|entry| entry.player == player_id)
+        .ok_or("Player is not on the injured list")?;
+
+    let entry = &team.injured_list[index];
+
+    if !entry.is_eligible() {
+        return Err(format!("Player can't be activated before {}", entry.eligible_at.0.to_rfc2822()).into())
+    }
+
+    ensure_active_spot(team)?;
+
+    let position = position.unwrap_or(entry.position);
+
+    team.roster.set_player(player_id, position)?;
+    team.injured_list.remove(index);
+
+    team.save_changes(db).await?;
+
+    record_move(db, team, TransactionReason::Activation, player_id).await
+}
@@ -1,15 +1,35 @@
 use std::collections::HashMap;
 
-use crate::models::{Lineup, Team};
-
-use common::*;
-use common::enums::Position;
+use chrono::{DateTime, Utc};
+
+use crate::auth::find_owned_team;
+use async_graphql::guard::Guard;
+use crate::auto_manager::{auto_manage, prepare_for_game};
+use crate::computer::{accepts_trade, draft_pick, max_bid};
+use crate::economy::{settle_game, EconomyRates, GameIncome, GameResult};
+use crate::farm::{activate, call_up, place_new_player, place_on_injured_list, send_down};
+use crate::keepers::{rollover, select_keepers};
+use crate::models::{
+    Ballpark, InjuredListEntry, Keeper, Lineup, Record, RosterIssue, Team,
+    TeamRosterIssues, Transaction, TransactionDetails, TransactionReason,
+};
+use crate::config::CONFIG;
+use crate::payroll::{contracted_salaries, run_payroll, PayrollReport};
+use crate::scouting::{scout, ScoutingMethod};
+use crate::services::{
+    exercise_option, fetch_designated_hitter, fetch_player, fetch_release_refund, fetch_team_players,
+    fetch_trade_issues, negotiate_contract, release_player, NegotiationResult,
+};
+
+use common::auth::{current_user, require_admin, RoleGuard};
+use common::enums::{ComputerStrategy, ContractOption, Position, RefundPolicy, Role};
 use async_graphql::*;
-use jsonwebtoken::TokenData;
 use wither::prelude::*;
 use wither::{mongodb::Database};
 
 use common::filter::process_filter;
+use futures::future::try_join_all;
+use serde_json::json;
 
 pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
 
@@ -61,6 +81,139 @@ impl Team {
     async fn lineup(&self) -> &Lineup {
         &self.lineup
     }
+
+    async fn auto_manage(&self) -> bool {
+        self.auto_manage
+    }
+
+    async fn computer(&self) -> Option<ComputerStrategy> {
+        self.computer
+    }
+
+    async fn keepers(&self) -> &Vec<Keeper> {
+        &self.keepers
+    }
+
+    async fn ballpark(&self) -> &Ballpark {
+        &self.ballpark
+    }
+
+    async fn record(&self) -> &Record {
+        &self.record
+    }
+
+    /// Players developing in the minors
+    async fn farm(&self) -> Vec<Player> {
+        vec_to_players(&self.farm)
+    }
+
+    async fn injured_list(&self) -> &Vec<InjuredListEntry> {
+        &self.injured_list
+    }
+
+    async fn call_ups_remaining(&self) -> i64 {
+        (CONFIG.farm.call_ups_per_season - self.call_ups).max(0)
+    }
+
+    /// Gold movements, newest first
+    async fn transactions(
+        &self, ctx: &Context<'_>,
+        #[graphql(default)] offset: i64,
+        #[graphql(default = 20)] limit: i64,
+    ) -> Result<Vec<Transaction>> {
+        let db: &Database = ctx.data()?;
+
+        match &self.id {
+            Some(id) => Transaction::find_by_team(db, &id.to_string(), offset, limit.min(100)).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn transaction_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let db: &Database = ctx.data()?;
+
+        match &self.id {
+            Some(id) => Transaction::count_by_team(db, &id.to_string()).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Checked against the league's designated hitter rule
+    async fn roster_issues(&self) -> Result<Vec<RosterIssue>> {
+        let designated_hitter = fetch_designated_hitter(&self.league).await?;
+
+        Ok(self.validate_roster(designated_hitter))
+    }
+}
+
+#[Object]
+impl Transaction {
+    async fn id(&self) -> ID {
+        if let Some(id) = &self.id {
+            ID::from(id)
+        } else {
+            ID::from("")
+        }
+    }
+
+    async fn team(&self) -> ID {
+        ID::from(&self.team)
+    }
+
+    async fn amount(&self) -> i64 {
+        self.amount
+    }
+
+    async fn balance(&self) -> i64 {
+        self.balance
+    }
+
+    async fn reason(&self) -> TransactionReason {
+        self.reason
+    }
+
+    async fn counterparty(&self) -> Option<ID> {
+        self.counterparty.as_ref().map(ID::from)
+    }
+
+    async fn player(&self) -> Option<Player> {
+        option_to_player(&self.player)
+    }
+
+    async fn trade(&self) -> Option<ID> {
+        self.trade.as_ref().map(ID::from)
+    }
+
+    async fn game(&self) -> Option<ID> {
+        self.game.as_ref().map(ID::from)
+    }
+
+    async fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.0
+    }
+}
+
+#[Object]
+impl InjuredListEntry {
+    async fn player(&self) -> Player {
+        Player { id: ID::from(&self.player) }
+    }
+
+    async fn position(&self) -> Position {
+        self.position
+    }
+
+    async fn placed_at(&self) -> DateTime<Utc> {
+        self.placed_at.0
+    }
+
+    async fn eligible_at(&self) -> DateTime<Utc> {
+        self.eligible_at.0
+    }
 }
 
 // USER
@@ -93,15 +246,10 @@ pub struct Player {
 }*/
 
 fn option_to_player(item: &Option<String>) -> Option<Player> {
-    if let Some(item) = item {
-        Some(Player { id: ID::from(item) })
-    } else {
-        None
-        //Player { id: ID::from("") }
-    }
+    item.as_ref().map(|item| Player { id: ID::from(item) })
 }
 
-fn vec_to_players(list: &Vec<String>) -> Vec<Player> {
+fn vec_to_players(list: &[String]) -> Vec<Player> {
     list.iter().map(|id| Player{
         id: ID::from(id)
     })
@@ -248,6 +396,98 @@ impl Query {
         }
     }
 
+    /// What a computer team would bid for a player, null when it passes. Called by the
+    /// services running auctions, so managers can't read a computer's limit
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn computer_bid(&self, ctx: &Context<'_>, team: ID, player: ID, current_bid: i64) -> Result<Option<i64>> {
+        let db: &Database = ctx.data()?;
+
+        let team = Team::find_by_id(db, &team).await.ok_or("Can't find team by id")?;
+        let strategy = team.computer.ok_or("Team isn't run by a computer manager")?;
+
+        let player = fetch_player(&player).await?;
+        let bid = max_bid(strategy, &team.roster, team.gold, &player);
+
+        if bid > current_bid {
+            Ok(Some(current_bid + 1))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Whether a computer team accepts a trade, gold is what the computer team is paid. Called
+    /// by the services settling trades, so managers can't probe what a computer accepts
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn computer_trade_response(
+        &self, ctx: &Context<'_>,
+        team: ID,
+        outgoing: Vec<ID>,
+        incoming: Vec<ID>,
+        gold: i64,
+    ) -> Result<bool> {
+        let db: &Database = ctx.data()?;
+
+        let team_id = team.to_string();
+        let team = Team::find_by_id(db, &team).await.ok_or("Can't find team by id")?;
+        let strategy = team.computer.ok_or("Team isn't run by a computer manager")?;
+
+        if outgoing.iter().any(|id| !team.roster.contains(id)) {
+            return Ok(false)
+        }
+
+        let outgoing_ids: Vec<String> = outgoing.iter().map(|id| id.to_string()).collect();
+
+        if !fetch_trade_issues(&team_id, &outgoing_ids).await?.is_empty() {
+            return Ok(false)
+        }
+
+        if gold < 0 && team.gold < -gold {
+            return Ok(false)
+        }
+
+        let outgoing = try_join_all(outgoing.iter().map(|id| fetch_player(id))).await?;
+        let incoming = try_join_all(incoming.iter().map(|id| fetch_player(id))).await?;
+
+        Ok(accepts_trade(strategy, &outgoing, &incoming, gold))
+    }
+
+    /// Contract problems with a trade, empty when both sides are free to deal
+    async fn trade_issues(
+        &self,
+        team: ID,
+        outgoing: Vec<ID>,
+        counterparty: ID,
+        incoming: Vec<ID>,
+    ) -> Result<Vec<String>> {
+        let outgoing: Vec<String> = outgoing.iter().map(|id| id.to_string()).collect();
+        let incoming: Vec<String> = incoming.iter().map(|id| id.to_string()).collect();
+
+        let mut issues = fetch_trade_issues(&team, &outgoing).await?;
+        issues.extend(fetch_trade_issues(&counterparty, &incoming).await?);
+
+        Ok(issues)
+    }
+
+    /// Teams in a league that aren't fit to play under its designated hitter rule, checked
+    /// before the season starts
+    async fn league_roster_issues(&self, ctx: &Context<'_>, league: ID) -> Result<Vec<TeamRosterIssues>> {
+        let db: &Database = ctx.data()?;
+
+        let designated_hitter = fetch_designated_hitter(&league).await?;
+        let teams = Team::find_by_league_id(db, &league).await?;
+
+        let issues = teams.iter()
+            .map(|team| TeamRosterIssues {
+                team: team.id.as_ref().map(ID::from).unwrap_or_else(|| ID::from("")),
+                name: team.name.clone(),
+                issues: team.validate_roster(designated_hitter),
+            })
+            .filter(|team| !team.issues.is_empty())
+            .collect();
+
+        Ok(issues)
+    }
+
     #[graphql(entity)]
     async fn find_user_by_id(&self, id: ID) -> User {
         User { id }
@@ -276,60 +516,444 @@ pub struct Mutation;
 impl Mutation {
     async fn create_team(&self, ctx: &Context<'_>, name: String, league_id: ID) -> Result<Team, Error> {
         let db: &Database = ctx.data()?;
-        let redis_client: &redis::Client = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        let mut new_team = Team::new_team(&name, &league_id, &current_user.id);
 
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        if new_team.save(db, None).await.is_ok() {
+            Ok(new_team)
+        } else {
+            Err(Error::new("Can't create team, bad user"))
+        }
+    }
 
-        if let Some(current_user) = maybe_current_user {
-            let mut new_team = Team::new_team(&name, &league_id, &current_user.id);
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn create_computer_team(
+        &self, ctx: &Context<'_>,
+        name: String,
+        league_id: ID,
+        owner: ID,
+        strategy: ComputerStrategy,
+    ) -> Result<Team> {
+        let db: &Database = ctx.data()?;
 
-            if let Ok(_) = new_team.save(&db, None).await {
-                Ok(new_team)
-            } else {
-                Err(Error::new("Can't create team, bad user"))
+        let mut new_team = Team::new_computer_team(&name, &league_id, &owner, strategy);
+
+        new_team.save(db, None).await?;
+
+        Ok(new_team)
+    }
+
+    /// Has a computer team take its next draft pick
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn computer_draft_pick(&self, ctx: &Context<'_>, team: ID) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        if let Some(mut team) = Team::find_by_id(db, &team).await {
+            if draft_pick(db, &mut team).await?.is_none() {
+                return Err("No affordable free agents left".into())
             }
+
+            Ok(team)
         } else {
-            Err("Can't create team".into())
+            Err("Can't find team by id".into())
         }
     }
 
+    /// Moving a player to the reserve sends them down to the farm, moving a farm player
+    /// anywhere else calls them up
     async fn set_player_position(&self, ctx: &Context<'_>, team: ID, player: ID, position: Position) -> Result<Team> {
         let db: &Database = ctx.data()?;
 
-        if let Some(mut team) = Team::find_by_id(db, &team).await {
-            let player_id = player.to_string();
-
-            match position {
-                Position::Reserve => {},
-                Position::StartingPitcher => {team.roster.starting_pitcher = Some(player_id);},
-                Position::ReliefPitcher => {team.roster.relief_pitchers.push(player_id);},
-                Position::Catcher => {team.roster.catcher = Some(player_id);},
-                Position::ReserveCatcher => {team.roster.catcher_reserves.push(player_id);},
-                Position::FirstBase => {team.roster.first_base = Some(player_id);},
-                Position::SecondBase => {team.roster.second_base = Some(player_id);},
-                Position::ThirdBase => {team.roster.third_base = Some(player_id);},
-                Position::Shortstop => {team.roster.shortstop = Some(player_id);},
-                Position::InfieldReserve => {team.roster.infield_reserves.push(player_id);},
-                Position::LeftField => {team.roster.left_field = Some(player_id);},
-                Position::CenterField => {team.roster.center_field = Some(player_id);},
-                Position::RightField => {team.roster.right_field = Some(player_id);},
-                Position::OutfieldReserve => {team.roster.outfield_reserves.push(player_id);},
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        if position == Position::Reserve {
+            send_down(db, &mut team, &player).await?;
+        } else if team.farm.iter().any(|id| id.as_str() == player.as_str()) {
+            call_up(db, &mut team, &player, Some(position)).await?;
+        } else {
+            let team_id = team.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+
+            // Anyone new to the roster has to have been signed to this team in its own league
+            if !team.all_player_ids().iter().any(|id| id.as_str() == player.as_str()) {
+                let summary = fetch_player(&player).await?;
+
+                if !summary.belongs_to(&team.league, &team_id) {
+                    return Err("That player hasn't signed with this team".into())
+                }
             }
 
-            team.save(db, None).await?;
+            team = Team::set_player_position(db, &team_id, &player, position).await?;
+        }
+
+        Ok(team)
+    }
+
+    async fn send_down_player(&self, ctx: &Context<'_>, team: ID, player: ID) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        send_down(db, &mut team, &player).await?;
+
+        Ok(team)
+    }
+
+    /// Calls a player up from the farm, to the reserve list they fit best when no position is given
+    async fn call_up_player(&self, ctx: &Context<'_>, team: ID, player: ID, position: Option<Position>) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        call_up(db, &mut team, &player, position).await?;
+
+        Ok(team)
+    }
+
+    async fn place_on_injured_list(&self, ctx: &Context<'_>, team: ID, player: ID) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        place_on_injured_list(db, &mut team, &player).await?;
+
+        Ok(team)
+    }
+
+    /// Brings a player back from the injured list, to their old position when no position is given
+    async fn activate_player(&self, ctx: &Context<'_>, team: ID, player: ID, position: Option<Position>) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        activate(db, &mut team, &player, position).await?;
+
+        Ok(team)
+    }
+
+    async fn swap_players(&self, ctx: &Context<'_>, team: ID, first: ID, second: ID) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        find_owned_team(ctx, db, &team).await?;
+
+        Team::swap_players(db, &team, &first, &second).await
+    }
+
+    /// Takes a player off the roster and out of the lineup without releasing them
+    async fn remove_player(&self, ctx: &Context<'_>, team: ID, player: ID) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        find_owned_team(ctx, db, &team).await?;
+
+        Team::remove_player(db, &team, &player).await
+    }
+
+    /// Removes a player from the team, refunds part of what it paid for them as the league's
+    /// rules say and returns them to the free agent pool. Only admins can pick another refund.
+    /// Without free agency the player just comes off the roster and stays with the team
+    async fn release_player(
+        &self, ctx: &Context<'_>,
+        team: ID,
+        player: ID,
+        refund: Option<RefundPolicy>,
+        #[graphql(default = true)] free_agency: bool,
+    ) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let owned = find_owned_team(ctx, db, &team).await?;
+
+        if !free_agency {
+            return Team::remove_player(db, &team, &player).await
+        }
+
+        let refund = match refund {
+            Some(refund) => {
+                require_admin(ctx)?;
+                refund
+            },
+            None => fetch_release_refund(&owned.league).await?,
+        };
+
+        let team = Team::release_player(db, &team, &player, refund).await?;
+
+        release_player(&player).await?;
+
+        Ok(team)
+    }
+
+    /// Opts a team in or out of the auto manager, running it straight away when enabled
+    async fn set_auto_manage(
+        &self, ctx: &Context<'_>,
+        team: ID,
+        enabled: bool,
+    ) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        team.auto_manage = enabled;
+
+        if enabled {
+            let designated_hitter = fetch_designated_hitter(&team.league).await?;
+
+            auto_manage(db, &mut team, designated_hitter).await?;
+        } else {
+            team.save_changes(db).await?;
+        }
+
+        Ok(team)
+    }
+
+    /// Called by the game scheduler before each game
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn prepare_for_game(&self, ctx: &Context<'_>, team: ID) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        if let Some(mut team) = Team::find_by_id(db, &team).await {
+            let designated_hitter = fetch_designated_hitter(&team.league).await?;
+
+            prepare_for_game(db, &mut team, designated_hitter).await?;
+
             Ok(team)
         } else {
             Err("Can't find team by id".into())
         }
     }
 
-    async fn modify_gold(&self, ctx: &Context<'_>, id: ID, cost: i64) -> Result<Team> {
+    async fn select_keepers(&self, ctx: &Context<'_>, team: ID, players: Vec<ID>) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        let players: Vec<String> = players.iter().map(|id| id.to_string()).collect();
+
+        select_keepers(db, &mut team, &players).await?;
+
+        Ok(team)
+    }
+
+    /// Called by the leagues service when a league moves on to its next season
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn rollover_league(
+        &self, ctx: &Context<'_>,
+        league: ID,
+        season: i64,
+        keepers_per_team: i64,
+        cost_inflation: i64,
+        dynasty: bool,
+    ) -> Result<Vec<Team>> {
+        let db: &Database = ctx.data()?;
+
+        let mut teams = Team::find_by_league_id(db, &league).await?;
+
+        for team in teams.iter_mut() {
+            rollover(db, team, season, keepers_per_team, cost_inflation, dynasty).await?;
+        }
+
+        Ok(teams)
+    }
+
+    async fn scout_player(&self, ctx: &Context<'_>, team: ID, player: ID, method: ScoutingMethod) -> Result<Team> {
         let db: &Database = ctx.data()?;
 
-        Team::modify_gold(db, &id, cost).await
+        let mut team = find_owned_team(ctx, db, &team).await?;
+
+        scout(db, &mut team, &player, method).await?;
+
+        Ok(team)
+    }
+
+    /// Offers a contract extension to one of the team's players, or bids on a free agent
+    async fn negotiate_contract(
+        &self, ctx: &Context<'_>,
+        team: ID,
+        player: ID,
+        terms: ContractTermsInput,
+    ) -> Result<NegotiationResult> {
+        let db: &Database = ctx.data()?;
+
+        let team = find_owned_team(ctx, db, &team).await?;
+        let team_id = team.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+
+        // Offers have to be covered for a pay period along with every other contract
+        let committed = contracted_salaries(&fetch_team_players(&team_id).await?, &player);
+
+        if committed + terms.salary > team.gold {
+            return Err(format!(
+                "The team has {} gold, not enough for {} a period on top of the {} it already pays",
+                team.gold, terms.salary, committed,
+            ).into())
+        }
+
+        let terms = json!({
+            "years": terms.years,
+            "salary": terms.salary,
+            "option": terms.option,
+            "noTrade": terms.no_trade,
+        });
+
+        negotiate_contract(&player, &team_id, &team.league, terms).await
+    }
+
+    /// Decides on a team option before the contract runs out at season rollover
+    async fn exercise_option(&self, ctx: &Context<'_>, team: ID, player: ID, exercise: bool) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        let team = find_owned_team(ctx, db, &team).await?;
+        let team_id = team.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+
+        exercise_option(&player, &team_id, exercise).await?;
+
+        Ok(team)
+    }
+
+    /// Called by the leagues service with the free agents who accepted an offer
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn add_signed_players(&self, ctx: &Context<'_>, league: ID, signings: Vec<SigningInput>) -> Result<Vec<Team>> {
+        let db: &Database = ctx.data()?;
+
+        let mut teams = Team::find_by_league_id(db, &league).await?;
+        let designated_hitter = fetch_designated_hitter(&league).await?;
+
+        for team in teams.iter_mut() {
+            let team_id = team.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+            let signed: Vec<&SigningInput> = signings.iter().filter(|signing| signing.team.as_str() == team_id).collect();
+
+            if signed.is_empty() {
+                continue;
+            }
+
+            for signing in signed.iter() {
+                let player = fetch_player(&signing.player).await?;
+
+                if !player.in_league(&league) {
+                    return Err(format!("Player {} isn't in this league", signing.player.as_str()).into())
+                }
+
+                place_new_player(team, &player)?;
+            }
+
+            if team.auto_manage {
+                auto_manage(db, team, designated_hitter).await?;
+            } else {
+                team.save_changes(db).await?;
+            }
+        }
+
+        Ok(teams)
+    }
+
+    /// Expanding charges the team for every seat added
+    async fn set_ballpark(&self, ctx: &Context<'_>, team: ID, name: String, capacity: i64) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        find_owned_team(ctx, db, &team).await?;
+
+        Team::set_ballpark(db, &team, &name, capacity).await
+    }
+
+    /// Called by the leagues service after each simulated game
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn settle_game(
+        &self, ctx: &Context<'_>,
+        league: ID,
+        result: GameResult,
+        rates: EconomyRates,
+    ) -> Result<Vec<GameIncome>> {
+        let db: &Database = ctx.data()?;
+
+        settle_game(db, &league, &result, &rates).await
+    }
+
+    /// Called by the leagues service at season rollover for players who retired or left in free agency
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn drop_players(&self, ctx: &Context<'_>, league: ID, players: Vec<ID>) -> Result<Vec<Team>> {
+        let db: &Database = ctx.data()?;
+
+        let mut teams = Team::find_by_league_id(db, &league).await?;
+
+        for team in teams.iter_mut() {
+            let mut changed = false;
+
+            for player in players.iter() {
+                changed |= team.drop_player(player);
+            }
+
+            if changed {
+                team.save_changes(db).await?;
+            }
+        }
+
+        Ok(teams)
+    }
+
+    /// Called by the leagues service each pay period
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn run_payroll(
+        &self, ctx: &Context<'_>,
+        league: ID,
+        salary_rate: i64,
+        salary_cap: Option<i64>,
+        #[graphql(default)] fine: i64,
+    ) -> Result<Vec<PayrollReport>> {
+        let db: &Database = ctx.data()?;
+
+        let mut teams = Team::find_by_league_id(db, &league).await?;
+        let mut reports = Vec::<PayrollReport>::new();
+
+        for team in teams.iter_mut() {
+            reports.push(run_payroll(db, team, salary_rate, salary_cap, fine).await?);
+        }
+
+        Ok(reports)
+    }
+
+    /// Called by the leagues service when a manager's account is deleted. Handing off keeps
+    /// the team, its roster and record, with the computer running it. Otherwise the players
+    /// go back to the free agent pool and the team is removed
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn manager_left(&self, ctx: &Context<'_>, league: ID, user: ID, hand_off: bool) -> Result<Vec<Team>> {
+        let db: &Database = ctx.data()?;
+
+        let mut teams = Team::find_by_league_and_owner(db, &league, &user).await?;
+
+        for team in teams.iter_mut() {
+            if hand_off {
+                team.computer = Some(ComputerStrategy::Balanced);
+                team.auto_manage = true;
+                team.save_changes(db).await?;
+            } else {
+                for player in team.all_player_ids() {
+                    release_player(&player).await?;
+                }
+
+                team.delete(db).await?;
+            }
+        }
+
+        Ok(teams)
+    }
+
+    /// Called by the leagues service when a league that never started is deleted, returns
+    /// how many teams went with it
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn league_deleted(&self, ctx: &Context<'_>, league: ID) -> Result<u64> {
+        let db: &Database = ctx.data()?;
+
+        Team::delete_by_league_id(db, &league).await
+    }
+
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn modify_gold(
+        &self, ctx: &Context<'_>,
+        id: ID,
+        cost: i64,
+        #[graphql(default_with = "TransactionReason::Adjustment")] reason: TransactionReason,
+        #[graphql(default)] details: TransactionDetails,
+    ) -> Result<Team> {
+        let db: &Database = ctx.data()?;
+
+        Team::modify_gold(db, &id, cost, reason, details).await
     }
 
     /*async fn add_player(&self, ctx: &Context<'_>, id: ID, player_id: ID, cost: i64) -> Result<Team> {
@@ -359,3 +983,19 @@ impl Mutation {
 
 
 }
+
+#[derive(Clone, InputObject)]
+pub struct ContractTermsInput {
+    pub years: i64,
+    /// Gold per pay period
+    pub salary: i64,
+    pub option: Option<ContractOption>,
+    #[graphql(default)]
+    pub no_trade: bool,
+}
+
+#[derive(Clone, InputObject)]
+pub struct SigningInput {
+    pub player: ID,
+    pub team: ID,
+}
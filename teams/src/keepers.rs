@@ -0,0 +1,186 @@
+use async_graphql::Result;
+use wither::mongodb::Database;
+
+use crate::models::{Keeper, Record, Team, Transaction, TransactionDetails, TransactionReason};
+use crate::services::{fetch_keeper_rules, fetch_player, fetch_team_players, release_player};
+
+fn inflate(cost: i64, inflation: i64) -> i64 {
+    cost * (100 + inflation) / 100
+}
+
+/// Replaces the team's keeper selections, players already kept before carry
+/// their years and price forward
+pub async fn select_keepers(db: &Database, team: &mut Team, player_ids: &[String]) -> Result<()> {
+    let (rules, open) = fetch_keeper_rules(&team.league).await?;
+
+    if rules.dynasty {
+        return Err("Dynasty leagues keep every player".into())
+    }
+
+    if !open {
+        return Err("The keeper deadline has passed".into())
+    }
+
+    if player_ids.len() as i64 > rules.keepers_per_team {
+        return Err(format!("Teams can only keep {} players", rules.keepers_per_team).into())
+    }
+
+    let mut keepers = Vec::<Keeper>::new();
+
+    for player_id in player_ids.iter() {
+        if !team.roster.contains(player_id) {
+            return Err(format!("Player {} isn't on the roster", player_id).into())
+        }
+
+        if keepers.iter().any(|keeper| &keeper.player == player_id) {
+            continue;
+        }
+
+        let keeper = match team.keepers.iter().find(|keeper| &keeper.player == player_id) {
+            Some(previous) => previous.clone(),
+            None => {
+                let player = fetch_player(player_id).await?;
+
+                Keeper {
+                    player: player_id.clone(),
+                    years_kept: 0,
+                    cost: inflate(player.cost, rules.cost_inflation),
+                }
+            }
+        };
+
+        keepers.push(keeper);
+    }
+
+    team.keepers = keepers;
+    team.save_changes(db).await?;
+
+    Ok(())
+}
+
+/// Carries the team into `season`: in dynasty leagues the roster stays whole. Otherwise
+/// players still under contract stay, the keepers are paid for and everyone else goes back
+/// to free agency. Contracted players don't take a keeper spot, they're already being paid.
+/// Teams already carried into `season` are left alone, and the season is only marked once
+/// everything else is done so a retry picks up where a failed rollover stopped
+pub async fn rollover(
+    db: &Database,
+    team: &mut Team,
+    season: i64,
+    keepers_per_team: i64,
+    cost_inflation: i64,
+    dynasty: bool,
+) -> Result<()> {
+    if team.rolled_over_season >= season {
+        return Ok(())
+    }
+
+    if !dynasty {
+        carry_over_players(db, team, season, keepers_per_team, cost_inflation).await?;
+    }
+
+    team.record = Record::default();
+    team.call_ups = 0;
+    team.rolled_over_season = season;
+
+    team.save_changes(db).await
+}
+
+async fn carry_over_players(
+    db: &Database,
+    team: &mut Team,
+    season: i64,
+    keepers_per_team: i64,
+    cost_inflation: i64,
+) -> Result<()> {
+    let team_id = match &team.id {
+        Some(id) => id.to_string(),
+        None => return Err("Team hasn't been saved".into()),
+    };
+
+    // Contracts that ran out were already dropped, so anyone with one left has years to go
+    let contracted: Vec<String> = fetch_team_players(&team_id).await?
+        .into_iter()
+        .filter(|player| player.contract.is_some())
+        .map(|player| player.id)
+        .collect();
+
+    let roster = team.roster.clone();
+
+    let mut keepers: Vec<Keeper> = team.keepers.iter()
+        .filter(|keeper| roster.contains(&keeper.player) && !contracted.contains(&keeper.player))
+        .take(keepers_per_team.max(0) as usize)
+        .cloned()
+        .collect();
+
+    let mut charged = Vec::<String>::new();
+
+    for keeper in keepers.iter() {
+        if Transaction::keeper_charged(db, &team_id, &keeper.player, season).await? {
+            charged.push(keeper.player.clone());
+        }
+    }
+
+    // Drop the most recently chosen keepers until the team can pay for the rest, a retry
+    // never drops one it already paid for
+    while keepers.iter()
+        .filter(|keeper| !charged.contains(&keeper.player))
+        .map(|keeper| keeper.cost)
+        .sum::<i64>() > team.gold
+    {
+        match keepers.iter().rposition(|keeper| !charged.contains(&keeper.player)) {
+            Some(index) => { keepers.remove(index); },
+            None => break,
+        }
+    }
+
+    for keeper in keepers.iter() {
+        if charged.contains(&keeper.player) {
+            continue;
+        }
+
+        let charged_team = Team::modify_gold(
+            db, &team_id, -keeper.cost,
+            TransactionReason::Keeper,
+            TransactionDetails::keeper(&keeper.player, season),
+        ).await?;
+
+        team.gold = charged_team.gold;
+    }
+
+    // Farm and injured players go back to free agency too unless they're kept
+    for player_id in team.all_player_ids() {
+        if keepers.iter().any(|keeper| keeper.player == player_id) || contracted.contains(&player_id) {
+            continue;
+        }
+
+        release_player(&player_id).await?;
+
+        team.drop_player(&player_id);
+    }
+
+    for keeper in keepers.iter_mut() {
+        keeper.years_kept += 1;
+        keeper.cost = inflate(keeper.cost, cost_inflation);
+    }
+
+    team.keepers = keepers;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inflation_raises_the_cost_by_a_percent() {
+        assert_eq!(inflate(1000, 10), 1100);
+        assert_eq!(inflate(1000, 0), 1000);
+    }
+
+    #[test]
+    fn inflation_rounds_down() {
+        assert_eq!(inflate(999, 10), 1098);
+    }
+}
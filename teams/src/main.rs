@@ -1,16 +1,26 @@
 #[macro_use]
 extern crate log;
 
+mod auth;
+mod auto_manager;
+mod computer;
 mod config;
+mod economy;
+mod farm;
 mod graphql;
+mod keepers;
 mod models;
+mod payroll;
 mod routes;
+mod scouting;
+mod services;
 
 use crate::config::CONFIG;
 use crate::graphql::{AppSchema, Mutation, Query};
-use crate::models::Team;
+use crate::models::{SettledGame, Team, Transaction};
 use crate::routes::index;
 
+use common::auth::SessionSecret;
 use actix_web::{middleware, web, App, HttpServer};
 use async_graphql::{
     extensions::{apollo_persisted_queries::ApolloPersistedQueries, ApolloTracing, Logger, apollo_persisted_queries::LruCacheStorage},
@@ -32,6 +42,14 @@ async fn init_db() -> Database {
         .await
         .expect("Failed syncing indexes");
 
+    Transaction::sync(&db)
+        .await
+        .expect("Failed syncing indexes");
+
+    SettledGame::sync(&db)
+        .await
+        .expect("Failed syncing indexes");
+
     db
 }
 
@@ -74,6 +92,8 @@ async fn main() -> std::io::Result<()> {
     println!("called main()");
     init_logger();
 
+    common::client::set_service_credentials("teams", &CONFIG.session.secret);
+
     let db = init_db().await;
     let redis = init_redis().await;
     let schema = init_graphql(&db, &redis);
@@ -82,6 +102,8 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(middleware::Logger::default())
             .data(db.clone())
+            .data(redis.clone())
+            .data(SessionSecret(CONFIG.session.secret.clone()))
             .data(schema.clone())
             .service(
                 web::resource("/").route(web::post().to(index))
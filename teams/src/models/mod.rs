@@ -1,3 +1,9 @@
+pub mod settled_game;
 pub mod teams;
+pub mod transaction;
+pub mod validation;
 
-pub use teams::*;
\ No newline at end of file
+pub use settled_game::*;
+pub use teams::*;
+pub use transaction::*;
+pub use validation::*;
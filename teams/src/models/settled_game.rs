@@ -0,0 +1,106 @@
+use async_graphql::*;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use wither::{bson::{self, doc, oid::ObjectId}, mongodb::Database};
+
+/// How long a settlement has before a retry may take it over and finish it
+const LEASE_MINUTES: i64 = 5;
+
+/// A game whose income is being or has been paid out, so it can't be paid out again
+#[derive(Clone, Debug, Model, Serialize, Deserialize)]
+#[model(
+    collection_name = "settled_games",
+    index(keys = r#"doc!{"game": 1}"#, options = r#"doc!{"unique": true}"#)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct SettledGame {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub game: String,
+    pub league: String,
+    /// Steps already done, a retried settlement skips them
+    #[serde(default)]
+    pub steps: Vec<String>,
+    /// Set once every step is done
+    pub settled_at: Option<bson::DateTime>,
+    #[serde(default)]
+    pub lease_until: Option<bson::DateTime>,
+}
+
+impl SettledGame {
+    /// Claims the game before anything is paid, the unique index turns a replay away even
+    /// when two arrive at once. A settlement that failed part way is handed back once its
+    /// lease runs out, so a retry can finish the steps it didn't get to
+    pub async fn claim(db: &Database, league_id: &str, game_id: &str) -> Result<Self> {
+        let lease_until = Utc::now() + Duration::minutes(LEASE_MINUTES);
+
+        let mut settled = SettledGame {
+            id: None,
+            game: game_id.to_string(),
+            league: league_id.to_string(),
+            steps: Vec::new(),
+            settled_at: None,
+            lease_until: Some(bson::DateTime::from(lease_until)),
+        };
+
+        match settled.save(db, None).await {
+            Ok(_) => return Ok(settled),
+            Err(error) if error.to_string().contains("E11000") => {},
+            Err(error) => return Err(error.into()),
+        }
+
+        let query = doc! {
+            "game": game_id,
+            "league": league_id,
+            "settledAt": null,
+            "leaseUntil": { "$lt": Utc::now() },
+        };
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let update = doc! { "$set": { "leaseUntil": lease_until } };
+
+        match SettledGame::find_one_and_update(db, query, update, options).await? {
+            Some(settled) => Ok(settled),
+            None => Err(format!("Game {} has already been settled", game_id).into()),
+        }
+    }
+
+    pub fn is_done(&self, step: &str) -> bool {
+        self.steps.iter().any(|done| done == step)
+    }
+
+    pub async fn step_done(&mut self, db: &Database, step: &str) -> Result<()> {
+        let id = self.id.clone().ok_or("Settlement hasn't been saved")?;
+
+        SettledGame::collection(db)
+            .update_one(doc! { "_id": id }, doc! { "$addToSet": { "steps": step } }, None)
+            .await?;
+
+        self.steps.push(step.to_string());
+
+        Ok(())
+    }
+
+    pub async fn finish(&mut self, db: &Database) -> Result<()> {
+        let id = self.id.clone().ok_or("Settlement hasn't been saved")?;
+        let settled_at = Utc::now();
+
+        SettledGame::collection(db)
+            .update_one(
+                doc! { "_id": id },
+                doc! { "$set": { "settledAt": settled_at, "leaseUntil": null } },
+                None,
+            )
+            .await?;
+
+        self.settled_at = Some(bson::DateTime::from(settled_at));
+        self.lease_until = None;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
 use async_graphql::*;
+use chrono::Utc;
 use futures::stream::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use wither::bson::Document;
 use wither::prelude::*;
-use wither::{bson::{doc, oid::ObjectId}, mongodb::Database};
+use wither::{bson::{self, doc, oid::ObjectId}, mongodb::Database};
+use wither::mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use common::enums::{ComputerStrategy, Position, RefundPolicy};
+
+use crate::config::CONFIG;
+use crate::models::{Transaction, TransactionDetails, TransactionReason};
 
 /// League representation
 #[derive(Clone, Debug, Model, Serialize, Deserialize)]
@@ -25,6 +32,91 @@ pub struct Team {
 
     pub roster: Roster,
     pub lineup: Lineup,
+
+    /// Let the auto manager set the roster and lineup before each game
+    #[serde(default)]
+    pub auto_manage: bool,
+
+    /// Set when the team is run by a computer manager
+    #[serde(default)]
+    pub computer: Option<ComputerStrategy>,
+
+    /// Players chosen to carry over into next season
+    #[serde(default)]
+    pub keepers: Vec<Keeper>,
+
+    #[serde(default)]
+    pub ballpark: Ballpark,
+    #[serde(default)]
+    pub record: Record,
+
+    /// Players developing in the minors, off the active roster
+    #[serde(default)]
+    pub farm: Vec<String>,
+    #[serde(default)]
+    pub injured_list: Vec<InjuredListEntry>,
+    /// Players called up from the farm this season
+    #[serde(default)]
+    pub call_ups: i64,
+    /// The last season the team was carried into, so a retried rollover skips it
+    #[serde(default)]
+    pub rolled_over_season: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Ballpark {
+    pub name: String,
+    /// Most fans that can attend a home game
+    pub capacity: i64,
+}
+
+impl Default for Ballpark {
+    fn default() -> Self {
+        Ballpark {
+            name: String::from("Home Field"),
+            capacity: 10000,
+        }
+    }
+}
+
+/// The team's results this season
+#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    pub wins: i64,
+    pub losses: i64,
+    pub home_wins: i64,
+    pub home_losses: i64,
+    pub playoff_wins: i64,
+    pub playoff_losses: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Keeper {
+    pub player: String,
+    /// Seasons this player has already been kept
+    pub years_kept: i64,
+    /// Gold charged at rollover to keep the player
+    pub cost: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InjuredListEntry {
+    pub player: String,
+    /// Where the player was on the roster before getting hurt
+    pub position: Position,
+    pub placed_at: bson::DateTime,
+    /// When the player can be activated again
+    pub eligible_at: bson::DateTime,
+}
+
+impl InjuredListEntry {
+    pub fn is_eligible(&self) -> bool {
+        self.eligible_at.0 <= Utc::now()
+    }
 }
 
 /*#[derive(Clone, Debug, Default, Serialize, Deserialize, SimpleObject)]
@@ -92,6 +184,224 @@ pub struct Lineup {
     pub ninth: Option<String>,
 }
 
+fn take_if(slot: &mut Option<String>, player_id: &str) -> bool {
+    if slot.as_deref() == Some(player_id) {
+        *slot = None;
+        true
+    } else {
+        false
+    }
+}
+
+fn take_from(list: &mut Vec<String>, player_id: &str) -> bool {
+    let len = list.len();
+    list.retain(|id| id != player_id);
+
+    list.len() != len
+}
+
+impl Roster {
+    /// The reserve slot a player falls back to when bumped from a starting slot
+    pub fn reserve_for(position: Position) -> Position {
+        match position {
+            Position::StartingPitcher | Position::ReliefPitcher => Position::ReliefPitcher,
+            Position::Catcher | Position::ReserveCatcher => Position::ReserveCatcher,
+            Position::FirstBase | Position::SecondBase | Position::ThirdBase
+                | Position::Shortstop | Position::InfieldReserve => Position::InfieldReserve,
+            Position::LeftField | Position::CenterField | Position::RightField
+                | Position::OutfieldReserve => Position::OutfieldReserve,
+            Position::Reserve => Position::Reserve,
+        }
+    }
+
+    pub fn position_of(&self, player_id: &str) -> Option<Position> {
+        let id = Some(player_id);
+        let contains = |list: &Vec<String>| list.iter().any(|p| p == player_id);
+
+        if self.starting_pitcher.as_deref() == id { return Some(Position::StartingPitcher) }
+        if contains(&self.relief_pitchers) { return Some(Position::ReliefPitcher) }
+        if self.catcher.as_deref() == id { return Some(Position::Catcher) }
+        if contains(&self.catcher_reserves) { return Some(Position::ReserveCatcher) }
+        if self.first_base.as_deref() == id { return Some(Position::FirstBase) }
+        if self.second_base.as_deref() == id { return Some(Position::SecondBase) }
+        if self.third_base.as_deref() == id { return Some(Position::ThirdBase) }
+        if self.shortstop.as_deref() == id { return Some(Position::Shortstop) }
+        if contains(&self.infield_reserves) { return Some(Position::InfieldReserve) }
+        if self.left_field.as_deref() == id { return Some(Position::LeftField) }
+        if self.center_field.as_deref() == id { return Some(Position::CenterField) }
+        if self.right_field.as_deref() == id { return Some(Position::RightField) }
+        if contains(&self.outfield_reserves) { return Some(Position::OutfieldReserve) }
+
+        None
+    }
+
+    /// The player in a single starting slot
+    pub fn player_in(&self, position: Position) -> Option<&String> {
+        match position {
+            Position::StartingPitcher => self.starting_pitcher.as_ref(),
+            Position::Catcher => self.catcher.as_ref(),
+            Position::FirstBase => self.first_base.as_ref(),
+            Position::SecondBase => self.second_base.as_ref(),
+            Position::ThirdBase => self.third_base.as_ref(),
+            Position::Shortstop => self.shortstop.as_ref(),
+            Position::LeftField => self.left_field.as_ref(),
+            Position::CenterField => self.center_field.as_ref(),
+            Position::RightField => self.right_field.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, player_id: &str) -> bool {
+        self.position_of(player_id).is_some()
+    }
+
+    /// Every player ID on the roster, starters first
+    pub fn player_ids(&self) -> Vec<String> {
+        let mut ids = Vec::<String>::new();
+
+        let slots = [
+            &self.starting_pitcher, &self.catcher, &self.first_base, &self.second_base,
+            &self.third_base, &self.shortstop, &self.left_field, &self.center_field, &self.right_field,
+        ];
+
+        for id in slots.iter().copied().flatten() {
+            ids.push(id.clone());
+        }
+
+        ids.extend(self.relief_pitchers.iter().cloned());
+        ids.extend(self.catcher_reserves.iter().cloned());
+        ids.extend(self.infield_reserves.iter().cloned());
+        ids.extend(self.outfield_reserves.iter().cloned());
+
+        ids
+    }
+
+    /// Removes the player from whichever slot they occupy, returning that slot
+    pub fn remove_player(&mut self, player_id: &str) -> Option<Position> {
+        let position = self.position_of(player_id)?;
+
+        take_if(&mut self.starting_pitcher, player_id);
+        take_from(&mut self.relief_pitchers, player_id);
+        take_if(&mut self.catcher, player_id);
+        take_from(&mut self.catcher_reserves, player_id);
+        take_if(&mut self.first_base, player_id);
+        take_if(&mut self.second_base, player_id);
+        take_if(&mut self.third_base, player_id);
+        take_if(&mut self.shortstop, player_id);
+        take_from(&mut self.infield_reserves, player_id);
+        take_if(&mut self.left_field, player_id);
+        take_if(&mut self.center_field, player_id);
+        take_if(&mut self.right_field, player_id);
+        take_from(&mut self.outfield_reserves, player_id);
+
+        Some(position)
+    }
+
+    fn slot(&mut self, position: Position) -> Option<&mut Option<String>> {
+        match position {
+            Position::StartingPitcher => Some(&mut self.starting_pitcher),
+            Position::Catcher => Some(&mut self.catcher),
+            Position::FirstBase => Some(&mut self.first_base),
+            Position::SecondBase => Some(&mut self.second_base),
+            Position::ThirdBase => Some(&mut self.third_base),
+            Position::Shortstop => Some(&mut self.shortstop),
+            Position::LeftField => Some(&mut self.left_field),
+            Position::CenterField => Some(&mut self.center_field),
+            Position::RightField => Some(&mut self.right_field),
+            _ => None,
+        }
+    }
+
+    fn list(&mut self, position: Position) -> Option<&mut Vec<String>> {
+        match position {
+            Position::ReliefPitcher => Some(&mut self.relief_pitchers),
+            Position::ReserveCatcher => Some(&mut self.catcher_reserves),
+            Position::InfieldReserve => Some(&mut self.infield_reserves),
+            Position::OutfieldReserve => Some(&mut self.outfield_reserves),
+            _ => None,
+        }
+    }
+
+    /// Puts the player into the given slot, taking them out of any slot they
+    /// held before. A starter bumped out of a single slot drops to the
+    /// matching reserve list so nobody falls off the roster.
+    pub fn set_player(&mut self, player_id: &str, position: Position) -> Result<()> {
+        if position == Position::Reserve {
+            return Err("Reserve players belong in the farm, send them down instead".into());
+        }
+
+        self.remove_player(player_id);
+
+        if let Some(list) = self.list(position) {
+            list.push(player_id.to_string());
+        } else if let Some(slot) = self.slot(position) {
+            let bumped = slot.replace(player_id.to_string());
+
+            if let Some(bumped) = bumped {
+                if let Some(list) = self.list(Roster::reserve_for(position)) {
+                    list.push(bumped);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Exchanges the slots of two players already on the roster
+    pub fn swap_players(&mut self, first_id: &str, second_id: &str) -> Result<()> {
+        let first_position = self.position_of(first_id)
+            .ok_or_else(|| Error::new(format!("Player {} is not on the roster", first_id)))?;
+        let second_position = self.position_of(second_id)
+            .ok_or_else(|| Error::new(format!("Player {} is not on the roster", second_id)))?;
+
+        if first_position == second_position {
+            return Ok(())
+        }
+
+        self.remove_player(first_id);
+        self.remove_player(second_id);
+
+        self.place(first_id, second_position);
+        self.place(second_id, first_position);
+
+        Ok(())
+    }
+
+    fn place(&mut self, player_id: &str, position: Position) {
+        if let Some(list) = self.list(position) {
+            list.push(player_id.to_string());
+        } else if let Some(slot) = self.slot(position) {
+            *slot = Some(player_id.to_string());
+        }
+    }
+}
+
+impl Lineup {
+    pub fn remove_player(&mut self, player_id: &str) -> bool {
+        let mut removed = false;
+
+        for slot in self.slots_mut().iter_mut() {
+            removed |= take_if(slot, player_id);
+        }
+
+        removed
+    }
+
+    pub fn slots(&self) -> [&Option<String>; 9] {
+        [
+            &self.first, &self.second, &self.third, &self.fourth, &self.fifth,
+            &self.sixth, &self.seventh, &self.eighth, &self.ninth,
+        ]
+    }
+
+    pub fn slots_mut(&mut self) -> [&mut Option<String>; 9] {
+        [
+            &mut self.first, &mut self.second, &mut self.third, &mut self.fourth, &mut self.fifth,
+            &mut self.sixth, &mut self.seventh, &mut self.eighth, &mut self.ninth,
+        ]
+    }
+}
+
 
 impl Team {
     pub fn new_team(name: &str, league_id: &str, owner_id: &str
@@ -104,11 +414,31 @@ impl Team {
             gold: 500000,
             roster: Roster::default(),
             lineup: Lineup::default(),
+            auto_manage: false,
+            computer: None,
+            keepers: Vec::new(),
+            ballpark: Ballpark {
+                name: format!("{} Park", name),
+                ..Ballpark::default()
+            },
+            record: Record::default(),
+            farm: Vec::new(),
+            injured_list: Vec::new(),
+            call_ups: 0,
+            rolled_over_season: 0,
         }
     }
 
+    pub fn new_computer_team(name: &str, league_id: &str, owner_id: &str, strategy: ComputerStrategy) -> Self {
+        let mut team = Team::new_team(name, league_id, owner_id);
+        team.auto_manage = true;
+        team.computer = Some(strategy);
+
+        team
+    }
+
     pub async fn find_all(db: &Database, filter: Option<Document>) -> Result<Vec::<Self>> {
-        let cursor = Team::find(&db, filter, None).await?;
+        let cursor = Team::find(db, filter, None).await?;
         let teams: Vec<Team> = cursor.try_collect().await?;
 
         Ok(teams)
@@ -116,7 +446,7 @@ impl Team {
 
     pub async fn find_by_id(db: &Database, id: &str) -> Option<Self> {
         let id = ObjectId::with_string(id).expect("Can't get id from String");
-        Team::find_one(&db, doc! { "_id": id }, None).await.unwrap()
+        Team::find_one(db, doc! { "_id": id }, None).await.unwrap()
     }
 
     /*pub async fn find_by_name(db: &Database, name: &str) -> Option<Self> {
@@ -126,8 +456,8 @@ impl Team {
     }*/
 
     pub async fn find_by_owner_id(db: &Database, owner_id: &str) -> Result<Vec::<Self>> {
-        let owner_id = ObjectId::with_string(&owner_id).expect("Can't get id from String");
-        let cursor = Team::find(&db, doc! {"owner": owner_id }, None).await?;
+        let owner_id = ObjectId::with_string(owner_id).expect("Can't get id from String");
+        let cursor = Team::find(db, doc! {"owner": owner_id }, None).await?;
 
         let teams: Vec<Team> = cursor.try_collect().await?;
 
@@ -136,13 +466,28 @@ impl Team {
 
     pub async fn find_by_league_id(db: &Database, league_id: &str) -> Result<Vec::<Self>> {
         //let league_id = ObjectId::with_string(&league_id).expect("Can't get id from String");
-        let cursor = Team::find(&db, doc! {"league": league_id }, None).await?;
+        let cursor = Team::find(db, doc! {"league": league_id }, None).await?;
+
+        let teams: Vec<Team> = cursor.try_collect().await?;
+
+        Ok(teams)
+    }
+
+    pub async fn find_by_league_and_owner(db: &Database, league_id: &str, owner_id: &str) -> Result<Vec::<Self>> {
+        let cursor = Team::find(db, doc! { "league": league_id, "owner": owner_id }, None).await?;
 
         let teams: Vec<Team> = cursor.try_collect().await?;
 
         Ok(teams)
     }
 
+    /// Returns how many teams were removed
+    pub async fn delete_by_league_id(db: &Database, league_id: &str) -> Result<u64> {
+        let result = Team::delete_many(db, doc! { "league": league_id }, None).await?;
+
+        Ok(result.deleted_count as u64)
+    }
+
     /*pub async fn find_user_team_for_league(
         db: &Database,
         owner_id: &str,
@@ -162,15 +507,67 @@ impl Team {
         }
     }*/
 
-    pub async fn modify_gold(db: &Database, id: &str, gold: i64) -> Result<Self> {
-        let query = doc! {
-            "_id": ObjectId::with_string(id)?
-        };
+    /// Takes a player off the roster, out of the lineup, the farm, the injured list
+    /// and the keeper list
+    pub fn drop_player(&mut self, player_id: &str) -> bool {
+        let on_roster = self.roster.remove_player(player_id).is_some();
+        let in_lineup = self.lineup.remove_player(player_id);
+        let in_farm = self.farm.contains(&player_id.to_string());
+        let injured = self.injured_list.iter().any(|entry| entry.player == player_id);
+        let keepers = self.keepers.len();
 
-        if let Some(mut team) = Team::find_one(db, Some(query), None).await? {
-            team.gold = team.gold + gold;
+        self.farm.retain(|id| id != player_id);
+        self.injured_list.retain(|entry| entry.player != player_id);
+        self.keepers.retain(|keeper| keeper.player != player_id);
 
-            team.save(db, None).await?;
+        on_roster || in_lineup || in_farm || injured || keepers != self.keepers.len()
+    }
+
+    /// Players on the roster, in the farm or on the injured list
+    pub fn all_player_ids(&self) -> Vec<String> {
+        let mut ids = self.roster.player_ids();
+        ids.extend(self.farm.iter().cloned());
+        ids.extend(self.injured_list.iter().map(|entry| entry.player.clone()));
+
+        ids
+    }
+
+    /// Whether the player is available to the active roster, neither in the farm nor injured
+    pub fn is_active(&self, player_id: &str) -> bool {
+        !self.farm.iter().any(|id| id == player_id)
+            && !self.injured_list.iter().any(|entry| entry.player == player_id)
+    }
+
+    pub fn active_roster_full(&self) -> bool {
+        self.roster.player_ids().len() >= CONFIG.farm.active_size
+    }
+
+    /// Renaming is free, every seat added is paid for up front
+    pub async fn set_ballpark(db: &Database, id: &str, name: &str, capacity: i64) -> Result<Self> {
+        if !(1000..=50000).contains(&capacity) {
+            return Err("Ballpark capacity must be between 1000 and 50000".into())
+        }
+
+        if let Some(mut team) = Team::find_by_id(db, id).await {
+            let added = capacity - team.ballpark.capacity;
+
+            if added > 0 {
+                team = Team::modify_gold(
+                    db, id, -added * CONFIG.ballpark.seat_cost,
+                    TransactionReason::Ballpark,
+                    TransactionDetails {
+                        note: Some(format!("{} seats added", added)),
+                        ..TransactionDetails::default()
+                    },
+                ).await?;
+            }
+
+            team.ballpark = Ballpark {
+                name: name.to_string(),
+                capacity,
+            };
+
+            team.save_changes(db).await?;
 
             Ok(team)
         } else {
@@ -178,6 +575,126 @@ impl Team {
         }
     }
 
+    /// Writes every field but gold, which only ever moves through `modify_gold`
+    /// so a roster change can't overwrite a concurrent payment
+    pub async fn save_changes(&self, db: &Database) -> Result<()> {
+        let id = self.id.clone().ok_or("Team hasn't been saved")?;
+
+        let mut document = self.document_from_instance()?;
+        document.remove("_id");
+        document.remove("gold");
+
+        Team::collection(db)
+            .update_one(doc! { "_id": id }, doc! { "$set": document }, None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Atomically moves gold in or out of the team and records it in the ledger,
+    /// refusing anything that would leave the team below zero
+    pub async fn modify_gold(
+        db: &Database,
+        id: &str,
+        amount: i64,
+        reason: TransactionReason,
+        details: TransactionDetails,
+    ) -> Result<Self> {
+        let oid = ObjectId::with_string(id)?;
+
+        let mut query = doc! { "_id": oid.clone() };
+
+        if amount < 0 {
+            query.insert("gold", doc! { "$gte": -amount });
+        }
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let updated = Team::find_one_and_update(db, query, doc! { "$inc": { "gold": amount } }, options).await?;
+
+        if let Some(team) = updated {
+            let mut transaction = Transaction::new_transaction(id, amount, team.gold, reason, details);
+            transaction.save(db, None).await?;
+
+            Ok(team)
+        } else if Team::find_one(db, doc! { "_id": oid }, None).await?.is_some() {
+            Err(format!("team with id: {:?} doesn't have {} gold", &id, -amount).into())
+        } else {
+            Err(format!("team with id: {:?} not found", &id).into())
+        }
+    }
+
+    pub async fn set_player_position(db: &Database, id: &str, player_id: &str, position: Position) -> Result<Self> {
+        if let Some(mut team) = Team::find_by_id(db, id).await {
+            if !team.is_active(player_id) {
+                return Err("Call the player up or activate them before setting their position".into())
+            }
+
+            if !team.roster.contains(player_id) && team.active_roster_full() {
+                return Err(format!("The active roster is full at {} players", CONFIG.farm.active_size).into())
+            }
+
+            team.roster.set_player(player_id, position)?;
+
+            team.save_changes(db).await?;
+
+            Ok(team)
+        } else {
+            Err(format!("team with id: {:?} not found", &id).into())
+        }
+    }
+
+    pub async fn swap_players(db: &Database, id: &str, first_id: &str, second_id: &str) -> Result<Self> {
+        if let Some(mut team) = Team::find_by_id(db, id).await {
+            team.roster.swap_players(first_id, second_id)?;
+
+            team.save_changes(db).await?;
+
+            Ok(team)
+        } else {
+            Err(format!("team with id: {:?} not found", &id).into())
+        }
+    }
+
+    pub async fn remove_player(db: &Database, id: &str, player_id: &str) -> Result<Self> {
+        if let Some(mut team) = Team::find_by_id(db, id).await {
+            if !team.drop_player(player_id) {
+                return Err(format!("player with id: {:?} is not on the team", &player_id).into())
+            }
+
+            team.save_changes(db).await?;
+
+            Ok(team)
+        } else {
+            Err(format!("team with id: {:?} not found", &id).into())
+        }
+    }
+
+    /// Refunds part of what the team paid for the player, as recorded in its ledger
+    pub async fn release_player(
+        db: &Database,
+        id: &str,
+        player_id: &str,
+        refund: RefundPolicy,
+    ) -> Result<Self> {
+        let cost = Transaction::signing_cost(db, id, player_id).await?;
+        let mut team = Team::remove_player(db, id, player_id).await?;
+
+        let gold = refund.refund(cost);
+
+        if gold != 0 {
+            team = Team::modify_gold(
+                db, id, gold,
+                TransactionReason::Release,
+                TransactionDetails::player(player_id),
+            ).await?;
+        }
+
+        Ok(team)
+    }
+
     /*pub async fn add_player(db: &Database, id: &str, player_id: &str, cost: i64) -> Result<Self> {
         let query = doc! {
             "_id": ObjectId::with_string(id)?
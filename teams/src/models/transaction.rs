@@ -0,0 +1,165 @@
+use async_graphql::*;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::{bson::{self, doc, oid::ObjectId}, mongodb::{Database, options::{FindOneOptions, FindOptions}}};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransactionReason {
+    Draft,
+    Release,
+    Keeper,
+    Trade,
+    Game,
+    GateReceipts,
+    PlayoffBonus,
+    Merchandise,
+    Salary,
+    Fine,
+    Scouting,
+    Adjustment,
+    CallUp,
+    SendDown,
+    InjuredList,
+    Activation,
+    Ballpark,
+}
+
+/// What a gold movement relates to, every field is optional
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct TransactionDetails {
+    /// The team on the other side, if any
+    pub counterparty: Option<ID>,
+    pub player: Option<ID>,
+    pub trade: Option<ID>,
+    pub game: Option<ID>,
+    pub note: Option<String>,
+}
+
+impl TransactionDetails {
+    pub fn player(player_id: &str) -> Self {
+        TransactionDetails {
+            player: Some(ID::from(player_id)),
+            ..TransactionDetails::default()
+        }
+    }
+
+    /// Marks a keeper charge with the season it pays for
+    pub fn keeper(player_id: &str, season: i64) -> Self {
+        TransactionDetails {
+            note: Some(keeper_note(season)),
+            ..TransactionDetails::player(player_id)
+        }
+    }
+}
+
+fn keeper_note(season: i64) -> String {
+    format!("Kept for season {}", season)
+}
+
+/// Append-only record of a change to a team's gold. Roster moves between the farm, the
+/// injured list and the active roster are recorded too, with no gold changing hands
+#[derive(Clone, Debug, Model, Serialize, Deserialize)]
+#[model(
+    collection_name = "transactions",
+    index(keys = r#"doc!{"team": 1, "createdAt": -1}"#)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub team: String,
+
+    pub amount: i64,
+    /// The team's gold right after this transaction
+    pub balance: i64,
+    pub reason: TransactionReason,
+
+    pub counterparty: Option<String>,
+    pub player: Option<String>,
+    pub trade: Option<String>,
+    pub game: Option<String>,
+    pub note: Option<String>,
+
+    pub created_at: bson::DateTime,
+}
+
+impl Transaction {
+    pub fn new_transaction(
+        team_id: &str,
+        amount: i64,
+        balance: i64,
+        reason: TransactionReason,
+        details: TransactionDetails,
+    ) -> Self {
+        Transaction {
+            id: None,
+            team: team_id.to_string(),
+            amount,
+            balance,
+            reason,
+            counterparty: details.counterparty.map(|id| id.to_string()),
+            player: details.player.map(|id| id.to_string()),
+            trade: details.trade.map(|id| id.to_string()),
+            game: details.game.map(|id| id.to_string()),
+            note: details.note,
+            created_at: bson::DateTime::from(Utc::now()),
+        }
+    }
+
+    /// Newest first
+    pub async fn find_by_team(db: &Database, team_id: &str, offset: i64, limit: i64) -> Result<Vec<Self>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "createdAt": -1 })
+            .skip(offset.max(0))
+            .limit(limit.max(0))
+            .build();
+
+        let cursor = Transaction::find(db, doc! { "team": team_id }, options).await?;
+        let transactions: Vec<Transaction> = cursor.try_collect().await?;
+
+        Ok(transactions)
+    }
+
+    pub async fn count_by_team(db: &Database, team_id: &str) -> Result<i64> {
+        let count = Transaction::collection(db)
+            .count_documents(doc! { "team": team_id }, None)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Whether the team already paid to keep the player for the season
+    pub async fn keeper_charged(db: &Database, team_id: &str, player_id: &str, season: i64) -> Result<bool> {
+        let filter = doc! {
+            "team": team_id,
+            "player": player_id,
+            "reason": "KEEPER",
+            "note": keeper_note(season),
+        };
+
+        let count = Transaction::collection(db).count_documents(filter, None).await?;
+
+        Ok(count > 0)
+    }
+
+    /// What the team paid to draft the player, nothing when it got them some other way
+    pub async fn signing_cost(db: &Database, team_id: &str, player_id: &str) -> Result<i64> {
+        let filter = doc! {
+            "team": team_id,
+            "player": player_id,
+            "reason": "DRAFT",
+            "amount": { "$lt": 0 },
+        };
+
+        let options = FindOneOptions::builder()
+            .sort(doc! { "createdAt": -1 })
+            .build();
+
+        let signing = Transaction::find_one(db, filter, options).await?;
+
+        Ok(signing.map(|signing| -signing.amount).unwrap_or(0))
+    }
+}
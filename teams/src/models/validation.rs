@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use async_graphql::*;
+
+use common::enums::Position;
+
+use crate::config::CONFIG;
+use crate::models::Team;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum)]
+pub enum RosterIssueKind {
+    /// A starting position has nobody in it
+    EmptyPosition,
+    /// A batting order slot has nobody in it
+    EmptyLineupSlot,
+    /// The same player fills more than one roster slot
+    DuplicateRosterPlayer,
+    /// The same player bats more than once
+    DuplicateLineupPlayer,
+    /// The lineup references a player who isn't on the roster
+    LineupPlayerNotOnRoster,
+    /// A pitcher is batting while the league uses a designated hitter
+    PitcherBatting,
+    /// More players are on the active roster than the limit allows
+    TooManyActivePlayers,
+}
+
+#[derive(Clone, SimpleObject)]
+pub struct RosterIssue {
+    pub kind: RosterIssueKind,
+    pub position: Option<Position>,
+    /// Batting order slot, 1 through 9
+    pub batting_order: Option<i64>,
+    pub player: Option<ID>,
+    pub message: String,
+}
+
+/// A team's issues as reported to the leagues service before the season starts
+#[derive(Clone, SimpleObject)]
+pub struct TeamRosterIssues {
+    pub team: ID,
+    pub name: String,
+    pub issues: Vec<RosterIssue>,
+}
+
+impl RosterIssue {
+    fn new(kind: RosterIssueKind, message: String) -> Self {
+        RosterIssue {
+            kind,
+            position: None,
+            batting_order: None,
+            player: None,
+            message,
+        }
+    }
+}
+
+impl Team {
+    /// Every reason this team couldn't take the field as it stands
+    pub fn validate_roster(&self, designated_hitter: bool) -> Vec<RosterIssue> {
+        let mut issues = Vec::<RosterIssue>::new();
+        let roster = &self.roster;
+
+        let starters = [
+            (Position::StartingPitcher, &roster.starting_pitcher),
+            (Position::Catcher, &roster.catcher),
+            (Position::FirstBase, &roster.first_base),
+            (Position::SecondBase, &roster.second_base),
+            (Position::ThirdBase, &roster.third_base),
+            (Position::Shortstop, &roster.shortstop),
+            (Position::LeftField, &roster.left_field),
+            (Position::CenterField, &roster.center_field),
+            (Position::RightField, &roster.right_field),
+        ];
+
+        for (position, slot) in starters.iter() {
+            if slot.is_none() {
+                let mut issue = RosterIssue::new(
+                    RosterIssueKind::EmptyPosition,
+                    format!("{} is empty", position),
+                );
+                issue.position = Some(*position);
+                issues.push(issue);
+            }
+        }
+
+        let active = roster.player_ids().len();
+
+        if active > CONFIG.farm.active_size {
+            issues.push(RosterIssue::new(
+                RosterIssueKind::TooManyActivePlayers,
+                format!("{} players are active, the limit is {}", active, CONFIG.farm.active_size),
+            ));
+        }
+
+        let mut roster_counts = HashMap::<String, usize>::new();
+
+        for id in roster.player_ids() {
+            *roster_counts.entry(id).or_insert(0) += 1;
+        }
+
+        for (id, count) in roster_counts.iter() {
+            if *count > 1 {
+                let mut issue = RosterIssue::new(
+                    RosterIssueKind::DuplicateRosterPlayer,
+                    format!("Player {} fills {} roster slots", id, count),
+                );
+                issue.player = Some(ID::from(id));
+                issues.push(issue);
+            }
+        }
+
+        let mut batters = Vec::<String>::new();
+
+        for (index, slot) in self.lineup.slots().iter().enumerate() {
+            let batting_order = index as i64 + 1;
+
+            let id = match slot {
+                Some(id) => id,
+                None => {
+                    let mut issue = RosterIssue::new(
+                        RosterIssueKind::EmptyLineupSlot,
+                        format!("Batting order slot {} is empty", batting_order),
+                    );
+                    issue.batting_order = Some(batting_order);
+                    issues.push(issue);
+                    continue;
+                }
+            };
+
+            if batters.contains(id) {
+                let mut issue = RosterIssue::new(
+                    RosterIssueKind::DuplicateLineupPlayer,
+                    format!("Player {} bats more than once", id),
+                );
+                issue.batting_order = Some(batting_order);
+                issue.player = Some(ID::from(id));
+                issues.push(issue);
+            }
+
+            batters.push(id.clone());
+
+            match roster.position_of(id) {
+                None => {
+                    let mut issue = RosterIssue::new(
+                        RosterIssueKind::LineupPlayerNotOnRoster,
+                        format!("Player {} is batting but isn't on the roster", id),
+                    );
+                    issue.batting_order = Some(batting_order);
+                    issue.player = Some(ID::from(id));
+                    issues.push(issue);
+                },
+                Some(position) if designated_hitter && is_pitcher(position) => {
+                    let mut issue = RosterIssue::new(
+                        RosterIssueKind::PitcherBatting,
+                        format!("Player {} is a pitcher and can't bat with a designated hitter", id),
+                    );
+                    issue.position = Some(position);
+                    issue.batting_order = Some(batting_order);
+                    issue.player = Some(ID::from(id));
+                    issues.push(issue);
+                },
+                Some(_) => {},
+            }
+        }
+
+        issues
+    }
+
+    /// Errors with every issue found if the team isn't fit to play
+    pub fn ensure_legal(&self, designated_hitter: bool) -> Result<()> {
+        let issues = self.validate_roster(designated_hitter);
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            let messages: Vec<String> = issues.into_iter().map(|issue| issue.message).collect();
+
+            Err(format!("{} has an illegal roster: {}", self.name, messages.join(", ")).into())
+        }
+    }
+}
+
+fn is_pitcher(position: Position) -> bool {
+    position == Position::StartingPitcher || position == Position::ReliefPitcher
+}
@@ -0,0 +1,211 @@
+use async_graphql::{Result, SimpleObject, ID};
+use wither::mongodb::Database;
+
+use crate::computer::player_value;
+use crate::models::{Team, TransactionDetails, TransactionReason};
+use crate::services::{fetch_team_players, release_player, PlayerSummary};
+
+/// What one pay period cost a team
+#[derive(Clone, SimpleObject)]
+pub struct PayrollReport {
+    pub team: ID,
+    /// Salaries paid to the players still on the team
+    pub payroll: i64,
+    /// Charged for going over the salary cap
+    pub luxury_tax: i64,
+    /// Charged for missing payroll
+    pub fined: i64,
+    /// Players let go because the team couldn't pay them
+    pub released: Vec<ID>,
+}
+
+/// A player's pay for one period: what their contract says, or else a share of
+/// their cost raised or lowered by up to a quarter depending on how they rate
+/// against their teammates
+pub fn salary(player: &PlayerSummary, salary_rate: i64, average_value: i64) -> i64 {
+    if let Some(salary) = player.contract.as_ref().and_then(|contract| contract.salary) {
+        return salary;
+    }
+
+    let base = player.cost * salary_rate / 100;
+
+    if average_value <= 0 {
+        return base;
+    }
+
+    let performance = ((player_value(player) - average_value) * 50 / average_value).clamp(-25, 25);
+
+    base * (100 + performance) / 100
+}
+
+fn salaries(players: &[PlayerSummary], salary_rate: i64) -> Vec<(String, i64)> {
+    let average_value = if players.is_empty() {
+        0
+    } else {
+        players.iter().map(player_value).sum::<i64>() / players.len() as i64
+    };
+
+    players.iter()
+        .map(|player| (player.id.clone(), salary(player, salary_rate, average_value)))
+        .collect()
+}
+
+/// Gold a period the team has agreed to pay in contracts, leaving out `except`, whose
+/// contract is being renegotiated
+pub fn contracted_salaries(players: &[PlayerSummary], except: &str) -> i64 {
+    players.iter()
+        .filter(|player| player.id != except)
+        .filter_map(|player| player.contract.as_ref().and_then(|contract| contract.salary))
+        .sum()
+}
+
+fn amount_due(salaries: &[(String, i64)], salary_cap: Option<i64>) -> (i64, i64) {
+    let payroll: i64 = salaries.iter().map(|(_, salary)| salary).sum();
+    let luxury_tax = salary_cap.map(|cap| (payroll - cap).max(0)).unwrap_or(0);
+
+    (payroll, luxury_tax)
+}
+
+/// Pays the team's salaries for one period. A team that can't cover them
+/// releases its highest paid players until it can and is fined on top
+pub async fn run_payroll(
+    db: &Database,
+    team: &mut Team,
+    salary_rate: i64,
+    salary_cap: Option<i64>,
+    fine: i64,
+) -> Result<PayrollReport> {
+    let team_id = match &team.id {
+        Some(id) => id.to_string(),
+        None => return Err("Team hasn't been saved".into()),
+    };
+
+    let players = fetch_team_players(&team_id).await?;
+
+    let mut salaries = salaries(&players, salary_rate);
+    salaries.sort_by_key(|(_, salary)| *salary);
+
+    let (mut payroll, mut luxury_tax) = amount_due(&salaries, salary_cap);
+    let mut released = Vec::<ID>::new();
+
+    while payroll + luxury_tax > team.gold {
+        let (player_id, _) = match salaries.pop() {
+            Some(highest) => highest,
+            None => break,
+        };
+
+        release_player(&player_id).await?;
+
+        team.drop_player(&player_id);
+
+        released.push(ID::from(player_id));
+
+        let due = amount_due(&salaries, salary_cap);
+        payroll = due.0;
+        luxury_tax = due.1;
+    }
+
+    if !released.is_empty() {
+        team.save_changes(db).await?;
+    }
+
+    if payroll > 0 {
+        *team = Team::modify_gold(
+            db, &team_id, -payroll,
+            TransactionReason::Salary,
+            TransactionDetails::default(),
+        ).await?;
+    }
+
+    if luxury_tax > 0 {
+        *team = Team::modify_gold(
+            db, &team_id, -luxury_tax,
+            TransactionReason::Fine,
+            TransactionDetails {
+                note: Some("Over the salary cap".to_string()),
+                ..TransactionDetails::default()
+            },
+        ).await?;
+    }
+
+    // The fine can't push a team into debt, it takes whatever is left
+    let fined = if released.is_empty() { 0 } else { fine.max(0).min(team.gold) };
+
+    if fined > 0 {
+        *team = Team::modify_gold(
+            db, &team_id, -fined,
+            TransactionReason::Fine,
+            TransactionDetails {
+                note: Some("Missed payroll".to_string()),
+                ..TransactionDetails::default()
+            },
+        ).await?;
+    }
+
+    Ok(PayrollReport {
+        team: ID::from(team_id),
+        payroll,
+        luxury_tax,
+        fined,
+        released,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::enums::{Class, Handedness};
+    use crate::services::ContractSummary;
+
+    fn player(id: &str, cost: i64, salary: Option<i64>) -> PlayerSummary {
+        PlayerSummary {
+            id: id.to_string(),
+            cost,
+            class: Class::Fighter,
+            handedness: Handedness::Right,
+            health: 10,
+            max_health: 10,
+            strength: 10,
+            dexterity: 10,
+            constitution: 10,
+            intelligence: 10,
+            wisdom: 10,
+            charisma: 10,
+            traits: Vec::new(),
+            contract: salary.map(|salary| ContractSummary { salary: Some(salary) }),
+            league: None,
+            team: None,
+        }
+    }
+
+    #[test]
+    fn contracts_set_the_salary() {
+        assert_eq!(salary(&player("a", 1000, Some(75)), 10, 0), 75);
+    }
+
+    #[test]
+    fn uncontracted_players_are_paid_at_the_league_rate() {
+        assert_eq!(salary(&player("a", 1000, None), 10, 0), 100);
+    }
+
+    #[test]
+    fn only_the_payroll_above_the_cap_is_taxed() {
+        let salaries = vec![(String::from("a"), 300), (String::from("b"), 200)];
+
+        assert_eq!(amount_due(&salaries, None), (500, 0));
+        assert_eq!(amount_due(&salaries, Some(400)), (500, 100));
+        assert_eq!(amount_due(&salaries, Some(600)), (500, 0));
+    }
+
+    #[test]
+    fn contracted_salaries_leave_out_the_renegotiated_player() {
+        let players = vec![
+            player("a", 1000, Some(300)),
+            player("b", 1000, Some(200)),
+            player("c", 1000, None),
+        ];
+
+        assert_eq!(contracted_salaries(&players, "none"), 500);
+        assert_eq!(contracted_salaries(&players, "a"), 200);
+    }
+}
@@ -0,0 +1,50 @@
+use async_graphql::{Enum, Result};
+use wither::mongodb::Database;
+
+use crate::config::CONFIG;
+use crate::models::{Team, TransactionDetails, TransactionReason};
+use crate::services::scout_player;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Enum)]
+pub enum ScoutingMethod {
+    /// Pay to have the report straight away
+    Gold,
+    /// Wait for the scouts to come back, free of charge
+    Time,
+}
+
+/// Sends the team's scouts after one of a player's hidden traits
+pub async fn scout(db: &Database, team: &mut Team, player_id: &str, method: ScoutingMethod) -> Result<()> {
+    let team_id = match &team.id {
+        Some(id) => id.to_string(),
+        None => return Err("Team hasn't been saved".into()),
+    };
+
+    if method == ScoutingMethod::Time {
+        return scout_player(player_id, &team_id, &team.league, CONFIG.scouting.days).await
+    }
+
+    let cost = CONFIG.scouting.cost;
+
+    *team = Team::modify_gold(
+        db, &team_id, -cost,
+        TransactionReason::Scouting,
+        TransactionDetails::player(player_id),
+    ).await?;
+
+    // Nothing was learned, so the team gets its gold back
+    if let Err(error) = scout_player(player_id, &team_id, &team.league, 0).await {
+        *team = Team::modify_gold(
+            db, &team_id, cost,
+            TransactionReason::Scouting,
+            TransactionDetails {
+                note: Some("Refund".to_string()),
+                ..TransactionDetails::player(player_id)
+            },
+        ).await?;
+
+        return Err(error)
+    }
+
+    Ok(())
+}
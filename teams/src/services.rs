@@ -0,0 +1,414 @@
+use async_graphql::{Result, SimpleObject};
+use serde::Deserialize;
+use serde_json::json;
+
+use common::client::query_service;
+use common::enums::{Class, Handedness, RefundPolicy, Trait};
+
+use crate::config::CONFIG;
+
+const PLAYERS: &str = r#"
+query Players($filter: JSONObject) {
+    players(filter: $filter) {
+        id
+        cost
+        class
+        handedness
+        health
+        maxHealth
+        strength
+        dexterity
+        constitution
+        intelligence
+        wisdom
+        charisma
+        traits
+        contract {
+            salary
+        }
+    }
+}
+"#;
+
+const PLAYER: &str = r#"
+query Player($id: ID!) {
+    player(id: $id) {
+        id
+        cost
+        class
+        handedness
+        health
+        maxHealth
+        strength
+        dexterity
+        constitution
+        intelligence
+        wisdom
+        charisma
+        traits
+        contract {
+            salary
+        }
+        league {
+            id
+        }
+        team {
+            id
+        }
+    }
+}
+"#;
+
+const SET_TEAM: &str = r#"
+mutation SetTeam($player: ID!, $team: ID!) {
+    setTeam(player: $player, team: $team) {
+        id
+    }
+}
+"#;
+
+const RELEASE_PLAYER: &str = r#"
+mutation ReleasePlayer($player: ID!) {
+    releasePlayer(player: $player) {
+        id
+    }
+}
+"#;
+
+const AWARD_GAME_EXPERIENCE: &str = r#"
+mutation AwardGameExperience($game: ID!, $players: [ID!]!, $won: Boolean!) {
+    awardGameExperience(game: $game, players: $players, won: $won) {
+        id
+    }
+}
+"#;
+
+const AWARD_FARM_EXPERIENCE: &str = r#"
+mutation AwardFarmExperience($game: ID!, $players: [ID!]!) {
+    awardFarmExperience(game: $game, players: $players) {
+        id
+    }
+}
+"#;
+
+const APPLY_GAME_MORALE: &str = r#"
+mutation ApplyGameMorale($team: ID!, $game: ID!, $played: [ID!]!, $won: Boolean!) {
+    applyGameMorale(team: $team, game: $game, played: $played, won: $won) {
+        kind
+    }
+}
+"#;
+
+const NEGOTIATE_CONTRACT: &str = r#"
+mutation NegotiateContract($player: ID!, $team: ID!, $league: ID!, $terms: ContractTermsInput!) {
+    negotiateContract(player: $player, team: $team, league: $league, terms: $terms) {
+        accepted
+        askingSalary
+        message
+    }
+}
+"#;
+
+const EXERCISE_OPTION: &str = r#"
+mutation ExerciseOption($player: ID!, $team: ID!, $exercise: Boolean!) {
+    exerciseOption(player: $player, team: $team, exercise: $exercise) {
+        id
+    }
+}
+"#;
+
+const TRADE_ISSUES: &str = r#"
+query TradeIssues($team: ID!, $players: [ID!]!) {
+    tradeIssues(team: $team, players: $players)
+}
+"#;
+
+const SCOUT_PLAYER: &str = r#"
+mutation ScoutPlayer($player: ID!, $team: ID!, $league: ID!, $days: Int!) {
+    scoutPlayer(player: $player, team: $team, league: $league, days: $days) {
+        id
+    }
+}
+"#;
+
+const DESIGNATED_HITTER: &str = r#"
+query DesignatedHitter($id: ID!) {
+    league(id: $id) {
+        designatedHitter
+    }
+}
+"#;
+
+const RELEASE_REFUND: &str = r#"
+query ReleaseRefund($id: ID!) {
+    league(id: $id) {
+        releaseRefund
+    }
+}
+"#;
+
+const KEEPER_RULES: &str = r#"
+query KeeperRules($id: ID!) {
+    league(id: $id) {
+        keepersOpen
+        keeperRules {
+            keepersPerTeam
+            costInflation
+            dynasty
+        }
+    }
+}
+"#;
+
+/// The parts of a player the teams service needs to make roster decisions
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSummary {
+    pub id: String,
+    pub cost: i64,
+    pub class: Class,
+    pub handedness: Handedness,
+    pub health: i64,
+    pub max_health: i64,
+    pub strength: i64,
+    pub dexterity: i64,
+    pub constitution: i64,
+    pub intelligence: i64,
+    pub wisdom: i64,
+    pub charisma: i64,
+    pub traits: Vec<Trait>,
+    #[serde(default)]
+    pub contract: Option<ContractSummary>,
+    /// Only fetched for a single player
+    #[serde(default)]
+    pub league: Option<EntityId>,
+    #[serde(default)]
+    pub team: Option<EntityId>,
+}
+
+impl PlayerSummary {
+    pub fn in_league(&self, league_id: &str) -> bool {
+        self.league.as_ref().map(|league| league.id == league_id).unwrap_or(false)
+    }
+
+    /// In the league and assigned to the team by the players service
+    pub fn belongs_to(&self, league_id: &str, team_id: &str) -> bool {
+        self.in_league(league_id) && self.team.as_ref().map(|team| team.id == team_id).unwrap_or(false)
+    }
+}
+
+/// A federated reference to another service's entity
+#[derive(Clone, Deserialize)]
+pub struct EntityId {
+    pub id: String,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractSummary {
+    /// None when the player is paid at the league's rate
+    pub salary: Option<i64>,
+}
+
+/// A player's answer to a contract offer
+#[derive(Clone, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct NegotiationResult {
+    pub accepted: bool,
+    pub asking_salary: i64,
+    pub message: String,
+}
+
+async fn fetch_players(filter: serde_json::Value) -> Result<Vec<PlayerSummary>> {
+    let data = query_service(
+        &CONFIG.services.players,
+        PLAYERS,
+        json!({ "filter": filter }),
+    ).await?;
+
+    let players: Vec<PlayerSummary> = serde_json::from_value(data["players"].clone())?;
+
+    Ok(players)
+}
+
+/// A league's keeper settings as reported by the leagues service
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeeperRules {
+    pub keepers_per_team: i64,
+    pub cost_inflation: i64,
+    pub dynasty: bool,
+}
+
+pub async fn fetch_team_players(team_id: &str) -> Result<Vec<PlayerSummary>> {
+    fetch_players(json!({ "team": team_id })).await
+}
+
+/// Players in the league that no team owns and who are still playing
+pub async fn fetch_free_agents(league_id: &str) -> Result<Vec<PlayerSummary>> {
+    fetch_players(json!({ "league": league_id, "team": null, "retired": { "_ne": true } })).await
+}
+
+pub async fn fetch_player(player_id: &str) -> Result<PlayerSummary> {
+    let data = query_service(
+        &CONFIG.services.players,
+        PLAYER,
+        json!({ "id": player_id }),
+    ).await?;
+
+    let player: PlayerSummary = serde_json::from_value(data["player"].clone())?;
+
+    Ok(player)
+}
+
+pub async fn assign_player(player_id: &str, team_id: &str) -> Result<()> {
+    query_service(
+        &CONFIG.services.players,
+        SET_TEAM,
+        json!({ "player": player_id, "team": team_id }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Returns a player to the league's free agent pool
+pub async fn release_player(player_id: &str) -> Result<()> {
+    query_service(
+        &CONFIG.services.players,
+        RELEASE_PLAYER,
+        json!({ "player": player_id }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Moves the morale of everyone on the team after a game
+pub async fn apply_game_morale(team_id: &str, game_id: &str, played: &[String], won: bool) -> Result<()> {
+    query_service(
+        &CONFIG.services.players,
+        APPLY_GAME_MORALE,
+        json!({ "team": team_id, "game": game_id, "played": played, "won": won }),
+    ).await?;
+
+    Ok(())
+}
+
+pub async fn negotiate_contract(
+    player_id: &str,
+    team_id: &str,
+    league_id: &str,
+    terms: serde_json::Value,
+) -> Result<NegotiationResult> {
+    let data = query_service(
+        &CONFIG.services.players,
+        NEGOTIATE_CONTRACT,
+        json!({ "player": player_id, "team": team_id, "league": league_id, "terms": terms }),
+    ).await?;
+
+    let result: NegotiationResult = serde_json::from_value(data["negotiateContract"].clone())?;
+
+    Ok(result)
+}
+
+pub async fn exercise_option(player_id: &str, team_id: &str, exercise: bool) -> Result<()> {
+    query_service(
+        &CONFIG.services.players,
+        EXERCISE_OPTION,
+        json!({ "player": player_id, "team": team_id, "exercise": exercise }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Contract problems stopping the team from trading these players away
+pub async fn fetch_trade_issues(team_id: &str, player_ids: &[String]) -> Result<Vec<String>> {
+    if player_ids.is_empty() {
+        return Ok(Vec::new())
+    }
+
+    let data = query_service(
+        &CONFIG.services.players,
+        TRADE_ISSUES,
+        json!({ "team": team_id, "players": player_ids }),
+    ).await?;
+
+    let issues: Vec<String> = serde_json::from_value(data["tradeIssues"].clone())?;
+
+    Ok(issues)
+}
+
+pub async fn scout_player(player_id: &str, team_id: &str, league_id: &str, days: i64) -> Result<()> {
+    query_service(
+        &CONFIG.services.players,
+        SCOUT_PLAYER,
+        json!({ "player": player_id, "team": team_id, "league": league_id, "days": days }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Gives everyone who played in a game their experience
+pub async fn award_game_experience(game_id: &str, player_ids: &[String], won: bool) -> Result<()> {
+    if player_ids.is_empty() {
+        return Ok(())
+    }
+
+    query_service(
+        &CONFIG.services.players,
+        AWARD_GAME_EXPERIENCE,
+        json!({ "game": game_id, "players": player_ids, "won": won }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Farm players develop a little every game their team plays
+pub async fn award_farm_experience(game_id: &str, player_ids: &[String]) -> Result<()> {
+    if player_ids.is_empty() {
+        return Ok(())
+    }
+
+    query_service(
+        &CONFIG.services.players,
+        AWARD_FARM_EXPERIENCE,
+        json!({ "game": game_id, "players": player_ids }),
+    ).await?;
+
+    Ok(())
+}
+
+/// Whether the league plays with a designated hitter, which decides what a legal roster is
+pub async fn fetch_designated_hitter(league_id: &str) -> Result<bool> {
+    let data = query_service(
+        &CONFIG.services.leagues,
+        DESIGNATED_HITTER,
+        json!({ "id": league_id }),
+    ).await?;
+
+    Ok(data["league"]["designatedHitter"].as_bool().unwrap_or(false))
+}
+
+/// How much of a released player's cost the league hands back to the team
+pub async fn fetch_release_refund(league_id: &str) -> Result<RefundPolicy> {
+    let data = query_service(
+        &CONFIG.services.leagues,
+        RELEASE_REFUND,
+        json!({ "id": league_id }),
+    ).await?;
+
+    Ok(serde_json::from_value(data["league"]["releaseRefund"].clone()).unwrap_or_default())
+}
+
+/// The league's keeper rules and whether keepers can still be chosen
+pub async fn fetch_keeper_rules(league_id: &str) -> Result<(KeeperRules, bool)> {
+    let data = query_service(
+        &CONFIG.services.leagues,
+        KEEPER_RULES,
+        json!({ "id": league_id }),
+    ).await?;
+
+    let rules: KeeperRules = serde_json::from_value(data["league"]["keeperRules"].clone())?;
+    let open = data["league"]["keepersOpen"].as_bool().unwrap_or(false);
+
+    Ok((rules, open))
+}
@@ -0,0 +1,4 @@
+// actix-web 3 runs on tokio 0.2, the async-std connection brings its own reactor and works there
+pub async fn connection(client: &redis::Client) -> redis::RedisResult<redis::aio::Connection> {
+    client.get_async_std_connection().await
+}
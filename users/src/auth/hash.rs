@@ -2,7 +2,7 @@ use argon2::{self, Config};
 use rand::{RngCore, rngs::OsRng};
 
 pub fn hash_password(password: &str) -> String {
-    let mut r = OsRng::default();
+    let mut r = OsRng;
     // Random bytes.
     let mut salt = vec![0u8; 32];
     r.fill_bytes(&mut salt);
@@ -0,0 +1,112 @@
+use chrono::Utc;
+
+use crate::config::CONFIG;
+
+/// The client address the gateway forwards, for limiting logins per IP
+#[derive(Clone, Debug)]
+pub struct ClientIp(pub String);
+
+/// Why a login was turned away before the password was checked
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LoginBlock {
+    /// The client IP made too many attempts, for any usernames
+    RateLimited,
+    /// The username failed too often and is locked for this many more seconds
+    LockedOut(u64),
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("login:ip:{}", ip)
+}
+
+fn failures_key(username: &str) -> String {
+    format!("login:failures:{}", username)
+}
+
+fn lockout_key(username: &str) -> String {
+    format!("login:lockout:{}", username)
+}
+
+/// Usernames and emails are limited together however they're typed
+pub fn login_name(username_or_email: &str) -> String {
+    username_or_email.trim().to_lowercase()
+}
+
+/// Adds an entry to a sliding window and returns how many are left in it
+async fn record(con: &mut redis::aio::Connection, key: &str) -> redis::RedisResult<u64> {
+    let now = Utc::now().timestamp_millis();
+    let window = CONFIG.login_limits.window_seconds;
+    let start = now - window as i64 * 1000;
+
+    // Scores are times, members only have to be unique
+    let member = format!("{}:{:x}", now, rand::random::<u64>());
+
+    let (count,): (u64,) = redis::pipe()
+        .atomic()
+        .cmd("ZREMRANGEBYSCORE").arg(key).arg("-inf").arg(start).ignore()
+        .cmd("ZADD").arg(key).arg(now).arg(member).ignore()
+        .cmd("ZCARD").arg(key)
+        .cmd("EXPIRE").arg(key).arg(window).ignore()
+        .query_async(con)
+        .await?;
+
+    Ok(count)
+}
+
+/// Counts the attempt against the client IP and checks the username isn't locked
+pub async fn check_login(con: &mut redis::aio::Connection, ip: Option<&ClientIp>, name: &str) -> redis::RedisResult<Option<LoginBlock>> {
+    if let Some(ClientIp(ip)) = ip {
+        if record(con, &ip_key(ip)).await? > CONFIG.login_limits.max_ip_attempts {
+            return Ok(Some(LoginBlock::RateLimited))
+        }
+    }
+
+    let ttl: i64 = redis::cmd("TTL").arg(lockout_key(name)).query_async(con).await?;
+
+    if ttl > 0 {
+        return Ok(Some(LoginBlock::LockedOut(ttl as u64)))
+    }
+
+    Ok(None)
+}
+
+/// Counts a failed login against the username, locking it once it reaches the limit. Returns
+/// whether this failure locked it
+pub async fn record_failure(con: &mut redis::aio::Connection, name: &str) -> redis::RedisResult<bool> {
+    let failures = record(con, &failures_key(name)).await?;
+
+    if failures < CONFIG.login_limits.max_failures {
+        return Ok(false)
+    }
+
+    let _: () = redis::pipe()
+        .atomic()
+        .cmd("SET").arg(lockout_key(name)).arg(failures).arg("EX").arg(CONFIG.login_limits.lockout_minutes * 60).ignore()
+        .cmd("DEL").arg(failures_key(name)).ignore()
+        .query_async(con)
+        .await?;
+
+    Ok(true)
+}
+
+/// A successful login starts the username's count over
+pub async fn clear_failures(con: &mut redis::aio::Connection, name: &str) -> redis::RedisResult<()> {
+    redis::cmd("DEL").arg(failures_key(name)).query_async(con).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_and_emails_share_a_count_however_typed() {
+        assert_eq!(login_name("  Slugger "), "slugger");
+        assert_eq!(login_name("Slugger@Example.com"), "slugger@example.com");
+    }
+
+    #[test]
+    fn limit_keys_keep_ips_and_names_apart() {
+        assert_ne!(ip_key("slugger"), failures_key("slugger"));
+        assert_ne!(failures_key("slugger"), lockout_key("slugger"));
+    }
+}
@@ -1,3 +1,11 @@
+pub mod connection;
 pub mod hash;
+pub mod limits;
+pub mod tokens;
+pub mod totp;
 
-pub use hash::*;
\ No newline at end of file
+pub use connection::*;
+pub use hash::*;
+pub use limits::*;
+pub use tokens::*;
+pub use totp::*;
@@ -0,0 +1,80 @@
+use rand::{RngCore, rngs::OsRng};
+
+/// What a single-use account token proves
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    /// Passed the password check, still needs a two-factor code to log in
+    TwoFactorChallenge,
+}
+
+impl TokenPurpose {
+    fn prefix(&self) -> &'static str {
+        match self {
+            TokenPurpose::PasswordReset => "reset",
+            TokenPurpose::EmailVerification => "verify",
+            TokenPurpose::TwoFactorChallenge => "2fa",
+        }
+    }
+
+    fn token_key(&self, token: &str) -> String {
+        format!("{}:token:{}", self.prefix(), token)
+    }
+
+    fn user_key(&self, user_id: &str) -> String {
+        format!("{}:user:{}", self.prefix(), user_id)
+    }
+}
+
+/// Stores a new token for the user that expires after `ttl` seconds. Only the newest token
+/// for each purpose works, issuing one cancels the last
+pub async fn issue_token(
+    con: &mut redis::aio::Connection,
+    purpose: TokenPurpose,
+    user_id: &str,
+    ttl: u64,
+) -> redis::RedisResult<String> {
+    let mut bytes = vec![0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    let previous: Option<String> = redis::cmd("GET").arg(purpose.user_key(user_id)).query_async(con).await?;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    if let Some(previous) = previous {
+        pipe.cmd("DEL").arg(purpose.token_key(&previous)).ignore();
+    }
+
+    pipe.cmd("SET").arg(purpose.token_key(&token)).arg(user_id).arg("EX").arg(ttl).ignore()
+        .cmd("SET").arg(purpose.user_key(user_id)).arg(&token).arg("EX").arg(ttl).ignore();
+
+    let _: () = pipe.query_async(con).await?;
+
+    Ok(token)
+}
+
+/// The user the token was issued to, if it's still valid. The token is used up either way
+pub async fn consume_token(
+    con: &mut redis::aio::Connection,
+    purpose: TokenPurpose,
+    token: &str,
+) -> redis::RedisResult<Option<String>> {
+    let key = purpose.token_key(token);
+
+    let (user_id,): (Option<String>,) = redis::pipe()
+        .atomic()
+        .cmd("GET").arg(&key)
+        .cmd("DEL").arg(&key).ignore()
+        .query_async(con)
+        .await?;
+
+    if let Some(user_id) = &user_id {
+        let _: () = redis::cmd("DEL").arg(purpose.user_key(user_id)).query_async(con).await?;
+    }
+
+    Ok(user_id)
+}
@@ -0,0 +1,223 @@
+use std::convert::TryInto;
+
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base32::Alphabet;
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use rand::{RngCore, rngs::OsRng};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// RFC 6238 defaults, the ones every authenticator app expects
+const DIGITS: u32 = 6;
+const PERIOD: i64 = 30;
+/// Codes from one period either side still work, to allow for clock drift
+const SKEW: i64 = 1;
+
+const SECRET_ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+
+/// Marks a secret as encrypted, secrets from before encryption are plain base32
+pub const SEALED_PREFIX: &str = "sealed:";
+const NONCE_LENGTH: usize = 12;
+
+/// A new random secret, base32 encoded the way authenticator apps take it
+pub fn generate_secret() -> String {
+    let mut bytes = vec![0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+
+    base32::encode(SECRET_ALPHABET, &bytes)
+}
+
+fn percent_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn cipher(key: &str) -> Aes256Gcm {
+    Aes256Gcm::new(&Sha256::digest(key.as_bytes()))
+}
+
+/// Encrypts a secret for storing with the user, so a database dump doesn't give away codes
+pub fn seal_secret(secret: &str, key: &str) -> Option<String> {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(cipher(key).encrypt(&Nonce::from(nonce), secret.as_bytes()).ok()?);
+
+    Some(format!("{}{}", SEALED_PREFIX, base32::encode(SECRET_ALPHABET, &sealed)))
+}
+
+/// The secret back from `seal_secret`, None if it was sealed with another key or tampered with
+pub fn open_secret(sealed: &str, key: &str) -> Option<String> {
+    let sealed = base32::decode(SECRET_ALPHABET, sealed.strip_prefix(SEALED_PREFIX)?)?;
+
+    if sealed.len() <= NONCE_LENGTH {
+        return None
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    let nonce: [u8; NONCE_LENGTH] = nonce.try_into().ok()?;
+    let secret = cipher(key).decrypt(&Nonce::from(nonce), ciphertext).ok()?;
+
+    String::from_utf8(secret).ok()
+}
+
+/// The otpauth URI authenticator apps read from a QR code
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        PERIOD,
+    )
+}
+
+/// RFC 4226 HOTP, which TOTP runs on the current time step
+fn hotp(key: &[u8], counter: u64) -> Option<u32> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).ok()?;
+    mac.update(&counter.to_be_bytes());
+
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+
+    let binary = ((hash[offset] as u32 & 0x7f) << 24)
+        | ((hash[offset + 1] as u32) << 16)
+        | ((hash[offset + 2] as u32) << 8)
+        | (hash[offset + 3] as u32);
+
+    Some(binary % 10u32.pow(DIGITS))
+}
+
+/// The time step the code belongs to if it's valid now. Callers keep the last step used so
+/// the same code can't be replayed
+pub fn verify_code(secret: &str, code: &str, last_step: i64) -> Option<i64> {
+    let key = base32::decode(SECRET_ALPHABET, secret)?;
+    let code: u32 = code.trim().replace(' ', "").parse().ok()?;
+
+    let current = Utc::now().timestamp() / PERIOD;
+
+    (current - SKEW..=current + SKEW)
+        .filter(|step| *step > last_step)
+        .find(|step| hotp(&key, *step as u64) == Some(code))
+}
+
+/// One-time codes for getting in without the authenticator, shown to the user once
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+
+            let code: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without the dash or case, however the user typed them
+pub fn normalize_recovery_code(code: &str) -> String {
+    let code: String = code.trim().to_lowercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+
+    if code.len() == 10 {
+        format!("{}-{}", &code[..5], &code[5..])
+    } else {
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    fn current_code(secret: &str) -> (String, i64) {
+        let key = base32::decode(SECRET_ALPHABET, secret).unwrap();
+        let step = Utc::now().timestamp() / PERIOD;
+
+        (format!("{:06}", hotp(&key, step as u64).unwrap()), step)
+    }
+
+    #[test]
+    fn hotp_matches_the_rfc_4226_test_values() {
+        assert_eq!(hotp(RFC_KEY, 0), Some(755224));
+        assert_eq!(hotp(RFC_KEY, 1), Some(287082));
+        assert_eq!(hotp(RFC_KEY, 9), Some(520489));
+    }
+
+    #[test]
+    fn the_current_code_is_accepted_once() {
+        let secret = generate_secret();
+        let (code, step) = current_code(&secret);
+
+        // Still inside the skew if the step ticks over between making the code and checking it
+        assert_eq!(verify_code(&secret, &code, 0), Some(step));
+        assert_eq!(verify_code(&secret, &code, step), None);
+    }
+
+    #[test]
+    fn codes_may_be_typed_with_spaces() {
+        let secret = generate_secret();
+        let (code, _) = current_code(&secret);
+
+        let spaced = format!(" {} {} ", &code[..3], &code[3..]);
+
+        assert_eq!(verify_code(&secret, &spaced, 0), verify_code(&secret, &code, 0));
+    }
+
+    #[test]
+    fn malformed_codes_and_secrets_are_rejected() {
+        let secret = generate_secret();
+
+        assert_eq!(verify_code(&secret, "not a code", 0), None);
+        assert_eq!(verify_code("!!not base32!!", "123456", 0), None);
+    }
+
+    #[test]
+    fn sealed_secrets_open_with_the_same_key() {
+        let secret = generate_secret();
+        let sealed = seal_secret(&secret, "key").unwrap();
+
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains(&secret));
+        assert_eq!(open_secret(&sealed, "key"), Some(secret));
+    }
+
+    #[test]
+    fn sealed_secrets_need_the_right_key() {
+        let sealed = seal_secret(&generate_secret(), "key").unwrap();
+
+        assert_eq!(open_secret(&sealed, "another key"), None);
+        assert_eq!(open_secret(&generate_secret(), "key"), None);
+        assert_eq!(open_secret(SEALED_PREFIX, "key"), None);
+    }
+
+    #[test]
+    fn recovery_codes_ignore_case_and_dashes() {
+        assert_eq!(normalize_recovery_code("ABCDE-12345"), "abcde-12345");
+        assert_eq!(normalize_recovery_code(" abcde12345 "), "abcde-12345");
+        assert_eq!(normalize_recovery_code("ab cde-123 45"), "abcde-12345");
+    }
+
+    #[test]
+    fn recovery_codes_of_the_wrong_length_stay_wrong() {
+        assert_eq!(normalize_recovery_code("abc-123"), "abc123");
+    }
+
+    #[test]
+    fn generated_recovery_codes_are_already_normal() {
+        for code in generate_recovery_codes(5) {
+            assert_eq!(normalize_recovery_code(&code), code);
+        }
+    }
+}
@@ -24,6 +24,20 @@ pub struct RedisConfig {
 #[serde(rename_all = "camelCase")]
 pub struct SessionConfig {
     pub secret: String,
+    /// Minutes an access token is good for before it has to be refreshed
+    #[serde(default = "default_access_minutes")]
+    pub access_minutes: i64,
+    /// Days a session survives without being used
+    #[serde(default = "default_refresh_days")]
+    pub refresh_days: u64,
+}
+
+fn default_access_minutes() -> i64 {
+    15
+}
+
+fn default_refresh_days() -> u64 {
+    30
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,6 +46,131 @@ pub struct ServerConfig {
     pub port: u16,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MailTransport {
+    Smtp,
+    /// Writes every email to the outbox directory, for local development and tests
+    File,
+    /// Only logs emails
+    Log,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MailConfig {
+    pub transport: MailTransport,
+    pub from: String,
+    /// Where the frontend lives, links in emails point here
+    pub app_url: String,
+    pub outbox: String,
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        MailConfig {
+            transport: MailTransport::Log,
+            from: String::from("Fantasy Baseball <no-reply@localhost>"),
+            app_url: String::from("http://localhost:8080"),
+            outbox: String::from("outbox"),
+            smtp: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AccountTokensConfig {
+    /// Minutes a password reset link stays valid
+    pub reset_minutes: u64,
+    /// Hours an email verification link stays valid
+    pub verification_hours: u64,
+}
+
+impl Default for AccountTokensConfig {
+    fn default() -> Self {
+        AccountTokensConfig {
+            reset_minutes: 60,
+            verification_hours: 48,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LoginLimitsConfig {
+    /// Seconds the attempt counts look back over
+    pub window_seconds: u64,
+    /// Failed logins for one username within the window before it's locked
+    pub max_failures: u64,
+    /// Logins from one client IP within the window, failed or not
+    pub max_ip_attempts: u64,
+    /// Minutes a username stays locked after too many failures
+    pub lockout_minutes: u64,
+    /// Addresses of the gateway, only requests from these may name the client with X-Forwarded-For
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for LoginLimitsConfig {
+    fn default() -> Self {
+        LoginLimitsConfig {
+            window_seconds: 15 * 60,
+            max_failures: 5,
+            max_ip_attempts: 30,
+            lockout_minutes: 15,
+            trusted_proxies: vec![String::from("127.0.0.1"), String::from("::1")],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TwoFactorConfig {
+    /// The name authenticator apps show next to the code
+    pub issuer: String,
+    /// Minutes between the password and the code before the login has to start over
+    pub challenge_minutes: u64,
+    pub recovery_codes: usize,
+    /// Encrypts the authenticator secrets stored with each user, changing it turns off everyone's
+    /// authenticator
+    pub secret_key: String,
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        TwoFactorConfig {
+            issuer: String::from("Fantasy Baseball"),
+            challenge_minutes: 5,
+            recovery_codes: 10,
+            secret_key: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServicesConfig {
+    pub leagues: String,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        ServicesConfig {
+            leagues: String::from("http://localhost:8001"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
@@ -40,6 +179,16 @@ pub struct Settings {
     pub server: ServerConfig,
     pub redis: RedisConfig,
     pub session: SessionConfig,
+    #[serde(default)]
+    pub mail: MailConfig,
+    #[serde(default)]
+    pub account_tokens: AccountTokensConfig,
+    #[serde(default)]
+    pub login_limits: LoginLimitsConfig,
+    #[serde(default)]
+    pub two_factor: TwoFactorConfig,
+    #[serde(default)]
+    pub services: ServicesConfig,
 }
 
 impl Settings {
@@ -1,15 +1,28 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 
 use crate::auth::*;
 use crate::config::CONFIG;
+use crate::mail::{password_reset_email, verification_email, Email, Mailer};
 use crate::models::*;
+use crate::services::user_deleted;
 use common::*;
 use async_graphql::*;
-use jsonwebtoken::TokenData;
+use async_graphql::guard::Guard;
+use actix_web::web;
+use rand::{RngCore, rngs::OsRng};
 use wither::prelude::*;
 use wither::{bson::doc, mongodb::Database};
 
-use common::filter::process_filter;
+use chrono::{DateTime, TimeZone, Utc};
+use common::auth::{current_user, require_admin, RoleGuard};
+use common::session::{
+    create_session, get_session, refresh_session, remove_session, remove_user_sessions,
+    user_sessions, Session,
+};
+use common::enums::{ApiScope, Role};
+use common::filter::{process_filter, uses_keys};
 
 pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
 
@@ -29,17 +42,25 @@ impl User {
     async fn email(&self) -> &str {
         &self.email
     }
-    async fn role(&self) -> &str {
-        &self.role
+    async fn email_verified(&self) -> bool {
+        self.email_verified
+    }
+    async fn role(&self) -> Role {
+        self.role
+    }
+    async fn two_factor_enabled(&self) -> bool {
+        self.has_two_factor()
+    }
+    async fn computer(&self) -> bool {
+        self.computer
+    }
+    async fn deleted(&self) -> bool {
+        self.deleted
     }
     async fn selected_league(&self) -> Option<League> {
-        if let Some(selected_league) = &self.selected_league {
-            Some(League{
-                id: ID::from(selected_league),
-            })
-        } else {
-            None
-        }
+        self.selected_league.as_ref().map(|selected_league| League {
+            id: ID::from(selected_league),
+        })
     }
 }
 
@@ -51,6 +72,12 @@ pub struct Team {
     pub id: ID,
 }
 
+/// Filtering on these would let anyone probe for password hashes, two-factor secrets and
+/// addresses, the operators because they can run code against the whole document
+const PRIVATE_FILTER_KEYS: [&str; 11] = [
+    "password", "two_factor", "email",
+    "_where", "$where", "_expr", "$expr", "_function", "$function", "_jsonSchema", "$jsonSchema",
+];
 
 pub struct Query;
 
@@ -58,22 +85,30 @@ pub struct Query;
 impl Query {
     /// Get current user info
     async fn me(&self, ctx: &Context<'_>) -> Result<User> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        let user = User::find_by_id(db, ID::from(&current_user.id)).await.ok_or("User not found")?;
+
+        Ok(user)
+    }
+
+    /// Every device the current user is logged in on
+    async fn my_sessions(&self, ctx: &Context<'_>) -> Result<Vec<DeviceSession>> {
         let redis_client: &redis::Client = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        let sessions = user_sessions(redis_client, &current_user.id).await.extend()?;
 
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        Ok(sessions.iter().map(|session| DeviceSession::new(session, &current_user.session)).collect())
+    }
 
-        if let Some(current_user) = maybe_current_user {
-            let db: &Database = ctx.data()?;
-            let id: ID = ID::from(current_user.id);
-            let user = User::find_by_id(db, id).await.unwrap();
+    /// Every API key issued, revoked ones included
+    async fn integrations(&self, ctx: &Context<'_>) -> Result<Vec<Integration>> {
+        let db: &Database = ctx.data()?;
+        require_admin(ctx)?;
 
-            Ok(user)
-        } else {
-            Err("Not logged in".into())
-        }
+        Integration::find_all(db).await
     }
 
     async fn users(&self, ctx: &Context<'_>, filter: Option<HashMap<String, serde_json::Value>>) -> Result<Vec<User>> {
@@ -81,6 +116,10 @@ impl Query {
 
         match filter {
             Some(filter) => {
+                if uses_keys(&filter, &PRIVATE_FILTER_KEYS) {
+                    return Err("Users can't be filtered by password, two-factor or email or with $where, $expr or $function".into())
+                }
+
                 let filter = process_filter(filter)?;
                 let users = User::find_all(db, Some(filter)).await?;
 
@@ -157,6 +196,33 @@ impl Team {
     }
 }
 
+#[Object]
+impl Integration {
+    async fn id(&self) -> ID {
+        if let Some(id) = &self.id {
+            ID::from(id)
+        } else {
+            ID::from("")
+        }
+    }
+    async fn name(&self) -> &str {
+        &self.name
+    }
+    async fn scopes(&self) -> &[ApiScope] {
+        &self.scopes
+    }
+    /// The start of the key, enough to tell keys apart
+    async fn key_prefix(&self) -> String {
+        format!("{}{}", common::api_key::API_KEY_PREFIX, self.key_id)
+    }
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at.0
+    }
+    async fn revoked_at(&self) -> Option<DateTime<Utc>> {
+        self.revoked_at.map(|revoked_at| revoked_at.0)
+    }
+}
+
 pub struct Mutation;
 
 #[Object]
@@ -164,81 +230,597 @@ impl Mutation {
     async fn signup(&self, ctx: &Context<'_>, new_user: SignupInput) -> Result<User, Error> {
         let db: &Database = ctx.data()?;
 
+        validate_username(&new_user.username)?;
+        validate_password(&new_user.password)?;
+
+        if !is_valid_email(&new_user.email) {
+            return Err(invalid_email_error())
+        }
+
+        if User::find_by_username(db, new_user.username.trim()).await?.is_some() {
+            return Err(duplicate_error("username"))
+        }
+
+        if User::find_by_email(db, &new_user.email).await?.is_some() {
+            return Err(duplicate_error("email"))
+        }
+
         let password = hash::hash_password(&new_user.password);
 
-        let mut user = User::new_user(&new_user.username, &new_user.email, &password);
+        let mut user = User::new_user(new_user.username.trim(), &new_user.email, &password);
 
-        if let Ok(_) = user.save(&db, None).await {
+        match user.save(db, None).await {
+            Ok(_) => {
+                // The account is usable straight away, so a failed email shouldn't fail signup
+                if let Err(error) = send_verification(ctx, &user).await {
+                    warn!("Couldn't send a verification email to {}: {}", user.email, error.message);
+                }
+
+                Ok(user)
+            },
+            // Someone else signed up with the same details in the meantime
+            Err(error) => match duplicate_key(&error) {
+                Some(field) => Err(duplicate_error(field)),
+                None => Err(Error::new("Can't signup user")),
+            },
+        }
+    }
+
+    /// Creates an account for a computer manager, nobody can log into it
+    #[graphql(guard(RoleGuard(role = "Role::Admin")))]
+    async fn create_computer_manager(&self, ctx: &Context<'_>, username: String) -> Result<User, Error> {
+        let db: &Database = ctx.data()?;
+
+        let mut secret = vec![0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+
+        let password = hash::hash_password(&format!("{:x?}", secret));
+
+        let mut user = User::new_computer_user(&username, &password);
+
+        if user.save(db, None).await.is_ok() {
             Ok(user)
         } else {
-            Err(Error::new("Can't signup user"))
+            Err(Error::new("Can't create computer manager"))
         }
     }
 
-    async fn login(&self, ctx: &Context<'_>, credentials: LoginInput) -> Result<LoginResponse> {
+    /// Logins are limited per client IP and per account, and an account that keeps failing is
+    /// locked for a while. Every failure is recorded. Accounts with two-factor on get a
+    /// challenge to finish with `completeLogin` instead of a session
+    async fn login(&self, ctx: &Context<'_>, credentials: LoginInput) -> Result<LoginResult> {
         let db: &Database = ctx.data()?;
+        let redis_client: &redis::Client = ctx.data()?;
+        let mut con = connection(redis_client).await?;
 
-        if let Some(user) = User::find_by_username(db, &credentials.username_or_email).await {
-            let clear_password = &credentials.password;
-            let hashed_password = &user.password;
+        let name = login_name(&credentials.username_or_email);
+        let user = User::find_by_username_or_email(db, &credentials.username_or_email).await?;
+        let user_id = user.as_ref().and_then(|user| user.id.as_ref()).map(|id| id.to_string());
 
-            let password_verified = hash::verify_hash(hashed_password, clear_password);
+        // Accounts are limited by id so their username and email share a count
+        let limit_key = user_id.clone().unwrap_or_else(|| name.clone());
 
-            if password_verified {
-                let redis_client: &redis::Client = ctx.data()?;
-                let mut con = redis_client.get_connection()?;
+        check_login_limits(ctx, &mut con, &name, &limit_key, user_id.clone()).await?;
 
-                let user_id = user.id.unwrap().to_string();
+        let user = match user {
+            Some(user) => user,
+            None => {
+                login_failed(ctx, &mut con, &name, &limit_key, None, LoginFailure::UnknownUser).await?;
+
+                return Err(invalid_login_error())
+            },
+        };
+
+        if !hash::verify_hash(&user.password, &credentials.password) {
+            login_failed(ctx, &mut con, &name, &limit_key, user_id, LoginFailure::WrongPassword).await?;
+
+            return Err(invalid_login_error())
+        }
+
+        // Only after the password, so this can't tell anyone which accounts are computers
+        if user.computer {
+            return Err(Error::new("Computer managers can't log in"))
+        }
+
+        let user_id = user_id.unwrap_or_default();
 
-                let session = generate_session(&mut con, &user_id);
-                let token = generate_token(&user_id, &user.username, &user.role, &session, &CONFIG.session.secret);
+        if user.has_two_factor() {
+            let ttl = CONFIG.two_factor.challenge_minutes * 60;
+            let challenge = issue_token(&mut con, TokenPurpose::TwoFactorChallenge, &user_id, ttl).await?;
+
+            return Ok(LoginResult::Challenge(TwoFactorChallenge { challenge }))
+        }
+
+        clear_failures(&mut con, &limit_key).await?;
+
+        let device = credentials.device.as_deref().unwrap_or("Unknown device");
+
+        Ok(LoginResult::Session(start_session(ctx, &user, device).await?))
+    }
+
+    /// Finishes a two-factor login with a code from the authenticator or a recovery code. The
+    /// challenge only gets one try
+    async fn complete_login(&self, ctx: &Context<'_>, input: TwoFactorLoginInput) -> Result<LoginResponse> {
+        let db: &Database = ctx.data()?;
+        let redis_client: &redis::Client = ctx.data()?;
+        let mut con = connection(redis_client).await?;
+
+        let user_id = consume_token(&mut con, TokenPurpose::TwoFactorChallenge, &input.challenge).await?
+            .ok_or("This login has expired, start again")?;
+
+        let mut user = User::find_by_id(db, ID::from(&user_id)).await.ok_or("User not found")?;
+        let name = login_name(&user.username);
+
+        check_login_limits(ctx, &mut con, &name, &user_id, Some(user_id.clone())).await?;
+
+        if !user.check_second_factor(db, &input.code, &CONFIG.two_factor.secret_key).await? {
+            login_failed(ctx, &mut con, &name, &user_id, Some(user_id.clone()), LoginFailure::WrongCode).await?;
+
+            return Err(Error::new("That code doesn't match, log in again"))
+        }
+
+        clear_failures(&mut con, &user_id).await?;
+
+        let device = input.device.as_deref().unwrap_or("Unknown device");
+
+        start_session(ctx, &user, device).await
+    }
+
+    /// Starts linking an authenticator app, finish with `confirmTwoFactor`
+    async fn enroll_two_factor(&self, ctx: &Context<'_>) -> Result<TwoFactorEnrollment> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        let (user, secret) = User::begin_two_factor(db, &current_user.id, &CONFIG.two_factor.secret_key).await?;
+        let provisioning_uri = provisioning_uri(&secret, &user.username, &CONFIG.two_factor.issuer);
+
+        Ok(TwoFactorEnrollment {
+            secret,
+            provisioning_uri,
+        })
+    }
+
+    /// Turns two-factor on with a first code from the app. The recovery codes are only ever
+    /// shown here
+    async fn confirm_two_factor(&self, ctx: &Context<'_>, code: String) -> Result<Vec<String>> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        User::confirm_two_factor(
+            db,
+            &current_user.id,
+            &code,
+            &CONFIG.two_factor.secret_key,
+            CONFIG.two_factor.recovery_codes,
+        ).await
+    }
+
+    /// Needs the password and a current code, so a stolen session can't switch it off
+    async fn disable_two_factor(&self, ctx: &Context<'_>, password: String, code: String) -> Result<User> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        let mut user = User::find_by_id(db, ID::from(&current_user.id)).await.ok_or("User not found")?;
+
+        if !hash::verify_hash(&user.password, &password) {
+            return Err("Incorrect password".into())
+        }
+
+        if !user.check_second_factor(db, &code, &CONFIG.two_factor.secret_key).await? {
+            return Err("That code doesn't match".into())
+        }
+
+        User::disable_two_factor(db, &current_user.id).await
+    }
+
+    /// Trades a refresh token for a new access token, and a new refresh token since each one
+    /// only works once
+    async fn refresh_session(&self, ctx: &Context<'_>, refresh_token: String) -> Result<LoginResponse> {
+        let db: &Database = ctx.data()?;
+        let redis_client: &redis::Client = ctx.data()?;
+
+        let (session, refresh_token) = refresh_session(redis_client, &refresh_token, refresh_ttl())
+            .await
+            .extend()?;
+
+        // Reloading the user picks up a changed role or username in the new token
+        let user = User::find_by_id(db, ID::from(&session.user)).await.ok_or("User not found")?;
+
+        Ok(login_response(&user, &session, refresh_token))
+    }
+
+    /// Ends the session on this device only
+    async fn logout(&self, ctx: &Context<'_>) -> Result<LogoutResponse> {
+        let redis_client: &redis::Client = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        remove_session(redis_client, &current_user.id, &current_user.session).await.extend()?;
+
+        Ok(LogoutResponse{
+            status: "Success".to_string(),
+        })
+    }
+
+    /// Emails a reset link if an account uses the address. Always succeeds so the
+    /// response doesn't give away who has an account
+    async fn request_password_reset(&self, ctx: &Context<'_>, email: String) -> Result<StatusResponse> {
+        let db: &Database = ctx.data()?;
+
+        if let Some(user) = User::find_by_email(db, &email).await? {
+            if !user.computer {
+                let ttl = CONFIG.account_tokens.reset_minutes * 60;
+                let sending = send_token_email(ctx, &user, TokenPurpose::PasswordReset, ttl, password_reset_email)?;
+
+                // Sent in the background, so neither a failure nor how long it takes gives away
+                // whether the account exists
+                actix_web::rt::spawn(async move {
+                    if let Err(error) = sending.await {
+                        error!("Couldn't send a password reset email: {}", error.message);
+                    }
+                });
+            }
+        }
+
+        Ok(StatusResponse {
+            status: "Success".to_string(),
+        })
+    }
+
+    /// Sets a new password from a reset link and logs the user out everywhere
+    async fn reset_password(&self, ctx: &Context<'_>, token: String, password: String) -> Result<StatusResponse> {
+        let db: &Database = ctx.data()?;
+
+        validate_password(&password)?;
+
+        let redis_client: &redis::Client = ctx.data()?;
+        let mut con = connection(redis_client).await?;
+
+        let user_id = consume_token(&mut con, TokenPurpose::PasswordReset, &token).await?
+            .ok_or("This reset link is invalid or has expired")?;
+
+        User::set_password(db, &user_id, &hash::hash_password(&password)).await?;
+
+        remove_user_sessions(redis_client, &user_id).await.extend()?;
+
+        Ok(StatusResponse {
+            status: "Success".to_string(),
+        })
+    }
+
+    /// Sends the logged in user a new verification link
+    async fn request_email_verification(&self, ctx: &Context<'_>) -> Result<StatusResponse> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-                Ok(LoginResponse{
-                    id: ID::from(user_id),
-                    username: user.username.clone(),
-                    email: user.email.clone(),
-                    role: user.role.clone(),
-                    token,
+        let user = User::find_by_id(db, ID::from(&current_user.id)).await.ok_or("User not found")?;
+
+        if user.email_verified {
+            return Err("Email is already verified".into())
+        }
+
+        send_verification(ctx, &user).await?;
+
+        Ok(StatusResponse {
+            status: "Success".to_string(),
+        })
+    }
+
+    async fn verify_email(&self, ctx: &Context<'_>, token: String) -> Result<User> {
+        let db: &Database = ctx.data()?;
+
+        let redis_client: &redis::Client = ctx.data()?;
+        let mut con = connection(redis_client).await?;
+
+        let user_id = consume_token(&mut con, TokenPurpose::EmailVerification, &token).await?
+            .ok_or("This verification link is invalid or has expired")?;
+
+        User::mark_email_verified(db, &user_id).await
+    }
+
+    /// Signs one of the user's devices out
+    async fn revoke_session(&self, ctx: &Context<'_>, id: ID) -> Result<StatusResponse> {
+        let redis_client: &redis::Client = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        match get_session(redis_client, &id).await.extend()? {
+            Some(session) if session.user == current_user.id => {
+                remove_session(redis_client, &current_user.id, &session.id).await.extend()?;
+
+                Ok(StatusResponse {
+                    status: "Success".to_string(),
                 })
-            } else {
-                Err(Error::new("Incorrect password"))
+            },
+            _ => Err("Session not found".into()),
+        }
+    }
+
+    /// Issues an API key for a service account. The key is only ever shown in this response
+    async fn create_integration(&self, ctx: &Context<'_>, name: String, scopes: Vec<ApiScope>) -> Result<IssuedApiKey> {
+        let db: &Database = ctx.data()?;
+        let redis_client: &redis::Client = ctx.data()?;
+        require_admin(ctx)?;
+        let admin = current_user(ctx)?;
+
+        let (integration, key) = Integration::issue(db, redis_client, &name, scopes, &admin.id).await?;
+
+        Ok(IssuedApiKey {
+            integration,
+            key,
+        })
+    }
+
+    /// Stops the integration's key working straight away
+    async fn revoke_integration(&self, ctx: &Context<'_>, id: ID) -> Result<Integration> {
+        let db: &Database = ctx.data()?;
+        let redis_client: &redis::Client = ctx.data()?;
+        require_admin(ctx)?;
+
+        Integration::revoke(db, redis_client, &id).await
+    }
+
+    /// Changing the email address marks it unverified and sends a new verification link
+    async fn update_profile(&self, ctx: &Context<'_>, input: UpdateProfileInput) -> Result<User> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        let mut user = User::find_for_update(db, &current_user.id).await?;
+        let mut email_changed = false;
+
+        if let Some(username) = input.username {
+            let username = username.trim();
+
+            if username != user.username {
+                validate_username(username)?;
+
+                if User::find_by_username(db, username).await?.is_some() {
+                    return Err(duplicate_error("username"))
+                }
+
+                user.username = username.to_string();
+            }
+        }
+
+        if let Some(email) = input.email {
+            let email = normalize_email(&email);
+
+            if email != user.email {
+                if !is_valid_email(&email) {
+                    return Err(invalid_email_error())
+                }
+
+                if User::find_by_email(db, &email).await?.is_some() {
+                    return Err(duplicate_error("email"))
+                }
+
+                user.email = email;
+                user.email_verified = false;
+                email_changed = true;
+            }
+        }
+
+        if let Err(error) = user.save(db, None).await {
+            return match duplicate_key(&error) {
+                Some(field) => Err(duplicate_error(field)),
+                None => Err("Can't update profile".into()),
+            }
+        }
+
+        if email_changed {
+            if let Err(error) = send_verification(ctx, &user).await {
+                warn!("Couldn't send a verification email to {}: {}", user.email, error.message);
             }
-        } else {
-            Err(Error::new("User not found"))
         }
+
+        Ok(user)
     }
 
-    async fn logout(&self, ctx: &Context<'_>) -> Result<LogoutResponse> {
+    /// Keeps the device making the change logged in and signs every other one out
+    async fn change_password(
+        &self, ctx: &Context<'_>,
+        old_password: String,
+        new_password: String,
+    ) -> Result<StatusResponse> {
+        let db: &Database = ctx.data()?;
         let redis_client: &redis::Client = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        let user = User::find_by_id(db, ID::from(&current_user.id)).await.ok_or("User not found")?;
 
-        remove_session(&mut con, &token_data.claims.id);
+        if !hash::verify_hash(&user.password, &old_password) {
+            return Err("Incorrect password".into())
+        }
 
-        Ok(LogoutResponse{
+        validate_password(&new_password)?;
+
+        User::set_password(db, &current_user.id, &hash::hash_password(&new_password)).await?;
+
+        for session in user_sessions(redis_client, &current_user.id).await.extend()? {
+            if session.id != current_user.session {
+                remove_session(redis_client, &current_user.id, &session.id).await.extend()?;
+            }
+        }
+
+        Ok(StatusResponse {
             status: "Success".to_string(),
         })
     }
 
-    // State
-    async fn select_league(&self, ctx: &Context<'_>, id: ID) -> Result<League> {
+    /// Hands the user's leagues and teams on, or removes the ones nothing has been played in,
+    /// then scrubs the account and signs it out everywhere. Needs a code as well when
+    /// two-factor is on
+    async fn delete_account(
+        &self, ctx: &Context<'_>,
+        password: String,
+        code: Option<String>,
+    ) -> Result<StatusResponse> {
+        let db: &Database = ctx.data()?;
         let redis_client: &redis::Client = ctx.data()?;
+        let current_user = current_user(ctx)?;
 
-        let mut con = redis_client.get_connection()?;
-        let token_data = ctx.data_opt::<TokenData<Claims>>().unwrap();
+        let mut user = User::find_by_id(db, ID::from(&current_user.id)).await.ok_or("User not found")?;
 
-        let maybe_current_user = get_current_user(&mut con, token_data);
+        if !hash::verify_hash(&user.password, &password) {
+            return Err("Incorrect password".into())
+        }
 
-        if let Some(current_user) = maybe_current_user {
-            let db: &Database = ctx.data()?;
-            Ok(User::select_league(db, ID::from(current_user.id), id).await.expect("Can't select league"))
-        } else {
-            Err("Not logged in".into())
+        if user.has_two_factor() {
+            let code = code.ok_or("Enter a code from your authenticator app")?;
+
+            if !user.check_second_factor(db, &code, &CONFIG.two_factor.secret_key).await? {
+                return Err("That code doesn't match".into())
+            }
         }
+
+        // Nothing is scrubbed until the other services are done, so a failure can be retried
+        user_deleted(&current_user.id).await?;
+
+        User::anonymize(db, &current_user.id).await?;
+
+        remove_user_sessions(redis_client, &current_user.id).await.extend()?;
+
+        Ok(StatusResponse {
+            status: "Success".to_string(),
+        })
+    }
+
+    // State
+    async fn select_league(&self, ctx: &Context<'_>, id: ID) -> Result<League> {
+        let db: &Database = ctx.data()?;
+        let current_user = current_user(ctx)?;
+
+        User::select_league(db, ID::from(&current_user.id), id).await
+    }
+}
+
+/// Turns the login away when the client IP or the account is over its limits
+async fn check_login_limits(
+    ctx: &Context<'_>,
+    con: &mut redis::aio::Connection,
+    name: &str,
+    limit_key: &str,
+    user_id: Option<String>,
+) -> Result<()> {
+    let db: &Database = ctx.data()?;
+    let ip = ctx.data_opt::<ClientIp>();
+    let ip_address = ip.map(|ClientIp(ip)| ip.clone());
+
+    match check_login(con, ip, limit_key).await? {
+        Some(LoginBlock::RateLimited) => {
+            LoginAttempt::record(db, name, user_id, ip_address, LoginFailure::RateLimited, false).await;
+
+            Err(Error::new("Too many login attempts, try again later")
+                .extend_with(|_, e| e.set("code", "RATE_LIMITED")))
+        },
+        Some(LoginBlock::LockedOut(seconds)) => {
+            LoginAttempt::record(db, name, user_id, ip_address, LoginFailure::LockedOut, false).await;
+
+            let minutes = seconds.div_ceil(60);
+
+            Err(Error::new(format!("Too many failed logins, try again in {} minutes", minutes))
+                .extend_with(|_, e| e.set("code", "ACCOUNT_LOCKED")))
+        },
+        None => Ok(()),
     }
 }
 
+/// Counts the failure towards a lockout and records it
+async fn login_failed(
+    ctx: &Context<'_>,
+    con: &mut redis::aio::Connection,
+    name: &str,
+    limit_key: &str,
+    user_id: Option<String>,
+    failure: LoginFailure,
+) -> Result<()> {
+    let db: &Database = ctx.data()?;
+    let ip_address = ctx.data_opt::<ClientIp>().map(|ClientIp(ip)| ip.clone());
+
+    let locked = record_failure(con, limit_key).await?;
+    LoginAttempt::record(db, name, user_id, ip_address, failure, locked).await;
+
+    Ok(())
+}
+
+async fn start_session(ctx: &Context<'_>, user: &User, device: &str) -> Result<LoginResponse> {
+    let redis_client: &redis::Client = ctx.data()?;
+    let user_id = user.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+
+    let (session, refresh_token) = create_session(redis_client, &user_id, device, refresh_ttl())
+        .await
+        .extend()?;
+
+    Ok(login_response(user, &session, refresh_token))
+}
+
+fn refresh_ttl() -> u64 {
+    CONFIG.session.refresh_days * 24 * 60 * 60
+}
+
+fn login_response(user: &User, session: &Session, refresh_token: String) -> LoginResponse {
+    let user_id = user.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+    let lifetime = CONFIG.session.access_minutes * 60;
+
+    LoginResponse {
+        id: ID::from(&user_id),
+        username: user.username.clone(),
+        email: user.email.clone(),
+        role: user.role,
+        token: generate_token(&user_id, &user.username, user.role, &session.id, lifetime, &CONFIG.session.secret),
+        refresh_token,
+        session: ID::from(&session.id),
+    }
+}
+
+async fn send_verification(ctx: &Context<'_>, user: &User) -> Result<()> {
+    let ttl = CONFIG.account_tokens.verification_hours * 60 * 60;
+
+    send_token_email(ctx, user, TokenPurpose::EmailVerification, ttl, verification_email)?.await
+}
+
+/// Issues the user a token and emails it to them. The mail transport blocks, so sending is
+/// handed to the blocking thread pool and the returned future waits for it
+fn send_token_email(
+    ctx: &Context<'_>,
+    user: &User,
+    purpose: TokenPurpose,
+    ttl: u64,
+    email: fn(&str, &str, &str) -> Email,
+) -> Result<impl Future<Output = Result<()>>> {
+    let redis_client: redis::Client = ctx.data::<redis::Client>()?.clone();
+    let mailer: Arc<dyn Mailer> = ctx.data::<Arc<dyn Mailer>>()?.clone();
+
+    let user_id = user.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+    let to = user.email.clone();
+    let username = user.username.clone();
+
+    Ok(async move {
+        let mut con = connection(&redis_client).await?;
+        let token = issue_token(&mut con, purpose, &user_id, ttl).await?;
+
+        web::block(move || mailer.send(&email(&to, &username, &token)).map_err(|error| error.message))
+            .await
+            .map_err(|error| Error::new(error.to_string()))
+    })
+}
+
+/// The same for an unknown account and a wrong password, so logins can't be used to find out
+/// who has an account
+fn invalid_login_error() -> Error {
+    Error::new("Incorrect username or password").extend_with(|_, e| e.set("code", "INVALID_LOGIN"))
+}
+
+fn invalid_email_error() -> Error {
+    Error::new("Email address isn't valid").extend_with(|_, e| e.set("code", "INVALID_EMAIL"))
+}
+
+fn duplicate_error(field: &str) -> Error {
+    let (message, code) = match field {
+        "email" => ("An account with that email already exists", "EMAIL_TAKEN"),
+        _ => ("That username is taken", "USERNAME_TAKEN"),
+    };
+
+    Error::new(message).extend_with(|_, e| e.set("code", code))
+}
 
 #[derive(InputObject)]
 pub struct SignupInput {
@@ -247,10 +829,18 @@ pub struct SignupInput {
     pub password: String,
 }
 
+#[derive(InputObject)]
+pub struct UpdateProfileInput {
+    pub username: Option<String>,
+    pub email: Option<String>,
+}
+
 #[derive(InputObject)]
 pub struct LoginInput {
     pub username_or_email: String,
     pub password: String,
+    /// Shown in the user's session list, like "Work laptop"
+    pub device: Option<String>,
 }
 
 #[derive(SimpleObject)]
@@ -258,11 +848,77 @@ pub struct LoginResponse {
     pub id: ID,
     pub username: String,
     pub email: String,
-    pub role: String,
+    pub role: Role,
+    /// Short lived, refresh it with the refresh token before it expires
     pub token: String,
+    pub refresh_token: String,
+    pub session: ID,
+}
+
+/// Two-factor accounts get a challenge from `login`, everyone else a session
+#[derive(Union)]
+pub enum LoginResult {
+    Session(LoginResponse),
+    Challenge(TwoFactorChallenge),
+}
+
+#[derive(SimpleObject)]
+pub struct TwoFactorChallenge {
+    /// Pass to `completeLogin` with a code, it expires after a few minutes
+    pub challenge: String,
+}
+
+#[derive(InputObject)]
+pub struct TwoFactorLoginInput {
+    pub challenge: String,
+    /// From the authenticator app, or one of the recovery codes
+    pub code: String,
+    pub device: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct TwoFactorEnrollment {
+    /// For typing into the app by hand
+    pub secret: String,
+    /// For showing as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(SimpleObject)]
+pub struct IssuedApiKey {
+    pub integration: Integration,
+    /// Send as `Authorization: Bearer <key>`, it can't be shown again
+    pub key: String,
+}
+
+#[derive(SimpleObject)]
+pub struct DeviceSession {
+    pub id: ID,
+    pub device: String,
+    pub created_at: DateTime<Utc>,
+    pub last_active: DateTime<Utc>,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+impl DeviceSession {
+    fn new(session: &Session, current_session: &str) -> Self {
+        DeviceSession {
+            id: ID::from(&session.id),
+            device: session.device.clone(),
+            created_at: Utc.timestamp_opt(session.created_at, 0).unwrap(),
+            last_active: Utc.timestamp_opt(session.last_active, 0).unwrap(),
+            current: session.id == current_session,
+        }
+    }
 }
 
 #[derive(SimpleObject)]
 pub struct LogoutResponse {
     pub status: String,
 }
+
+#[derive(SimpleObject)]
+pub struct StatusResponse {
+    pub status: String,
+}
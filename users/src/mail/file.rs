@@ -0,0 +1,38 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_graphql::Result;
+
+use super::{Email, Mailer};
+
+/// Drops every email into a directory, one file each, so tests and local setups can read
+/// the links out of them
+pub struct FileMailer {
+    outbox: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(outbox: &str) -> Self {
+        FileMailer {
+            outbox: PathBuf::from(outbox),
+        }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        fs::create_dir_all(&self.outbox)?;
+
+        let sent_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let recipient: String = email.to.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '@' || c == '.' { c } else { '_' })
+            .collect();
+
+        let path = self.outbox.join(format!("{}-{}.eml", sent_at, recipient));
+
+        fs::write(path, format!("To: {}\nSubject: {}\n\n{}", email.to, email.subject, email.body))?;
+
+        Ok(())
+    }
+}
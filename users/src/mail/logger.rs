@@ -0,0 +1,14 @@
+use async_graphql::Result;
+
+use super::{Email, Mailer};
+
+/// Writes emails to the log instead of sending them
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        info!("Email to {}: {}\n{}", email.to, email.subject, email.body);
+
+        Ok(())
+    }
+}
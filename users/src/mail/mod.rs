@@ -0,0 +1,62 @@
+pub mod file;
+pub mod logger;
+pub mod smtp;
+
+pub use file::FileMailer;
+pub use logger::LogMailer;
+pub use smtp::SmtpMailer;
+
+use async_graphql::Result;
+
+use std::sync::Arc;
+
+use crate::config::{MailTransport, CONFIG};
+
+#[derive(Clone, Debug)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Sends account emails, picked by `mail.transport` in the settings
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<()>;
+}
+
+pub fn mailer_from_config() -> Result<Arc<dyn Mailer>> {
+    match CONFIG.mail.transport {
+        MailTransport::Smtp => {
+            let smtp = CONFIG.mail.smtp.as_ref().ok_or("The smtp transport needs mail.smtp settings")?;
+
+            Ok(Arc::new(SmtpMailer::new(smtp, &CONFIG.mail.from)?))
+        },
+        MailTransport::File => Ok(Arc::new(FileMailer::new(&CONFIG.mail.outbox))),
+        MailTransport::Log => Ok(Arc::new(LogMailer)),
+    }
+}
+
+pub fn password_reset_email(to: &str, username: &str, token: &str) -> Email {
+    Email {
+        to: to.to_string(),
+        subject: String::from("Reset your password"),
+        body: format!(
+            "Hi {},\n\nSomeone asked to reset the password for your account. If it was you, \
+            follow this link within {} minutes:\n\n{}/reset-password?token={}\n\n\
+            If it wasn't, you can ignore this email.\n",
+            username, CONFIG.account_tokens.reset_minutes, CONFIG.mail.app_url, token,
+        ),
+    }
+}
+
+pub fn verification_email(to: &str, username: &str, token: &str) -> Email {
+    Email {
+        to: to.to_string(),
+        subject: String::from("Verify your email"),
+        body: format!(
+            "Hi {},\n\nConfirm this is your email address by following this link within {} hours:\n\n\
+            {}/verify-email?token={}\n",
+            username, CONFIG.account_tokens.verification_hours, CONFIG.mail.app_url, token,
+        ),
+    }
+}
@@ -0,0 +1,39 @@
+use async_graphql::Result;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use super::{Email, Mailer};
+use crate::config::SmtpConfig;
+
+pub struct SmtpMailer {
+    from: String,
+    transport: SmtpTransport,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig, from: &str) -> Result<Self> {
+        let transport = SmtpTransport::relay(&config.host)?
+            .port(config.port)
+            .credentials(Credentials::new(config.username.clone(), config.password.clone()))
+            .build();
+
+        Ok(SmtpMailer {
+            from: from.to_string(),
+            transport,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.parse()?)
+            .to(email.to.parse()?)
+            .subject(email.subject.clone())
+            .body(email.body.clone())?;
+
+        self.transport.send(&message)?;
+
+        Ok(())
+    }
+}
@@ -4,14 +4,18 @@ extern crate log;
 mod auth;
 mod config;
 mod graphql;
+mod mail;
 mod models;
 mod routes;
+mod services;
 
 use crate::config::CONFIG;
 use crate::graphql::{AppSchema, Mutation, Query};
-use crate::models::User;
+use crate::mail::{mailer_from_config, Mailer};
+use crate::models::{Integration, LoginAttempt, User};
 use crate::routes::index;
 
+use common::auth::SessionSecret;
 use actix_web::{middleware, web, App, HttpServer};
 use async_graphql::{
     extensions::{apollo_persisted_queries::ApolloPersistedQueries, ApolloTracing, Logger, apollo_persisted_queries::LruCacheStorage},
@@ -20,10 +24,12 @@ use async_graphql::{
 use wither::mongodb::{Client, Database};
 use wither::Model;
 
+use std::io;
+use std::sync::Arc;
+
 
 // TODO: Make all auth look ups skip mongo and only use redis
 // TODO: Put all auth checks into an is_verified common library
-// TODO: Ensure session look ups are async???
 
 async fn init_db() -> Database {
     let db = Client::with_uri_str(&CONFIG.database.url)
@@ -33,10 +39,35 @@ async fn init_db() -> Database {
 
     info!("Mongo database initialized");
 
+    let sealed = User::seal_stored_secrets(&db, &CONFIG.two_factor.secret_key)
+        .await
+        .expect("Failed encrypting two-factor secrets");
+
+    if sealed > 0 {
+        info!("Encrypted {} two-factor secrets", sealed);
+    }
+
+    // Has to happen before the unique email index is built over the normalised addresses
+    let clashes = User::normalize_stored_emails(&db)
+        .await
+        .expect("Failed normalising emails");
+
+    for (user_id, email) in clashes.iter() {
+        warn!("User {} wasn't normalised, another account already uses {}", user_id, email);
+    }
+
     User::sync(&db)
         .await
         .expect("Failed syncing indexes");
 
+    LoginAttempt::sync(&db)
+        .await
+        .expect("Failed syncing indexes");
+
+    Integration::sync(&db)
+        .await
+        .expect("Failed syncing indexes");
+
     db
 }
 
@@ -50,10 +81,11 @@ async fn init_redis() -> redis::Client {
     client
 }
 
-fn init_graphql(db: &Database, redis: &redis::Client) -> AppSchema {
+fn init_graphql(db: &Database, redis: &redis::Client, mailer: Arc<dyn Mailer>) -> AppSchema {
     let schema = Schema::build(Query, Mutation, EmptySubscription)
         .data(db.clone())
         .data(redis.clone())
+        .data(mailer)
         .extension(ApolloTracing)
         .extension(ApolloPersistedQueries::new(LruCacheStorage::new(256)))
         .extension(Logger)
@@ -79,14 +111,30 @@ async fn main() -> std::io::Result<()> {
     println!("called main()");
     init_logger();
 
+    common::client::set_service_credentials("users", &CONFIG.session.secret);
+
+    if CONFIG.two_factor.secret_key.is_empty() {
+        return Err(io::Error::other("twoFactor.secretKey isn't set, it encrypts two-factor secrets"))
+    }
+
     let db = init_db().await;
     let redis = init_redis().await;
-    let schema = init_graphql(&db, &redis);
+
+    Integration::publish_all(&db, &redis)
+        .await
+        .expect("Failed publishing integration keys");
+
+    let mailer = mailer_from_config()
+        .map_err(|error| io::Error::other(format!("Cannot set up the mailer: {}", error.message)))?;
+
+    let schema = init_graphql(&db, &redis, mailer);
 
     HttpServer::new(move || {
         App::new()
             .wrap(middleware::Logger::default())
             .data(db.clone())
+            .data(redis.clone())
+            .data(SessionSecret(CONFIG.session.secret.clone()))
             .data(schema.clone())
             .service(
                 web::resource("/").route(web::post().to(index))
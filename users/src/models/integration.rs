@@ -0,0 +1,117 @@
+use async_graphql::*;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::{bson::{self, doc, oid::ObjectId}, mongodb::Database};
+
+use common::api_key::{generate_api_key, hash_secret, publish_api_key, remove_api_key, ApiKey};
+use common::enums::ApiScope;
+
+/// A service account that calls the API with a key instead of logging in. The users service
+/// keeps them here and publishes live keys to redis, where every service checks them
+#[derive(Clone, Debug, Model, Serialize, Deserialize)]
+#[model(
+    collection_name = "integrations",
+    index(keys = r#"doc!{"keyId": 1}"#, options = r#"doc!{"unique": true}"#)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Integration {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub key_id: String,
+    pub secret_hash: String,
+    /// The admin who issued the key
+    pub created_by: String,
+    pub created_at: bson::DateTime,
+    pub revoked_at: Option<bson::DateTime>,
+}
+
+impl Integration {
+    fn api_key(&self) -> ApiKey {
+        ApiKey {
+            id: self.key_id.clone(),
+            name: self.name.clone(),
+            scopes: self.scopes.clone(),
+            secret_hash: self.secret_hash.clone(),
+        }
+    }
+
+    /// Issues a key for a new integration, returning it with the full key. The key can't be
+    /// recovered afterwards
+    pub async fn issue(
+        db: &Database,
+        redis_client: &redis::Client,
+        name: &str,
+        scopes: Vec<ApiScope>,
+        created_by: &str,
+    ) -> Result<(Self, String)> {
+        if name.trim().is_empty() {
+            return Err("Integrations need a name".into())
+        }
+
+        if scopes.is_empty() {
+            return Err("Give the key at least one scope".into())
+        }
+
+        let (key_id, secret, key) = generate_api_key();
+
+        let mut integration = Integration {
+            id: None,
+            name: name.trim().to_string(),
+            scopes,
+            key_id,
+            secret_hash: hash_secret(&secret),
+            created_by: created_by.to_string(),
+            created_at: bson::DateTime::from(Utc::now()),
+            revoked_at: None,
+        };
+
+        integration.save(db, None).await?;
+        publish_api_key(redis_client, &integration.api_key()).await?;
+
+        Ok((integration, key))
+    }
+
+    pub async fn revoke(db: &Database, redis_client: &redis::Client, id: &str) -> Result<Self> {
+        let query = doc! { "_id": ObjectId::with_string(id)? };
+
+        let mut integration = Integration::find_one(db, Some(query), None).await?
+            .ok_or("Integration not found")?;
+
+        if integration.revoked_at.is_none() {
+            integration.revoked_at = Some(bson::DateTime::from(Utc::now()));
+            integration.save(db, None).await?;
+        }
+
+        remove_api_key(redis_client, &integration.key_id).await?;
+
+        Ok(integration)
+    }
+
+    /// Newest first, revoked ones included
+    pub async fn find_all(db: &Database) -> Result<Vec<Self>> {
+        let options = wither::mongodb::options::FindOptions::builder()
+            .sort(doc! { "createdAt": -1 })
+            .build();
+
+        let cursor = Integration::find(db, None, options).await?;
+        let integrations: Vec<Integration> = cursor.try_collect().await?;
+
+        Ok(integrations)
+    }
+
+    /// Republishes every live key, so redis can be flushed without locking integrations out
+    pub async fn publish_all(db: &Database, redis_client: &redis::Client) -> Result<()> {
+        let cursor = Integration::find(db, doc! { "revokedAt": null }, None).await?;
+        let integrations: Vec<Integration> = cursor.try_collect().await?;
+
+        for integration in integrations.iter() {
+            publish_api_key(redis_client, &integration.api_key()).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use wither::prelude::*;
+use wither::{bson::{self, doc, oid::ObjectId}, mongodb::Database};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LoginFailure {
+    UnknownUser,
+    WrongPassword,
+    /// The password was right but the two-factor code wasn't
+    WrongCode,
+    /// Turned away because the client IP made too many attempts
+    RateLimited,
+    /// Turned away because the username is locked
+    LockedOut,
+}
+
+/// Audit record of a failed login
+#[derive(Clone, Debug, Model, Serialize, Deserialize)]
+#[model(
+    collection_name = "login_attempts",
+    index(keys = r#"doc!{"name": 1, "createdAt": -1}"#),
+    index(keys = r#"doc!{"ip": 1, "createdAt": -1}"#)
+)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginAttempt {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The username or email as it was typed, lowercased
+    pub name: String,
+    /// The account it belongs to, when there is one
+    pub user: Option<String>,
+    pub ip: Option<String>,
+    pub failure: LoginFailure,
+    /// Whether this failure locked the username
+    pub locked: bool,
+    pub created_at: bson::DateTime,
+}
+
+impl LoginAttempt {
+    /// Recording is best effort, a failed write never changes the login's outcome
+    pub async fn record(
+        db: &Database,
+        name: &str,
+        user: Option<String>,
+        ip: Option<String>,
+        failure: LoginFailure,
+        locked: bool,
+    ) {
+        let mut attempt = LoginAttempt {
+            id: None,
+            name: name.to_string(),
+            user,
+            ip,
+            failure,
+            locked,
+            created_at: bson::DateTime::from(Utc::now()),
+        };
+
+        if let Err(err) = attempt.save(db, None).await {
+            warn!("Failed to record login attempt for {}: {}", name, err);
+        }
+    }
+}
@@ -1,5 +1,9 @@
-pub mod role;
+pub mod integration;
+pub mod login_attempt;
+pub mod two_factor;
 pub mod user;
 
-pub use role::*;
+pub use integration::*;
+pub use login_attempt::*;
+pub use two_factor::*;
 pub use user::*;
@@ -1,10 +0,0 @@
-use async_graphql::Enum;
-use serde::{Deserialize, Serialize};
-use strum_macros::{Display, EnumString};
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Enum, Display, EnumString)]
-#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
-pub enum Role {
-    Admin,
-    User,
-}
\ No newline at end of file
@@ -0,0 +1,172 @@
+use async_graphql::*;
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use wither::bson::{doc, Regex};
+use wither::mongodb::Database;
+use wither::mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use wither::prelude::*;
+
+use crate::auth::{
+    generate_recovery_codes, generate_secret, hash_password, normalize_recovery_code, open_secret,
+    seal_secret, verify_code, verify_hash, SEALED_PREFIX,
+};
+use crate::models::User;
+
+/// An authenticator app linked to the account
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TwoFactor {
+    /// Encrypted with the two-factor secret key, see `seal_secret`
+    pub secret: String,
+    /// Off until the user proves their app works by entering a first code
+    pub enabled: bool,
+    /// Hashed like passwords, each one works once
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+    /// The time step of the last code accepted, so a code can't be used twice
+    #[serde(default)]
+    pub last_step: i64,
+}
+
+impl TwoFactor {
+    fn open(&self, key: &str) -> Result<String> {
+        open_secret(&self.secret, key).ok_or_else(|| "Two-factor secret can't be read".into())
+    }
+}
+
+impl User {
+    pub fn has_two_factor(&self) -> bool {
+        self.two_factor.as_ref().is_some_and(|two_factor| two_factor.enabled)
+    }
+
+    /// Starts enrollment with a new secret, replacing one that was never confirmed
+    pub async fn begin_two_factor(db: &Database, id: &str, key: &str) -> Result<(Self, String)> {
+        let mut user = User::find_for_update(db, id).await?;
+
+        if user.has_two_factor() {
+            return Err("Two-factor authentication is already on".into())
+        }
+
+        let secret = generate_secret();
+
+        user.two_factor = Some(TwoFactor {
+            secret: seal_secret(&secret, key).ok_or("Can't encrypt the two-factor secret")?,
+            enabled: false,
+            recovery_codes: Vec::new(),
+            last_step: 0,
+        });
+        user.save(db, None).await?;
+
+        Ok((user, secret))
+    }
+
+    /// Turns two-factor on once the first code checks out, returning the recovery codes
+    pub async fn confirm_two_factor(
+        db: &Database,
+        id: &str,
+        code: &str,
+        key: &str,
+        recovery_codes: usize,
+    ) -> Result<Vec<String>> {
+        let mut user = User::find_for_update(db, id).await?;
+
+        let two_factor = match user.two_factor.as_mut() {
+            Some(two_factor) if !two_factor.enabled => two_factor,
+            Some(_) => return Err("Two-factor authentication is already on".into()),
+            None => return Err("Start two-factor enrollment first".into()),
+        };
+
+        let step = verify_code(&two_factor.open(key)?, code, two_factor.last_step)
+            .ok_or("That code doesn't match, check the time on your device")?;
+
+        let codes = generate_recovery_codes(recovery_codes);
+
+        two_factor.enabled = true;
+        two_factor.last_step = step;
+        two_factor.recovery_codes = codes.iter().map(|code| hash_password(code)).collect();
+
+        user.save(db, None).await?;
+
+        Ok(codes)
+    }
+
+    /// Checks an authenticator code, or failing that a recovery code, using it up. The code is
+    /// only accepted if it's still unused when the user is updated, so two requests racing with
+    /// the same code can't both get in
+    pub async fn check_second_factor(&mut self, db: &Database, code: &str, key: &str) -> Result<bool> {
+        let id = self.id.clone().ok_or("User hasn't been saved")?;
+
+        let two_factor = match self.two_factor.as_ref() {
+            Some(two_factor) if two_factor.enabled => two_factor,
+            _ => return Ok(false),
+        };
+
+        let (query, update) = if let Some(step) = verify_code(&two_factor.open(key)?, code, two_factor.last_step) {
+            (
+                doc! { "_id": id, "two_factor.enabled": true, "two_factor.last_step": { "$lt": step } },
+                doc! { "$set": { "two_factor.last_step": step } },
+            )
+        } else {
+            let code = normalize_recovery_code(code);
+
+            match two_factor.recovery_codes.iter().find(|hash| verify_hash(hash, &code)) {
+                Some(hash) => (
+                    doc! { "_id": id, "two_factor.enabled": true, "two_factor.recovery_codes": hash },
+                    doc! { "$pull": { "two_factor.recovery_codes": hash } },
+                ),
+                None => return Ok(false),
+            }
+        };
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        // Someone else used the code first
+        match User::find_one_and_update(db, query, update, options).await? {
+            Some(user) => {
+                *self = user;
+
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// Encrypts secrets stored before they were sealed, returns how many there were
+    pub async fn seal_stored_secrets(db: &Database, key: &str) -> Result<u64> {
+        let sealed_prefix = Regex { pattern: format!("^{}", SEALED_PREFIX), options: String::new() };
+        let filter = doc! { "two_factor.secret": { "$exists": true, "$not": sealed_prefix } };
+
+        let cursor = User::find(db, filter, None).await?;
+        let users: Vec<User> = cursor.try_collect().await?;
+
+        let mut sealed = 0;
+
+        for user in users.into_iter() {
+            let (id, secret) = match (&user.id, &user.two_factor) {
+                (Some(id), Some(two_factor)) => (id.clone(), two_factor.secret.clone()),
+                _ => continue,
+            };
+
+            let update = doc! { "$set": { "two_factor.secret": seal_secret(&secret, key).ok_or("Can't encrypt the two-factor secret")? } };
+
+            // Only if the user didn't enroll again in the meantime
+            User::collection(db)
+                .update_one(doc! { "_id": id, "two_factor.secret": secret }, update, None)
+                .await?;
+
+            sealed += 1;
+        }
+
+        Ok(sealed)
+    }
+
+    pub async fn disable_two_factor(db: &Database, id: &str) -> Result<Self> {
+        let mut user = User::find_for_update(db, id).await?;
+
+        user.two_factor = None;
+        user.save(db, None).await?;
+
+        Ok(user)
+    }
+}
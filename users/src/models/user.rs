@@ -1,7 +1,12 @@
+use crate::auth::hash_password;
 use crate::graphql::League;
+use crate::models::TwoFactor;
+
+use common::enums::Role;
 
 use async_graphql::*;
 use futures::stream::TryStreamExt;
+use rand::{RngCore, rngs::OsRng};
 use serde::{Deserialize, Serialize};
 use wither::bson::Document;
 use wither::prelude::*;
@@ -9,18 +14,38 @@ use wither::{
     bson::{doc, oid::ObjectId},
     mongodb::Database,
 };
+use wither::WitherError;
 
 /// User representation
+#[allow(clippy::duplicated_attributes)]
 #[derive(Debug, Model, Serialize, Deserialize)]
-#[model(index(keys = r#"doc!{"username": 1}"#, options = r#"doc!{"unique": true}"#))]
+#[model(
+    index(keys = r#"doc!{"username": 1}"#, options = r#"doc!{"unique": true}"#),
+    index(keys = r#"doc!{"email": 1}"#, options = r#"doc!{"unique": true}"#)
+)]
 pub struct User {
     /// The ID of the model.
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub username: String,
+    /// Always stored lowercase so the unique index ignores case
     pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
     pub password: String,
-    pub role: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub two_factor: Option<TwoFactor>,
+
+    /// Computer managers can't log in, their teams are run by the teams service
+    #[serde(default)]
+    pub computer: bool,
+
+    /// Deleted accounts are kept, scrubbed, so the leagues and teams they played in still
+    /// have someone to point at
+    #[serde(default)]
+    pub deleted: bool,
 
     // move to details, "state"?
     pub selected_league: Option<ObjectId>,
@@ -31,30 +56,136 @@ impl User {
         User {
             id: None,
             username: String::from(username),
-            email: String::from(email),
+            email: normalize_email(email),
+            email_verified: false,
             password: String::from(password),
-            role: String::from("user"),
+            role: Role::User,
+            two_factor: None,
+            computer: false,
+            deleted: false,
             selected_league: None,
         }
     }
 
+    pub fn new_computer_user(username: &str, password: &str) -> Self {
+        let email = format!("{}@computer.invalid", username);
+
+        let mut user = User::new_user(username, &email, password);
+        user.computer = true;
+
+        user
+    }
+
     // query
     pub async fn find_all(db: &Database, filter: Option<Document>) -> Result<Vec::<Self>> {
-        let cursor = User::find(&db, filter, None).await?;
+        let cursor = User::find(db, filter, None).await?;
         let users: Vec<User> = cursor.try_collect().await?;
 
         Ok(users)
     }
 
     pub async fn find_by_id(db: &Database, id: ID) -> Option<Self> {
-        let oid = ObjectId::with_string(&id).expect("Can't get id from String");
-        User::find_one(&db, doc! { "_id": oid }, None).await.unwrap()
+        let oid = ObjectId::with_string(&id).ok()?;
+        User::find_one(db, doc! { "_id": oid }, None).await.ok()?
+    }
+
+    pub async fn find_by_username(db: &Database, username: &str) -> Result<Option<Self>> {
+        Ok(User::find_one(db, doc! { "username": username }, None).await?)
+    }
+
+    pub async fn find_by_email(db: &Database, email: &str) -> Result<Option<Self>> {
+        Ok(User::find_one(db, doc! { "email": normalize_email(email) }, None).await?)
+    }
+
+    /// Usernames can't contain an @, so anything with one is looked up as an email
+    pub async fn find_by_username_or_email(db: &Database, username_or_email: &str) -> Result<Option<Self>> {
+        if username_or_email.contains('@') {
+            User::find_by_email(db, username_or_email).await
+        } else {
+            User::find_by_username(db, username_or_email.trim()).await
+        }
     }
 
-    pub async fn find_by_username(db: &Database, username: &str) -> Option<Self> {
-        User::find_one(&db, doc! { "username": username }, None)
-            .await
-            .unwrap()
+    pub(crate) async fn find_for_update(db: &Database, id: &str) -> Result<Self> {
+        let query = doc! { "_id": ObjectId::with_string(id)? };
+
+        match User::find_one(db, Some(query), None).await? {
+            Some(user) => Ok(user),
+            None => Err(format!("User with id: {:?} not found", id).into()),
+        }
+    }
+
+    /// Takes an already hashed password
+    pub async fn set_password(db: &Database, id: &str, password: &str) -> Result<Self> {
+        let mut user = User::find_for_update(db, id).await?;
+
+        user.password = password.to_string();
+        user.save(db, None).await?;
+
+        Ok(user)
+    }
+
+    pub async fn mark_email_verified(db: &Database, id: &str) -> Result<Self> {
+        let mut user = User::find_for_update(db, id).await?;
+
+        user.email_verified = true;
+        user.save(db, None).await?;
+
+        Ok(user)
+    }
+
+    /// Replaces everything that could identify the person behind the account, and the
+    /// password with one nobody knows
+    pub async fn anonymize(db: &Database, id: &str) -> Result<Self> {
+        let mut user = User::find_for_update(db, id).await?;
+
+        let mut secret = vec![0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+
+        user.username = format!("deleted-{}", id);
+        user.email = format!("{}@deleted.invalid", id);
+        user.email_verified = false;
+        user.password = hash_password(&format!("{:x?}", secret));
+        user.role = Role::User;
+        user.two_factor = None;
+        user.selected_league = None;
+        user.deleted = true;
+        user.save(db, None).await?;
+
+        Ok(user)
+    }
+
+    /// Lowercases emails saved before addresses were normalised, so the unique index sees them
+    /// the way logins do. An account whose address would clash with another's is left as it is
+    /// and returned, with the address it clashes on, to be merged by hand
+    pub async fn normalize_stored_emails(db: &Database) -> Result<Vec<(String, String)>> {
+        // Only addresses normalising would change
+        let filter = doc! { "email": { "$regex": r"[A-Z]|^\s|\s$" } };
+
+        let cursor = User::find(db, filter, None).await?;
+        let users: Vec<User> = cursor.try_collect().await?;
+
+        let mut clashes = Vec::<(String, String)>::new();
+
+        for user in users.into_iter() {
+            let id = match &user.id {
+                Some(id) => id.clone(),
+                None => continue,
+            };
+
+            let email = normalize_email(&user.email);
+
+            if User::find_one(db, doc! { "email": &email }, None).await?.is_some() {
+                clashes.push((id.to_string(), email));
+                continue;
+            }
+
+            User::collection(db)
+                .update_one(doc! { "_id": id }, doc! { "$set": { "email": email } }, None)
+                .await?;
+        }
+
+        Ok(clashes)
     }
 
     pub async fn select_league(db: &Database, user_id: ID, league_id: ID) -> Result<League> {
@@ -74,3 +205,101 @@ impl User {
         }
     }
 }
+
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// A light check that the address could receive mail: something before a single @
+/// and a dotted domain after it
+pub fn is_valid_email(email: &str) -> bool {
+    let email = email.trim();
+
+    if email.len() > 254 || email.chars().any(char::is_whitespace) {
+        return false
+    }
+
+    let mut parts = email.split('@');
+
+    let (local, domain) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(local), Some(domain), None) => (local, domain),
+        _ => return false,
+    };
+
+    !local.is_empty()
+        && domain.contains('.')
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+pub fn validate_username(username: &str) -> Result<()> {
+    let username = username.trim();
+
+    if username.len() < 3 || username.len() > 32 {
+        return Err("Usernames must be between 3 and 32 characters".into())
+    }
+
+    if username.contains('@') || username.chars().any(char::is_whitespace) {
+        return Err("Usernames can't contain spaces or an @".into())
+    }
+
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> Result<()> {
+    if password.chars().count() < 8 {
+        return Err("Passwords must be at least 8 characters".into())
+    }
+
+    Ok(())
+}
+
+/// The unique field a failed insert collided with, if that's why it failed
+pub fn duplicate_key(error: &WitherError) -> Option<&'static str> {
+    let message = error.to_string();
+
+    if !message.contains("E11000") {
+        return None
+    }
+
+    if message.contains("email") {
+        Some("email")
+    } else if message.contains("username") {
+        Some("username")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emails_are_stored_lowercase_and_trimmed() {
+        assert_eq!(normalize_email(" Slugger@Example.COM "), "slugger@example.com");
+    }
+
+    #[test]
+    fn plain_addresses_are_valid() {
+        assert!(is_valid_email("slugger@example.com"));
+        assert!(is_valid_email("first.last+tag@mail.example.co.uk"));
+        assert!(is_valid_email("  slugger@example.com  "));
+    }
+
+    #[test]
+    fn addresses_need_one_at_and_a_dotted_domain() {
+        assert!(!is_valid_email("slugger"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("slugger@localhost"));
+        assert!(!is_valid_email("slugger@example..com"));
+        assert!(!is_valid_email("slugger@@example.com"));
+        assert!(!is_valid_email("slug ger@example.com"));
+    }
+
+    #[test]
+    fn overlong_addresses_are_invalid() {
+        let email = format!("{}@example.com", "a".repeat(250));
+
+        assert!(!is_valid_email(&email));
+    }
+}
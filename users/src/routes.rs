@@ -1,36 +1,51 @@
+use crate::auth::ClientIp;
 use crate::config::CONFIG;
 use crate::AppSchema;
 
-use common::decode_token;
+use common::auth::MaybeUser;
 use actix_web::{HttpRequest, web::Data};
 use async_graphql_actix_web::{Request, Response};
 
+use std::net::IpAddr;
+
 pub async fn index(
     schema: Data<AppSchema>,
     http_req: HttpRequest,
+    current_user: MaybeUser,
     gql_request: Request,
 ) -> Response {
     let mut request = gql_request.into_inner();
 
-    if let Some(token) = get_header_token(http_req) {
-        if let Ok(token) = decode_token(&token, &CONFIG.session.secret) {
-            request = request.data(token);
-        }
+    // The current user is resolved once here, every resolver checks against it
+    if let Some(current_user) = current_user.into_inner() {
+        request = request.data(current_user);
+    }
+
+    if let Some(ip) = client_ip(&http_req) {
+        request = request.data(ClientIp(ip));
     }
 
     schema.execute(request).await.into()
 }
 
-pub fn get_header_token(http_req: HttpRequest) -> Option<String> {
+/// The gateway passes the caller's address along, anyone else gets their peer address so
+/// they can't dodge the login limits by making one up
+fn client_ip(http_req: &HttpRequest) -> Option<String> {
+    let peer = http_req.peer_addr().map(|addr| addr.ip())?;
+
+    let from_gateway = CONFIG.login_limits.trusted_proxies.iter()
+        .filter_map(|proxy| proxy.parse::<IpAddr>().ok())
+        .any(|proxy| proxy == peer);
+
+    if !from_gateway {
+        return Some(peer.to_string())
+    }
+
     http_req
         .headers()
-        .get("Authorization")
-        .and_then(|header_value| {
-            header_value.to_str().ok().map(|s| {
-                let jwt_start_index = "Bearer ".len();
-                let jwt = s[jwt_start_index..s.len()].to_string();
-
-                jwt
-            })
-        })
-}
\ No newline at end of file
+        .get("X-Forwarded-For")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(|ip| ip.trim().to_string())
+        .or_else(|| Some(peer.to_string()))
+}
@@ -0,0 +1,25 @@
+use async_graphql::Result;
+use serde_json::json;
+
+use common::client::query_service;
+
+use crate::config::CONFIG;
+
+const USER_DELETED: &str = r#"
+mutation UserDeleted($user: ID!) {
+    userDeleted(user: $user) {
+        id
+    }
+}
+"#;
+
+/// Has the leagues service hand off or remove the user's leagues, and their teams with them
+pub async fn user_deleted(user_id: &str) -> Result<()> {
+    query_service(
+        &CONFIG.services.leagues,
+        USER_DELETED,
+        json!({ "user": user_id }),
+    ).await?;
+
+    Ok(())
+}